@@ -4,16 +4,29 @@ use bevy::{
     asset::{AssetLoader, AssetPath, BoxedFuture, LoadContext, LoadedAsset},
     prelude::*,
     reflect::TypeUuid,
+    render::texture::ImageType,
 };
 
+use crate::chunks::{self, TileChunk};
+
 #[derive(TypeUuid)]
 #[uuid = "b5114809-ec1c-48ca-84c1-95f446050cee"]
 pub struct LdtkProject {
     pub project: ldtk::Project,
 
     pub spritesheets: HashMap<i64, Handle<TextureAtlas>>,
+    /// Chunks of the tile layers, by level uid and layer definition uid.
+    pub tile_chunks: HashMap<(i64, i64), Vec<TileChunk>>,
     pub int_grid_colors: HashMap<i64, Vec<Handle<ColorMaterial>>>,
+    /// Line color of each entity definition, with its line opacity.
     pub entity_colors: HashMap<i64, Handle<ColorMaterial>>,
+    /// Fill color of each entity definition, with its fill opacity.
+    pub entity_fill_colors: HashMap<i64, Handle<ColorMaterial>>,
+    /// Fill and line meshes of the entity placeholders, by entity definition uid, width and height.
+    pub placeholder_meshes: HashMap<(i64, i64, i64), (Option<Handle<Mesh>>, Option<Handle<Mesh>>)>,
+    pub backgrounds: HashMap<i64, (Handle<TextureAtlas>, ldtk::LevelBackground)>,
+    /// Tiles of the entities rendered as tiles, with their index in the tileset atlas.
+    pub entity_tiles: HashMap<ldtk::EntityRef, Vec<(u32, ldtk::TileQuad)>>,
 }
 
 impl LdtkProject {
@@ -22,8 +35,13 @@ impl LdtkProject {
             project,
 
             spritesheets: HashMap::default(),
+            tile_chunks: HashMap::default(),
             int_grid_colors: HashMap::default(),
             entity_colors: HashMap::default(),
+            entity_fill_colors: HashMap::default(),
+            placeholder_meshes: HashMap::default(),
+            backgrounds: HashMap::default(),
+            entity_tiles: HashMap::default(),
         }
     }
 }
@@ -37,27 +55,110 @@ impl AssetLoader for LdtkProjectLoader {
         load_context: &'a mut LoadContext,
     ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let project: ldtk::Project = serde_json::from_slice(bytes)?;
+            let mut project: ldtk::Project = serde_json::from_slice(bytes)?;
+            project.arrange_levels();
             let mut asset = LdtkProject::new(project);
 
+            let mut entity_quads = Vec::new();
+            for level in &asset.project.levels {
+                for layer in level.layer_instances.iter().flatten() {
+                    for (index, entity) in layer.entity_instances.iter().enumerate() {
+                        let def = match asset.project.defs.entities.iter().find(|d| d.uid == entity.def_uid) {
+                            Some(def) if matches!(def.render_mode, ldtk::RenderMode::Tile) => def,
+                            _ => continue,
+                        };
+                        let entity_ref = ldtk::EntityRef {
+                            level_uid: level.uid,
+                            layer_def_uid: layer.layer_def_uid,
+                            index,
+                        };
+                        for quad in entity.tile_quads(def) {
+                            entity_quads.push((entity_ref, quad));
+                        }
+                    }
+                }
+            }
+
+            let mut tileset_textures = HashMap::new();
             for tileset in &asset.project.defs.tilesets {
                 let path = load_context.path().parent().unwrap().join(&tileset.rel_path);
 
                 let asset_path = AssetPath::new(path, None);
 
+                let texture: Handle<Texture> = load_context.get_handle(asset_path.clone());
+                tileset_textures.insert(tileset.uid, texture.clone());
+
+                // One atlas rect per tile id, so that tile ids can be used as atlas indices
+                let mut atlas =
+                    TextureAtlas::new_empty(texture, Vec2::new(tileset.px_wid as f32, tileset.px_hei as f32));
+                for tile_id in 0..tileset.tile_count() {
+                    let rect = tileset.tile_rect(tile_id);
+                    atlas.add_texture(bevy::sprite::Rect {
+                        min: Vec2::new(rect.x as f32, rect.y as f32),
+                        max: Vec2::new(rect.right() as f32, rect.bottom() as f32),
+                    });
+                }
+
+                // Entity tiles may be cropped, so they get their own rects after the tiles
+                for (entity_ref, quad) in entity_quads.iter().filter(|(_, q)| q.tileset_uid == tileset.uid) {
+                    let index = atlas.textures.len() as u32;
+                    atlas.add_texture(bevy::sprite::Rect {
+                        min: Vec2::new(quad.src.x as f32, quad.src.y as f32),
+                        max: Vec2::new(quad.src.right() as f32, quad.src.bottom() as f32),
+                    });
+                    asset.entity_tiles.entry(*entity_ref).or_default().push((index, *quad));
+                }
+
                 let texture_atlas: Handle<TextureAtlas> = load_context.set_labeled_asset(
                     &format!("Tileset {}", tileset.identifier),
-                    LoadedAsset::new(TextureAtlas::from_grid(
-                        load_context.get_handle(asset_path.clone()),
-                        Vec2::new(tileset.tile_grid_size as f32, tileset.tile_grid_size as f32),
-                        (tileset.px_wid / tileset.tile_grid_size) as usize,
-                        (tileset.px_hei / tileset.tile_grid_size) as usize,
-                    ))
-                    .with_dependency(asset_path),
+                    LoadedAsset::new(atlas).with_dependency(asset_path),
                 );
                 asset.spritesheets.insert(tileset.uid, texture_atlas);
             }
 
+            // Tile layers are drawn as chunk meshes, with one material per tileset and layer opacity
+            let mut tile_materials = HashMap::new();
+            for level in &asset.project.levels {
+                for layer in level.layer_instances.iter().flatten() {
+                    let tileset = match asset
+                        .project
+                        .defs
+                        .tilesets
+                        .iter()
+                        .find(|t| Some(t.uid) == layer.tileset_def_uid)
+                    {
+                        Some(tileset) => tileset,
+                        None => continue,
+                    };
+                    let alpha = (layer.opacity.clamp(0., 1.) * 255.).round() as u8;
+                    let material = tile_materials
+                        .entry((tileset.uid, alpha))
+                        .or_insert_with(|| {
+                            load_context.set_labeled_asset(
+                                &format!("Tileset Material {} {}", tileset.identifier, alpha),
+                                LoadedAsset::new(ColorMaterial {
+                                    color: Color::rgba_u8(255, 255, 255, alpha),
+                                    texture: tileset_textures.get(&tileset.uid).cloned(),
+                                }),
+                            )
+                        })
+                        .clone();
+
+                    let mut chunks = Vec::new();
+                    for ((x, y), mesh) in chunks::chunk_meshes(tileset, layer) {
+                        let mesh = load_context.set_labeled_asset(
+                            &format!("Tile Chunk {} {} {}x{}", level.identifier, layer.identifier, x, y),
+                            LoadedAsset::new(mesh),
+                        );
+                        chunks.push(TileChunk {
+                            mesh,
+                            material: material.clone(),
+                        });
+                    }
+                    asset.tile_chunks.insert((level.uid, layer.layer_def_uid), chunks);
+                }
+            }
+
             for layer in asset
                 .project
                 .defs
@@ -67,8 +168,7 @@ impl AssetLoader for LdtkProjectLoader {
             {
                 let mut colors = Vec::new();
                 for (ix, i) in layer.int_grid_values.iter().enumerate() {
-                    let clr =
-                        Color::hex(&i.color[1..]).map_err(|e| anyhow::format_err!("Failed to parse color: {:?}", e))?;
+                    let clr: Color = i.color.into();
                     let clr_mat = load_context.set_labeled_asset(
                         &format!(
                             "Layer Color {}x{}",
@@ -83,13 +183,85 @@ impl AssetLoader for LdtkProjectLoader {
             }
 
             for entity in &asset.project.defs.entities {
-                let clr = Color::hex(&entity.color[1..])
-                    .map_err(|e| anyhow::format_err!("Failed to parse color: {:?}", e))?;
+                let mut clr: Color = entity.color.into();
+                let mut fill_clr = clr;
+                clr.set_a(entity.line_opacity as f32);
+                fill_clr.set_a(entity.fill_opacity as f32);
                 let clr_mat = load_context.set_labeled_asset(
                     &format!("Entity Color {}", entity.identifier,),
                     LoadedAsset::new(ColorMaterial::from(clr)),
                 );
                 asset.entity_colors.insert(entity.uid, clr_mat);
+
+                let fill_mat = load_context.set_labeled_asset(
+                    &format!("Entity Fill Color {}", entity.identifier,),
+                    LoadedAsset::new(ColorMaterial::from(fill_clr)),
+                );
+                asset.entity_fill_colors.insert(entity.uid, fill_mat);
+            }
+
+            // Placeholder meshes depend on the entity size, which instances can override
+            for level in &asset.project.levels {
+                for entity in level.layer_instances.iter().flatten().flat_map(|l| &l.entity_instances) {
+                    let key = (entity.def_uid, entity.width, entity.height);
+                    let def = match asset.project.defs.entities.iter().find(|d| d.uid == entity.def_uid) {
+                        Some(def) if !asset.placeholder_meshes.contains_key(&key) => def,
+                        _ => continue,
+                    };
+                    let label = format!(
+                        "Entity Placeholder {} {}x{}",
+                        def.identifier, entity.width, entity.height
+                    );
+                    let fill = crate::placeholder::fill_mesh(def, entity.width, entity.height)
+                        .map(|mesh| load_context.set_labeled_asset(&format!("{} Fill", label), LoadedAsset::new(mesh)));
+                    let line = crate::placeholder::line_mesh(def, entity.width, entity.height)
+                        .map(|mesh| load_context.set_labeled_asset(&format!("{} Line", label), LoadedAsset::new(mesh)));
+                    asset.placeholder_meshes.insert(key, (fill, line));
+                }
+            }
+
+            // Background images are loaded here, as their size is needed to place them. A missing or broken image
+            // only loses the background of its level.
+            let base_dir = load_context.path().parent().unwrap().to_path_buf();
+            for level in &asset.project.levels {
+                let path = match &level.bg_rel_path {
+                    Some(rel_path) => base_dir.join(rel_path),
+                    None => continue,
+                };
+                let bytes = match load_context.read_asset_bytes(&path).await {
+                    Ok(bytes) => bytes,
+                    Err(err) => {
+                        warn!("Skipping background of level {}: {}", level.identifier, err);
+                        continue;
+                    }
+                };
+                let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("png");
+                let texture = match Texture::from_buffer(&bytes, ImageType::Extension(extension)) {
+                    Ok(texture) => texture,
+                    Err(err) => {
+                        warn!("Skipping background of level {}: {}", level.identifier, err);
+                        continue;
+                    }
+                };
+                let size = Vec2::new(texture.size.width as f32, texture.size.height as f32);
+                let background = match level.background(&base_dir, size.x as f64, size.y as f64) {
+                    Some(background) => background,
+                    None => continue,
+                };
+
+                let texture = load_context
+                    .set_labeled_asset(&format!("Background {}", level.identifier), LoadedAsset::new(texture));
+                let [x, y, width, height] = background.crop;
+                let mut texture_atlas = TextureAtlas::new_empty(texture, size);
+                texture_atlas.add_texture(bevy::sprite::Rect {
+                    min: Vec2::new(x as f32, y as f32),
+                    max: Vec2::new((x + width) as f32, (y + height) as f32),
+                });
+                let texture_atlas = load_context.set_labeled_asset(
+                    &format!("Background Atlas {}", level.identifier),
+                    LoadedAsset::new(texture_atlas),
+                );
+                asset.backgrounds.insert(level.uid, (texture_atlas, background));
             }
 
             load_context.set_default_asset(LoadedAsset::new(asset));
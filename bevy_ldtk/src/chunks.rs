@@ -0,0 +1,99 @@
+//! Tile layers batched into meshes of `CHUNK_SIZE` x `CHUNK_SIZE` tiles.
+//!
+//! Meshes are in level pixels, y pointing up, and drawn with the sprite pipeline at a sprite size of 1.
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, Mesh},
+        pipeline::PrimitiveTopology,
+    },
+};
+use ldtk::{LayerInstance, TileInstance, TilesetDefinition};
+use std::collections::BTreeMap;
+
+/// Width and height of chunks, in tiles.
+pub const CHUNK_SIZE: i64 = 32;
+
+/// A part of a tile layer drawn as a single mesh.
+#[derive(Clone, Debug)]
+pub struct TileChunk {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<ColorMaterial>,
+}
+
+#[derive(Default)]
+struct ChunkBuilder {
+    positions: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl ChunkBuilder {
+    fn tile(&mut self, tileset: &TilesetDefinition, layer: &LayerInstance, tile: &TileInstance) {
+        let (src_x, src_y, x, y) = match (&tile.src[..], &tile.px[..]) {
+            ([src_x, src_y], [x, y]) => (*src_x, *src_y, *x, *y),
+            _ => return,
+        };
+        let size = layer.grid_size as f32;
+        let x = (x + layer.px_total_offset_x) as f32;
+        let y = (y + layer.px_total_offset_y) as f32;
+
+        let (width, height) = (tileset.px_wid.max(1) as f32, tileset.px_hei.max(1) as f32);
+        let grid = tileset.tile_grid_size as f32;
+        let (mut u0, mut v0) = (src_x as f32 / width, src_y as f32 / height);
+        let (mut u1, mut v1) = (u0 + grid / width, v0 + grid / height);
+        if tile.f & 0b01 != 0 {
+            std::mem::swap(&mut u0, &mut u1);
+        }
+        if tile.f & 0b10 != 0 {
+            std::mem::swap(&mut v0, &mut v1);
+        }
+
+        let first = self.positions.len() as u32;
+        self.positions.extend_from_slice(&[
+            [x, -y - size, 0.],
+            [x + size, -y - size, 0.],
+            [x + size, -y, 0.],
+            [x, -y, 0.],
+        ]);
+        self.uvs.extend_from_slice(&[[u0, v1], [u1, v1], [u1, v0], [u0, v0]]);
+        self.indices
+            .extend_from_slice(&[first, first + 1, first + 2, first, first + 2, first + 3]);
+    }
+
+    fn into_mesh(self) -> Mesh {
+        let count = self.positions.len();
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; count]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+/// Tiles drawn by a layer: its own tiles for tile layers, its rule tiles for auto and IntGrid layers.
+fn layer_tiles(layer: &LayerInstance) -> &[TileInstance] {
+    match &layer.layer_instance_type[..] {
+        "Tiles" => &layer.grid_tiles,
+        "AutoLayer" | "IntGrid" => &layer.auto_layer_tiles,
+        _ => &[],
+    }
+}
+
+/// Meshes of the tiles of `layer`, by chunk coordinates. Tiles keep their draw order within a chunk.
+pub(crate) fn chunk_meshes(tileset: &TilesetDefinition, layer: &LayerInstance) -> Vec<((i64, i64), Mesh)> {
+    let chunk_px = (CHUNK_SIZE * layer.grid_size).max(1);
+    let mut chunks: BTreeMap<(i64, i64), ChunkBuilder> = BTreeMap::new();
+    for tile in layer_tiles(layer) {
+        if let [x, y] = tile.px[..] {
+            let key = (x.div_euclid(chunk_px), y.div_euclid(chunk_px));
+            chunks.entry(key).or_default().tile(tileset, layer, tile);
+        }
+    }
+    chunks
+        .into_iter()
+        .map(|(key, chunk)| (key, chunk.into_mesh()))
+        .collect()
+}
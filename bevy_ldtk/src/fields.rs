@@ -0,0 +1,84 @@
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use ldtk::FieldShape;
+
+/// Path of an entity, from its first `PointPath` or `PointPathLoop` field. Points are in world space, starting
+/// with the entity position.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PatrolPath {
+    pub field: String,
+    pub points: Vec<Vec2>,
+    /// Whether the last point links back to the first one.
+    pub looped: bool,
+}
+
+/// Radius of an entity in pixels, from its first `RadiusPx` or `RadiusGrid` field.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldRadius {
+    pub field: String,
+    pub radius: f32,
+}
+
+/// Components resolved from the entity fields displayed as shapes in the editor.
+#[derive(Clone, Debug, Default)]
+pub struct FieldComponents {
+    pub patrol_path: Option<PatrolPath>,
+    pub radius: Option<FieldRadius>,
+}
+
+impl FieldComponents {
+    /// Resolves the fields of `entity`, found in `layer` of `level`. Level positions are moved by `offset`
+    /// pixels, for levels not rendered at their world position.
+    pub(crate) fn new(
+        project: &ldtk::Project,
+        level: &ldtk::Level,
+        layer: &ldtk::LayerInstance,
+        entity: &ldtk::EntityInstance,
+        offset: Vec2,
+    ) -> Self {
+        let to_world = |points: Vec<[f64; 2]>| -> Vec<Vec2> {
+            points
+                .into_iter()
+                .map(|[x, y]| Vec2::new(x as f32 + offset.x, -(y as f32 + offset.y)))
+                .collect()
+        };
+
+        let mut components = Self::default();
+        for field in &entity.field_instances {
+            let field_name = field.identifier.clone();
+            match entity.field_shape(project, level, layer, &field.identifier) {
+                Some(FieldShape::Path(points)) if components.patrol_path.is_none() => {
+                    components.patrol_path = Some(PatrolPath {
+                        field: field_name,
+                        points: to_world(points),
+                        looped: false,
+                    })
+                }
+                Some(FieldShape::Loop(points)) if components.patrol_path.is_none() => {
+                    components.patrol_path = Some(PatrolPath {
+                        field: field_name,
+                        points: to_world(points),
+                        looped: true,
+                    })
+                }
+                Some(FieldShape::Radius(radius)) if components.radius.is_none() => {
+                    components.radius = Some(FieldRadius {
+                        field: field_name,
+                        radius: radius as f32,
+                    })
+                }
+                _ => {}
+            }
+        }
+        components
+    }
+
+    /// Inserts the resolved components on a spawned entity.
+    pub fn insert(&self, entity: &mut EntityCommands) {
+        if let Some(patrol_path) = &self.patrol_path {
+            entity.insert(patrol_path.clone());
+        }
+        if let Some(radius) = &self.radius {
+            entity.insert(radius.clone());
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+/// Values of a spawned IntGrid layer, on its layer entity.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct IntGridLayer {
+    pub identifier: String,
+    pub level_uid: i64,
+    pub c_wid: i64,
+    pub c_hei: i64,
+    pub grid_size: i64,
+    /// Cell values from left to right and top to bottom, `0` meaning empty.
+    pub values: Vec<i64>,
+    /// Identifiers of the values, by value.
+    pub value_identifiers: Vec<(i64, String)>,
+    /// Top-left corner of cell (0, 0), relative to the layer entity.
+    pub origin: Vec2,
+}
+
+impl IntGridLayer {
+    pub(crate) fn new(project: &ldtk::Project, level: &ldtk::Level, layer: &ldtk::LayerInstance, origin: Vec2) -> Self {
+        let value_identifiers = project
+            .defs
+            .layers
+            .iter()
+            .find(|def| def.uid == layer.layer_def_uid)
+            .map(|def| {
+                def.int_grid_values
+                    .iter()
+                    .filter_map(|v| Some((v.value, v.identifier.clone()?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            identifier: layer.identifier.clone(),
+            level_uid: level.uid,
+            c_wid: layer.c_wid,
+            c_hei: layer.c_hei,
+            grid_size: layer.grid_size,
+            values: layer.int_grid_csv.clone(),
+            value_identifiers,
+            origin,
+        }
+    }
+
+    /// Value of cell (`cx`, `cy`), or `None` outside the layer.
+    pub fn value(&self, cx: i64, cy: i64) -> Option<i64> {
+        if cx < 0 || cy < 0 || cx >= self.c_wid || cy >= self.c_hei {
+            return None;
+        }
+        self.values.get((cx + cy * self.c_wid) as usize).copied()
+    }
+
+    /// Identifier of `value`, if the layer definition names it.
+    pub fn value_identifier(&self, value: i64) -> Option<&str> {
+        self.value_identifiers
+            .iter()
+            .find(|(v, _)| *v == value)
+            .map(|(_, identifier)| identifier.as_str())
+    }
+
+    /// The cell holding `position`, relative to the layer entity, even if outside the layer.
+    pub fn cell_at(&self, position: Vec2) -> (i64, i64) {
+        let grid_size = self.grid_size.max(1) as f32;
+        (
+            ((position.x - self.origin.x) / grid_size).floor() as i64,
+            ((self.origin.y - position.y) / grid_size).floor() as i64,
+        )
+    }
+
+    /// Center of cell (`cx`, `cy`), relative to the layer entity.
+    pub fn cell_center(&self, cx: i64, cy: i64) -> Vec2 {
+        let grid_size = self.grid_size as f32;
+        Vec2::new(
+            self.origin.x + (cx as f32 + 0.5) * grid_size,
+            self.origin.y - (cy as f32 + 0.5) * grid_size,
+        )
+    }
+}
+
+/// A cell of a spawned IntGrid layer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct IntGridCell {
+    pub layer: Entity,
+    pub level_uid: i64,
+    pub cx: i64,
+    pub cy: i64,
+    /// The cell value, `0` meaning empty.
+    pub value: i64,
+}
+
+/// Queries the IntGrid layers of all spawned levels, in world positions.
+#[derive(SystemParam)]
+pub struct LdtkGrid<'a> {
+    layers: Query<'a, (Entity, &'static IntGridLayer, &'static GlobalTransform)>,
+}
+
+impl<'a> LdtkGrid<'a> {
+    /// Cells at the world `position`, one per spawned IntGrid layer covering it.
+    pub fn cells_at(&self, position: Vec2) -> Vec<IntGridCell> {
+        self.layers
+            .iter()
+            .filter_map(|(entity, layer, transform)| layer_cell(entity, layer, transform, position))
+            .collect()
+    }
+
+    /// Cell of the layer named `identifier` at the world `position`, if a spawned level has one there.
+    pub fn cell_at(&self, identifier: &str, position: Vec2) -> Option<IntGridCell> {
+        self.layers
+            .iter()
+            .filter(|(_, layer, _)| layer.identifier == identifier)
+            .find_map(|(entity, layer, transform)| layer_cell(entity, layer, transform, position))
+    }
+
+    /// Value of the layer named `identifier` at the world `position`, `0` if empty or outside all levels.
+    pub fn value_at(&self, identifier: &str, position: Vec2) -> i64 {
+        self.cell_at(identifier, position).map_or(0, |cell| cell.value)
+    }
+
+    /// Cell (`cx`, `cy`) of the IntGrid layer entity `layer`, or `None` outside the layer.
+    pub fn cell(&self, layer: Entity, cx: i64, cy: i64) -> Option<IntGridCell> {
+        let (_, int_grid, _) = self.layers.get(layer).ok()?;
+        Some(IntGridCell {
+            layer,
+            level_uid: int_grid.level_uid,
+            cx,
+            cy,
+            value: int_grid.value(cx, cy)?,
+        })
+    }
+
+    /// World position of the center of cell (`cx`, `cy`) of the IntGrid layer entity `layer`.
+    pub fn cell_center(&self, layer: Entity, cx: i64, cy: i64) -> Option<Vec2> {
+        let (_, int_grid, transform) = self.layers.get(layer).ok()?;
+        let center = int_grid.cell_center(cx, cy).extend(0.);
+        Some(transform.compute_matrix().transform_point3(center).truncate())
+    }
+}
+
+fn layer_cell(
+    entity: Entity,
+    layer: &IntGridLayer,
+    transform: &GlobalTransform,
+    position: Vec2,
+) -> Option<IntGridCell> {
+    let local = transform
+        .compute_matrix()
+        .inverse()
+        .transform_point3(position.extend(0.))
+        .truncate();
+    let (cx, cy) = layer.cell_at(local);
+    Some(IntGridCell {
+        layer: entity,
+        level_uid: layer.level_uid,
+        cx,
+        cy,
+        value: layer.value(cx, cy)?,
+    })
+}
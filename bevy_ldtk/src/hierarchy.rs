@@ -0,0 +1,96 @@
+//! Components identifying the level and layer entities, parents of the spawned tiles and entities.
+
+use bevy::prelude::*;
+
+/// A level of an LDtk project.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LdtkLevel {
+    pub uid: i64,
+    pub identifier: String,
+}
+
+/// Custom fields of a level.
+#[derive(Clone, Debug, Default)]
+pub struct LdtkLevelFields(pub Vec<ldtk::FieldInstance>);
+
+/// Bounds of a level in LDtk world pixels, y pointing down.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LdtkLevelBounds(pub ldtk::Rect);
+
+#[derive(Bundle)]
+pub struct LdtkLevelBundle {
+    pub level: LdtkLevel,
+    pub fields: LdtkLevelFields,
+    pub bounds: LdtkLevelBounds,
+    pub visible: Visible,
+}
+
+impl LdtkLevelBundle {
+    pub fn new(level: &ldtk::Level) -> Self {
+        Self {
+            level: LdtkLevel {
+                uid: level.uid,
+                identifier: level.identifier.clone(),
+            },
+            fields: LdtkLevelFields(level.field_instances.clone()),
+            bounds: LdtkLevelBounds(level.world_bounds()),
+            visible: Visible::default(),
+        }
+    }
+}
+
+/// A layer of a level, parent of its tiles and entities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LdtkLayer {
+    pub def_uid: i64,
+    pub identifier: String,
+    pub layer_type: ldtk::Type,
+    pub grid_size: i64,
+}
+
+#[derive(Bundle)]
+pub struct LdtkLayerBundle {
+    pub layer: LdtkLayer,
+    pub visible: Visible,
+    pub transform: Transform,
+    pub global_transform: GlobalTransform,
+}
+
+impl LdtkLayerBundle {
+    pub fn new(layer: &ldtk::LayerInstance, layer_type: ldtk::Type) -> Self {
+        Self {
+            layer: LdtkLayer {
+                def_uid: layer.layer_def_uid,
+                identifier: layer.identifier.clone(),
+                layer_type,
+                grid_size: layer.grid_size,
+            },
+            visible: Visible::default(),
+            transform: Transform::default(),
+            global_transform: GlobalTransform::default(),
+        }
+    }
+}
+
+/// Copies the visibility of changed levels and layers to everything below them, as sprites do not inherit it.
+pub fn propagate_visibility(
+    mut queries: QuerySet<(
+        Query<(Entity, &Visible), (Changed<Visible>, Or<(With<LdtkLevel>, With<LdtkLayer>)>)>,
+        Query<&mut Visible>,
+    )>,
+    children: Query<&Children>,
+) {
+    let changed: Vec<(Entity, bool)> = queries.q0().iter().map(|(e, v)| (e, v.is_visible)).collect();
+    for (entity, is_visible) in changed {
+        set_visible(entity, is_visible, &children, queries.q1_mut());
+    }
+}
+
+fn set_visible(entity: Entity, is_visible: bool, children: &Query<&Children>, visibles: &mut Query<&mut Visible>) {
+    for child in children.get(entity).into_iter().flat_map(|c| c.iter()) {
+        if let Ok(mut visible) = visibles.get_mut(*child) {
+            visible.is_visible = is_visible;
+        }
+        set_visible(*child, is_visible, children, visibles);
+    }
+}
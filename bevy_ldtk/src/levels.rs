@@ -0,0 +1,116 @@
+use crate::{LdtkProject, LdtkProjectCfg, LdtkRenderType};
+use bevy::prelude::*;
+
+/// Designates a level of a project.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LevelSelector {
+    Uid(i64),
+    Identifier(String),
+    /// The level `iid`, written by editor versions newer than the schema of this crate.
+    Iid(String),
+}
+
+impl LevelSelector {
+    /// Index of the level in `project.levels`.
+    pub fn find(&self, project: &ldtk::Project) -> Option<usize> {
+        project.levels.iter().position(|level| match self {
+            LevelSelector::Uid(uid) => level.uid == *uid,
+            LevelSelector::Identifier(identifier) => &level.identifier == identifier,
+            LevelSelector::Iid(iid) => level.unknown_fields.get("iid").and_then(|v| v.as_str()) == Some(iid),
+        })
+    }
+}
+
+/// Renders `level` as the single level of the entities holding `project`.
+pub struct LoadLevel {
+    pub project: Handle<LdtkProject>,
+    pub level: LevelSelector,
+}
+
+/// Sent when a level entity is spawned, whatever the render type.
+pub struct LevelSpawned {
+    pub project: Handle<LdtkProject>,
+    pub level_uid: i64,
+    pub entity: Entity,
+}
+
+/// Sent when a level entity is despawned, whatever the render type.
+pub struct LevelDespawned {
+    pub project: Handle<LdtkProject>,
+    pub level_uid: i64,
+    pub entity: Entity,
+}
+
+pub fn load_levels(
+    mut events: EventReader<LoadLevel>,
+    mut projects: Query<(&Handle<LdtkProject>, &mut LdtkProjectCfg)>,
+    project_assets: Res<Assets<LdtkProject>>,
+) {
+    for event in events.iter() {
+        let project = match project_assets.get(event.project.clone()) {
+            Some(project) => project,
+            None => {
+                warn!("Cannot load level {:?}, its project is not loaded", event.level);
+                continue;
+            }
+        };
+        let level = match event.level.find(&project.project) {
+            Some(level) => level,
+            None => {
+                warn!("Cannot load level {:?}, no such level", event.level);
+                continue;
+            }
+        };
+
+        for (project_handle, mut project_cfg) in projects.iter_mut() {
+            if project_handle == &event.project {
+                project_cfg.render_type = LdtkRenderType::SingleLevel(level);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ldtk::ProjectBuilder;
+    use serde_json::json;
+
+    #[test]
+    fn level_selectors() {
+        let mut builder = ProjectBuilder::new();
+        builder.level("First", 256, 256).finish();
+        let second = builder.level("Second", 256, 256).finish();
+        let mut project = builder.build();
+        project.levels[1]
+            .unknown_fields
+            .insert("iid".into(), json!("5d1d4f30-66b0-11ec-8a86-a9e6c7ea7b45"));
+
+        assert_eq!(LevelSelector::Uid(second).find(&project), Some(1));
+        assert_eq!(LevelSelector::Identifier("Second".to_string()).find(&project), Some(1));
+        let iid = LevelSelector::Iid("5d1d4f30-66b0-11ec-8a86-a9e6c7ea7b45".to_string());
+        assert_eq!(iid.find(&project), Some(1));
+
+        assert_eq!(LevelSelector::Uid(-1).find(&project), None);
+        assert_eq!(LevelSelector::Identifier("Third".to_string()).find(&project), None);
+        assert_eq!(LevelSelector::Iid("Second".to_string()).find(&project), None);
+    }
+
+    #[test]
+    fn keeps_spawned_levels() {
+        let mut cfg = LdtkProjectCfg {
+            rendered: Some(LdtkRenderType::FullWorld),
+            ..Default::default()
+        };
+        assert!(cfg.keeps_spawned_levels());
+
+        cfg.render_type = LdtkRenderType::SingleLevel(1);
+        // The full world is despawned and the second level spawned in its place
+        assert!(!cfg.keeps_spawned_levels());
+
+        cfg.rendered = Some(cfg.render_type);
+        assert!(cfg.keeps_spawned_levels());
+        cfg.render_type = LdtkRenderType::Streaming { radius: 64.0 };
+        assert!(!cfg.keeps_spawned_levels());
+    }
+}
@@ -1,10 +1,25 @@
 use bevy::prelude::*;
 
 mod assets;
+mod chunks;
+mod fields;
+mod grid;
+mod hierarchy;
+mod levels;
+mod placeholder;
+mod registry;
 mod render;
+mod tracker;
 
 pub use assets::LdtkProject;
-pub use render::{AttachEnumsEvent, EntitySpawn, LdtkProjectBundle, LdtkProjectCfg, LdtkRenderType};
+pub use chunks::{TileChunk, CHUNK_SIZE};
+pub use fields::{FieldComponents, FieldRadius, PatrolPath};
+pub use grid::{IntGridCell, IntGridLayer, LdtkGrid};
+pub use hierarchy::{LdtkLayer, LdtkLayerBundle, LdtkLevel, LdtkLevelBounds, LdtkLevelBundle, LdtkLevelFields};
+pub use levels::{LevelDespawned, LevelSelector, LevelSpawned, LoadLevel};
+pub use registry::{LdtkEntityAppExt, LdtkEntityRegistry};
+pub use render::{AttachEnumsEvent, EntitySpawn, LdtkProjectBundle, LdtkProjectCfg, LdtkRenderType, LevelStreamEvent};
+pub use tracker::{LdtkLevelTracker, LevelTransition, NeighbourDirection};
 
 #[derive(Debug)]
 pub struct LdtkPlugin;
@@ -12,6 +27,9 @@ pub struct LdtkPlugin;
 pub static LDTK_RENDER: &str = "ldtk_render";
 pub static LDTK_CLEANUP: &str = "ldtk_cleanup";
 pub static LDTK_HOT_RELOAD: &str = "ldtk_hot_reload";
+pub static LDTK_SPAWN: &str = "ldtk_spawn";
+pub static LDTK_LOAD_LEVEL: &str = "ldtk_load_level";
+pub static LDTK_TRACK_LEVELS: &str = "ldtk_track_levels";
 
 impl Plugin for LdtkPlugin {
     fn build(&self, app: &mut AppBuilder) {
@@ -19,13 +37,33 @@ impl Plugin for LdtkPlugin {
             .init_asset_loader::<assets::LdtkProjectLoader>()
             .add_event::<render::EntitySpawn>()
             .add_event::<render::AttachEnumsEvent>()
+            .add_event::<render::LevelStreamEvent>()
+            .add_event::<levels::LoadLevel>()
+            .add_event::<levels::LevelSpawned>()
+            .add_event::<levels::LevelDespawned>()
+            .add_event::<tracker::LevelTransition>()
+            .init_resource::<registry::LdtkEntityRegistry>()
             .add_system(render::render_ldtk_projects.system().label(LDTK_RENDER))
+            .add_system(
+                registry::spawn_registered_entities
+                    .system()
+                    .label(LDTK_SPAWN)
+                    .after(LDTK_RENDER),
+            )
+            .add_system(hierarchy::propagate_visibility.system())
             .add_system(
                 render::ldtk_entity_cleanup
                     .system()
                     .label(LDTK_CLEANUP)
                     .before(LDTK_RENDER),
             )
+            .add_system(levels::load_levels.system().label(LDTK_LOAD_LEVEL).before(LDTK_CLEANUP))
+            .add_system(
+                tracker::track_levels
+                    .system()
+                    .label(LDTK_TRACK_LEVELS)
+                    .before(LDTK_CLEANUP),
+            )
             .add_system(
                 render::ldtk_hot_reload
                     .system()
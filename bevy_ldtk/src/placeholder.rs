@@ -0,0 +1,111 @@
+//! Meshes drawing entities as their editor shapes, for prototyping before art exists.
+//!
+//! Meshes are in pixels, centered on the entity bounds, and drawn with the sprite pipeline at a sprite size of 1.
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, Mesh},
+        pipeline::PrimitiveTopology,
+    },
+};
+use ldtk::RenderMode;
+
+const LINE_WIDTH: f32 = 1.;
+const ELLIPSE_SEGMENTS: usize = 32;
+
+#[derive(Default)]
+struct Shape {
+    positions: Vec<[f32; 3]>,
+    indices: Vec<u32>,
+}
+
+impl Shape {
+    /// Adds a convex polygon, as a triangle fan.
+    fn polygon(&mut self, points: &[Vec2]) {
+        let first = self.positions.len() as u32;
+        self.positions.extend(points.iter().map(|p| [p.x, p.y, 0.]));
+        for i in 1..points.len().saturating_sub(1) as u32 {
+            self.indices.extend_from_slice(&[first, first + i, first + i + 1]);
+        }
+    }
+
+    /// Adds a line from `a` to `b`, `LINE_WIDTH` thick.
+    fn segment(&mut self, a: Vec2, b: Vec2) {
+        let length = (b - a).length();
+        if length <= 0. {
+            return;
+        }
+        let direction = (b - a) / length;
+        let normal = Vec2::new(-direction.y, direction.x) * LINE_WIDTH / 2.;
+        self.polygon(&[a - normal, b - normal, b + normal, a + normal]);
+    }
+
+    fn outline(&mut self, points: &[Vec2]) {
+        for (i, a) in points.iter().enumerate() {
+            self.segment(*a, points[(i + 1) % points.len()]);
+        }
+    }
+
+    fn into_mesh(self) -> Mesh {
+        let count = self.positions.len();
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0., 0., 1.]; count]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0., 0.]; count]);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        mesh
+    }
+}
+
+/// Corners of a `width` x `height` rectangle, inset by `inset` pixels.
+fn rectangle(width: f32, height: f32, inset: f32) -> Vec<Vec2> {
+    let (x, y) = (width / 2. - inset, height / 2. - inset);
+    vec![Vec2::new(-x, -y), Vec2::new(x, -y), Vec2::new(x, y), Vec2::new(-x, y)]
+}
+
+fn ellipse(width: f32, height: f32, inset: f32) -> Vec<Vec2> {
+    let (rx, ry) = (width / 2. - inset, height / 2. - inset);
+    (0..ELLIPSE_SEGMENTS)
+        .map(|i| {
+            let angle = i as f32 / ELLIPSE_SEGMENTS as f32 * std::f32::consts::TAU;
+            Vec2::new(rx * angle.cos(), ry * angle.sin())
+        })
+        .collect()
+}
+
+/// Filled part of an entity shape, if the definition has one.
+pub(crate) fn fill_mesh(def: &ldtk::EntityDefinition, width: i64, height: i64) -> Option<Mesh> {
+    if def.hollow || def.fill_opacity <= 0. {
+        return None;
+    }
+    let (width, height) = (width as f32, height as f32);
+    let mut shape = Shape::default();
+    match def.render_mode {
+        RenderMode::Rectangle => shape.polygon(&rectangle(width, height, 0.)),
+        RenderMode::Ellipse => shape.polygon(&ellipse(width, height, 0.)),
+        RenderMode::Cross | RenderMode::Tile => return None,
+    }
+    Some(shape.into_mesh())
+}
+
+/// Outline of an entity shape, or its lines for crosses.
+pub(crate) fn line_mesh(def: &ldtk::EntityDefinition, width: i64, height: i64) -> Option<Mesh> {
+    if def.line_opacity <= 0. {
+        return None;
+    }
+    let (width, height) = (width as f32, height as f32);
+    let inset = LINE_WIDTH / 2.;
+    let mut shape = Shape::default();
+    match def.render_mode {
+        RenderMode::Rectangle => shape.outline(&rectangle(width, height, inset)),
+        RenderMode::Ellipse => shape.outline(&ellipse(width, height, inset)),
+        RenderMode::Cross => {
+            let corners = rectangle(width, height, inset);
+            shape.segment(corners[0], corners[2]);
+            shape.segment(corners[1], corners[3]);
+        }
+        RenderMode::Tile => return None,
+    }
+    Some(shape.into_mesh())
+}
@@ -0,0 +1,105 @@
+use crate::{EntitySpawn, LdtkProject};
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use ldtk::EntityInstance;
+use std::collections::{HashMap, HashSet};
+
+type SpawnFn = Box<dyn Fn(&EntityInstance, &ldtk::Project, &mut EntityCommands) + Send + Sync>;
+
+/// Spawn handlers of LDtk entities, by entity identifier. Filled with [`LdtkEntityAppExt`].
+#[derive(Default)]
+pub struct LdtkEntityRegistry {
+    handlers: HashMap<String, SpawnFn>,
+    fallback: Option<SpawnFn>,
+}
+
+impl LdtkEntityRegistry {
+    pub fn is_registered(&self, identifier: &str) -> bool {
+        self.handlers.contains_key(identifier)
+    }
+}
+
+/// Registers how LDtk entities are spawned.
+///
+/// Registered entities are spawned as children of their level entity, with a `Transform` at their position, the
+/// components resolved from their fields and their `EntityInstance`. `EntitySpawn` events are still sent for all
+/// entities.
+pub trait LdtkEntityAppExt {
+    /// Spawns `B::default()` for entities named `identifier`.
+    fn register_ldtk_entity<B: Bundle + Default>(&mut self, identifier: &str) -> &mut Self;
+
+    /// Calls `spawn` for entities named `identifier`, with commands for the spawned entity.
+    fn register_ldtk_entity_with<F>(&mut self, identifier: &str, spawn: F) -> &mut Self
+    where
+        F: Fn(&EntityInstance, &ldtk::Project, &mut EntityCommands) + Send + Sync + 'static;
+
+    /// Calls `spawn` for entities without a registered identifier, instead of warning about them.
+    fn set_ldtk_entity_fallback<F>(&mut self, spawn: F) -> &mut Self
+    where
+        F: Fn(&EntityInstance, &ldtk::Project, &mut EntityCommands) + Send + Sync + 'static;
+}
+
+impl LdtkEntityAppExt for AppBuilder {
+    fn register_ldtk_entity<B: Bundle + Default>(&mut self, identifier: &str) -> &mut Self {
+        self.register_ldtk_entity_with(identifier, |_, _, entity| {
+            entity.insert_bundle(B::default());
+        })
+    }
+
+    fn register_ldtk_entity_with<F>(&mut self, identifier: &str, spawn: F) -> &mut Self
+    where
+        F: Fn(&EntityInstance, &ldtk::Project, &mut EntityCommands) + Send + Sync + 'static,
+    {
+        registry(self).handlers.insert(identifier.to_string(), Box::new(spawn));
+        self
+    }
+
+    fn set_ldtk_entity_fallback<F>(&mut self, spawn: F) -> &mut Self
+    where
+        F: Fn(&EntityInstance, &ldtk::Project, &mut EntityCommands) + Send + Sync + 'static,
+    {
+        registry(self).fallback = Some(Box::new(spawn));
+        self
+    }
+}
+
+fn registry(app: &mut AppBuilder) -> Mut<LdtkEntityRegistry> {
+    app.init_resource::<LdtkEntityRegistry>();
+    app.world_mut().get_resource_mut::<LdtkEntityRegistry>().unwrap()
+}
+
+pub fn spawn_registered_entities(
+    mut commands: Commands,
+    mut events: EventReader<EntitySpawn>,
+    registry: Res<LdtkEntityRegistry>,
+    project_assets: Res<Assets<LdtkProject>>,
+    mut warned: Local<HashSet<String>>,
+) {
+    for event in events.iter() {
+        let identifier = &event.entity.identifier;
+        let spawn = match registry.handlers.get(identifier).or_else(|| registry.fallback.as_ref()) {
+            Some(spawn) => spawn,
+            None => {
+                if warned.insert(identifier.clone()) {
+                    warn!("No spawn handler registered for LDtk entity {}", identifier);
+                }
+                continue;
+            }
+        };
+        let project = match project_assets.get(event.project.clone()) {
+            Some(project) => project,
+            None => continue,
+        };
+
+        let mut entity = commands.spawn();
+        spawn(&event.entity, &project.project, &mut entity);
+        // Inserted after the bundle, so that bundles with their own transform are placed too
+        entity.insert_bundle((
+            Transform::from_translation(event.translation),
+            GlobalTransform::default(),
+            event.entity.clone(),
+        ));
+        event.fields.insert(&mut entity);
+        let id = entity.id();
+        commands.entity(event.level).push_children(&[id]);
+    }
+}
@@ -1,6 +1,17 @@
-use crate::LdtkProject;
-use bevy::prelude::*;
+use crate::{
+    FieldComponents, IntGridLayer, LdtkLayerBundle, LdtkLevel, LdtkLevelBundle, LdtkLevelTracker, LdtkProject,
+    LevelDespawned, LevelSpawned, TileChunk,
+};
+use bevy::{
+    prelude::*,
+    render::{
+        camera::{Camera, OrthographicProjection},
+        render_graph::base::camera::CAMERA_2D,
+    },
+    sprite::SpriteResizeMode,
+};
 use ldtk::{EntityInstance, TileInstance};
+use std::collections::HashMap;
 
 #[derive(Clone, Default, Debug)]
 pub struct LdtkRenderedProject(Handle<LdtkProject>);
@@ -16,13 +27,41 @@ pub struct LdtkRenderBundle {
 #[derive(Default)]
 pub struct LdtkProjectCfg {
     pub render_type: LdtkRenderType,
-    pub rendered: Option<usize>,
+    /// The render type of the spawned levels, `None` when they are to be spawned again.
+    pub rendered: Option<LdtkRenderType>,
+    /// Whether to spawn one sprite per tile, for gameplay code needing tile entities, instead of drawing tile
+    /// layers as chunk meshes. `AttachEnumsEvent` is only sent for tile entities.
+    pub tile_entities: bool,
+    /// Whether to spawn sprites for entities rendered as tiles, on top of sending `EntitySpawn` events.
+    pub entity_tiles: bool,
+    /// Whether to draw the other entities as their editor shapes, for prototyping before art exists.
+    pub placeholders: bool,
+    /// Font of the placeholder names, for entity definitions showing them. Names are not drawn without it.
+    pub placeholder_font: Option<Handle<Font>>,
+    /// Level entities spawned by `LdtkRenderType::Streaming`, by level uid.
+    pub streamed_levels: HashMap<i64, Entity>,
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum LdtkRenderType {
     SingleLevel(usize),
     FullWorld,
+    /// Levels within `radius` pixels of the 2d camera view are spawned at their world position, and despawned
+    /// once farther than twice `radius`, so that levels at the edge do not keep coming and going.
+    Streaming {
+        radius: f32,
+    },
+}
+
+impl LdtkProjectCfg {
+    /// Whether the spawned levels are the ones `render_type` asks for, so that none are despawned.
+    pub(crate) fn keeps_spawned_levels(&self) -> bool {
+        match (self.rendered, self.render_type) {
+            // Streamed levels come and go with the camera, whatever the radius
+            (Some(LdtkRenderType::Streaming { .. }), LdtkRenderType::Streaming { .. }) => true,
+            (rendered, current) => rendered == Some(current),
+        }
+    }
 }
 
 impl Default for LdtkRenderType {
@@ -40,6 +79,8 @@ pub struct LdtkProjectBundle {
     pub global_transform: GlobalTransform,
 }
 
+const PLACEHOLDER_FONT_SIZE: f32 = 12.;
+
 #[derive(Copy, Clone)]
 struct LevelInfo {
     world_x: i32,
@@ -56,34 +97,25 @@ struct LayerInfo {
 
 #[derive(Copy, Clone, Default)]
 struct ExtraEntDefs {
-    __tile_id: i32,
     __width: i32,
     __height: i32,
-    __scale: f32,
 }
 
 pub fn ldtk_hot_reload(
-    mut commands: Commands,
     mut events: EventReader<AssetEvent<LdtkProject>>,
     mut projects: Query<(&Handle<LdtkProject>, &mut LdtkProjectCfg)>,
-    entities: Query<(Entity, &LdtkRenderedProject)>,
 ) {
     for event in events.iter() {
         if let AssetEvent::Modified {
             handle: modified_handle,
         } = event
         {
+            // Levels are despawned by `ldtk_entity_cleanup`, which sees the changed configuration
             for (project_handle, mut project_cfg) in projects.iter_mut() {
                 if project_handle == modified_handle {
                     project_cfg.rendered = None;
                 }
             }
-
-            for (entity, project) in entities.iter() {
-                if modified_handle == &project.0 {
-                    commands.entity(entity).despawn_recursive();
-                }
-            }
         }
     }
 }
@@ -91,20 +123,44 @@ pub fn ldtk_hot_reload(
 pub fn ldtk_entity_cleanup(
     mut commands: Commands,
     level: Query<(&Handle<LdtkProject>, &LdtkProjectCfg), Changed<LdtkProjectCfg>>,
-    entities: Query<(Entity, &LdtkRenderedProject)>,
+    entities: Query<(
+        Entity,
+        &LdtkRenderedProject,
+        &LdtkLevel,
+        &GlobalTransform,
+        Option<&Children>,
+    )>,
+    mut trackers: Query<(Entity, &Parent, &mut Transform), With<LdtkLevelTracker>>,
+    mut level_despawned: EventWriter<LevelDespawned>,
 ) {
     for (project_handle, project_cfg) in level.iter() {
-        match project_cfg.rendered {
-            None => {}
-            Some(rendered) => match project_cfg.render_type {
-                LdtkRenderType::SingleLevel(current) if rendered != current => {}
-                _ => continue,
-            },
+        if project_cfg.keeps_spawned_levels() {
+            continue;
         }
 
-        for (entity, project) in entities.iter() {
+        for (entity, project, level, level_transform, children) in entities.iter() {
             if project_handle == &project.0 {
-                commands.entity(entity).despawn_recursive();
+                // Tracked entities spawned in the level, such as by the entity registry, outlive it at the same
+                // world position, for the level transitions they drive
+                let mut kept = Vec::new();
+                for (tracked, parent, mut transform) in trackers.iter_mut() {
+                    if parent.0 == entity {
+                        *transform = level_transform.mul_transform(*transform).into();
+                        commands.entity(tracked).remove::<Parent>();
+                        kept.push(tracked);
+                    }
+                }
+                for child in children.iter().flat_map(|children| children.iter()) {
+                    if !kept.contains(child) {
+                        commands.entity(*child).despawn_recursive();
+                    }
+                }
+                commands.entity(entity).despawn();
+                level_despawned.send(LevelDespawned {
+                    project: project_handle.clone(),
+                    level_uid: level.uid,
+                    entity,
+                });
             }
         }
     }
@@ -117,6 +173,19 @@ pub struct AttachEnumsEvent {
     pub entity_id: Entity,
 }
 
+/// Sent when `LdtkRenderType::Streaming` spawns or despawns a level.
+pub enum LevelStreamEvent {
+    In {
+        project: Handle<LdtkProject>,
+        level_uid: i64,
+        entity: Entity,
+    },
+    Out {
+        project: Handle<LdtkProject>,
+        level_uid: i64,
+    },
+}
+
 #[non_exhaustive]
 pub struct EntitySpawn {
     pub project: Handle<LdtkProject>,
@@ -124,6 +193,12 @@ pub struct EntitySpawn {
     pub size: Vec2,
     pub entity: EntityInstance,
     pub parent_id: u32,
+    /// The layer entity the entity belongs to.
+    pub parent: Entity,
+    /// The level entity, parent of the entities spawned by the `LdtkEntityRegistry`.
+    pub level: Entity,
+    /// Components from the entity fields displayed as paths or radii, to insert on the spawned entity.
+    pub fields: FieldComponents,
 }
 
 pub fn render_ldtk_projects(
@@ -131,14 +206,17 @@ pub fn render_ldtk_projects(
     mut projects: Query<(&Handle<LdtkProject>, &mut LdtkProjectCfg, &Transform)>,
     mut entity_spawner: EventWriter<EntitySpawn>,
     mut attach_enums: EventWriter<AttachEnumsEvent>,
+    mut level_streams: EventWriter<LevelStreamEvent>,
+    mut level_spawned: EventWriter<LevelSpawned>,
+    mut level_despawned: EventWriter<LevelDespawned>,
     project_assets: Res<Assets<LdtkProject>>,
+    cameras: Query<(&Camera, &OrthographicProjection, &GlobalTransform)>,
 ) {
     for (project_handle, mut project_cfg, transform) in projects.iter_mut() {
         match project_cfg.render_type {
             LdtkRenderType::SingleLevel(current) => {
-                match project_cfg.rendered {
-                    Some(l) if l == current => continue,
-                    _ => {}
+                if project_cfg.keeps_spawned_levels() {
+                    continue;
                 }
 
                 let project = match project_assets.get(project_handle.clone()) {
@@ -146,21 +224,29 @@ pub fn render_ldtk_projects(
                     None => continue,
                 };
 
-                render_single_ldtk_level(
+                // Only a single level fills the view, other render types keep the clear color as is
+                commands.insert_resource(ClearColor(project.project.levels[current].bg_color.into()));
+                let entity = render_single_ldtk_level(
                     &mut commands,
                     transform,
                     current,
                     false,
+                    &project_cfg,
                     project_handle,
                     project,
                     &mut entity_spawner,
                     &mut attach_enums,
                 );
+                level_spawned.send(LevelSpawned {
+                    project: project_handle.clone(),
+                    level_uid: project.project.levels[current].uid,
+                    entity,
+                });
 
-                project_cfg.rendered = Some(current);
+                project_cfg.rendered = Some(project_cfg.render_type);
             }
             LdtkRenderType::FullWorld => {
-                if project_cfg.rendered.is_some() {
+                if project_cfg.keeps_spawned_levels() {
                     continue;
                 }
 
@@ -170,43 +256,163 @@ pub fn render_ldtk_projects(
                 };
 
                 for level in 0..project.project.levels.len() {
-                    render_single_ldtk_level(
+                    let entity = render_single_ldtk_level(
                         &mut commands,
                         transform,
                         level,
                         true,
+                        &project_cfg,
                         project_handle,
                         project,
                         &mut entity_spawner,
                         &mut attach_enums,
-                    )
+                    );
+                    level_spawned.send(LevelSpawned {
+                        project: project_handle.clone(),
+                        level_uid: project.project.levels[level].uid,
+                        entity,
+                    });
+                }
+
+                project_cfg.rendered = Some(project_cfg.render_type);
+            }
+            LdtkRenderType::Streaming { radius } => {
+                if !project_cfg.keeps_spawned_levels() {
+                    // Levels were despawned by a reload or another render type
+                    project_cfg.streamed_levels.clear();
+                    project_cfg.rendered = Some(project_cfg.render_type);
                 }
 
-                project_cfg.rendered = Some(1);
+                let project = match project_assets.get(project_handle.clone()) {
+                    Some(p) => p,
+                    None => continue,
+                };
+                let (projection, camera_transform) = match cameras
+                    .iter()
+                    .find(|(camera, _, _)| camera.name.as_deref() == Some(CAMERA_2D))
+                {
+                    Some((_, projection, camera_transform)) => (projection, camera_transform),
+                    None => continue,
+                };
+                let view = camera_view(projection, camera_transform, transform);
+
+                for (idx, level) in project.project.levels.iter().enumerate() {
+                    let spawned = project_cfg.streamed_levels.get(&level.uid).copied();
+                    match (
+                        spawned,
+                        stream_level(&level.world_bounds(), spawned.is_some(), &view, radius),
+                    ) {
+                        (Some(entity), Some(Stream::Out)) => {
+                            debug!("Streaming out level {}", level.identifier);
+                            commands.entity(entity).despawn_recursive();
+                            project_cfg.streamed_levels.remove(&level.uid);
+                            level_streams.send(LevelStreamEvent::Out {
+                                project: project_handle.clone(),
+                                level_uid: level.uid,
+                            });
+                            level_despawned.send(LevelDespawned {
+                                project: project_handle.clone(),
+                                level_uid: level.uid,
+                                entity,
+                            });
+                        }
+                        (None, Some(Stream::In)) => {
+                            debug!("Streaming in level {}", level.identifier);
+                            let entity = render_single_ldtk_level(
+                                &mut commands,
+                                transform,
+                                idx,
+                                true,
+                                &project_cfg,
+                                project_handle,
+                                project,
+                                &mut entity_spawner,
+                                &mut attach_enums,
+                            );
+                            project_cfg.streamed_levels.insert(level.uid, entity);
+                            level_streams.send(LevelStreamEvent::In {
+                                project: project_handle.clone(),
+                                level_uid: level.uid,
+                                entity,
+                            });
+                            level_spawned.send(LevelSpawned {
+                                project: project_handle.clone(),
+                                level_uid: level.uid,
+                                entity,
+                            });
+                        }
+                        _ => {}
+                    }
+                }
             }
         }
     }
 }
 
+/// The part of the world seen by a 2d camera, in LDtk pixels relative to the project `transform`.
+fn camera_view(
+    projection: &OrthographicProjection,
+    camera_transform: &GlobalTransform,
+    transform: &Transform,
+) -> ldtk::Rect {
+    let scale = projection.scale * camera_transform.scale.truncate();
+    let origin = camera_transform.translation - transform.translation;
+    let left = origin.x + projection.left * scale.x;
+    let right = origin.x + projection.right * scale.x;
+    let bottom = origin.y + projection.bottom * scale.y;
+    let top = origin.y + projection.top * scale.y;
+    // LDtk y points down
+    ldtk::Rect::new(
+        left.floor() as i64,
+        (-top).floor() as i64,
+        (right - left).ceil() as i64,
+        (top - bottom).ceil() as i64,
+    )
+}
+
+#[derive(Debug, PartialEq)]
+enum Stream {
+    In,
+    Out,
+}
+
+/// Whether a level with world `bounds` has to be streamed in or out for the camera `view`, `spawned` telling
+/// whether it is currently spawned. Levels are spawned within `radius` pixels of the view, and kept until farther
+/// than twice `radius`.
+fn stream_level(bounds: &ldtk::Rect, spawned: bool, view: &ldtk::Rect, radius: f32) -> Option<Stream> {
+    let near = |margin: f32| {
+        let margin = margin.ceil() as i64;
+        let view = ldtk::Rect::new(
+            view.x - margin,
+            view.y - margin,
+            view.width + 2 * margin,
+            view.height + 2 * margin,
+        );
+        bounds.intersection(&view).is_some()
+    };
+    match spawned {
+        false if near(radius) => Some(Stream::In),
+        true if !near(radius * 2.) => Some(Stream::Out),
+        _ => None,
+    }
+}
+
 fn render_single_ldtk_level(
     commands: &mut Commands,
     transform: &Transform,
     level: usize,
     reposition_level: bool,
+    cfg: &LdtkProjectCfg,
     project_handle: &Handle<LdtkProject>,
     project: &LdtkProject,
     entity_spawner: &mut EventWriter<EntitySpawn>,
     attach_enums: &mut EventWriter<AttachEnumsEvent>,
-) {
+) -> Entity {
     debug!(
         "Beginning render pass for project at level {} ({})",
         level, project.project.levels[level].identifier
     );
 
-    commands.insert_resource(ClearColor(
-        Color::hex(&project.project.levels[level].bg_color[1..]).unwrap(),
-    ));
-
     commands
         .spawn()
         .insert_bundle(LdtkRenderBundle {
@@ -215,7 +421,9 @@ fn render_single_ldtk_level(
 
             ..Default::default()
         })
+        .insert_bundle(LdtkLevelBundle::new(&project.project.levels[level]))
         .with_children(|builder| {
+            let level_entity = builder.parent_entity();
             let level_info = if reposition_level {
                 LevelInfo {
                     world_x: project.project.levels[level].world_x as i32,
@@ -225,7 +433,14 @@ fn render_single_ldtk_level(
                 LevelInfo { world_x: 0, world_y: 0 }
             };
 
-            if let Some(layer_instances) = project.project.levels[level].layer_instances.as_ref() {
+            let level = &project.project.levels[level];
+            if let Some((texture_atlas, background)) = project.backgrounds.get(&level.uid) {
+                // Right below the bottom-most layer.
+                let z_index = 50 - level.layer_instances.as_ref().map_or(0, |l| l.len()) as i32;
+                display_background(&level_info, z_index, texture_atlas.clone(), background, builder);
+            }
+
+            if let Some(layer_instances) = level.layer_instances.as_ref() {
                 for (idx, layer) in layer_instances.iter().enumerate().rev() {
                     debug!("\tBeginning render pass for layer {}", layer.identifier);
                     let tileset_uid = layer.tileset_def_uid.unwrap_or(-1);
@@ -238,8 +453,33 @@ fn render_single_ldtk_level(
                         z_index: 50 - idx as i32,
                     };
 
-                    match &layer.layer_instance_type[..] {
-                        "Tiles" => {
+                    let layer_type = match ldtk::Type::from_name(&layer.layer_instance_type) {
+                        Some(layer_type) => layer_type,
+                        None => {
+                            error!("\t\t{} Not implemented", idx);
+                            continue;
+                        }
+                    };
+
+                    let mut layer_entity = builder.spawn_bundle(LdtkLayerBundle::new(layer, layer_type.clone()));
+                    if layer_type == ldtk::Type::IntGrid {
+                        let origin = Vec2::new(
+                            (level_info.world_x as i64 + layer.px_total_offset_x) as f32,
+                            -(level_info.world_y as i64 + layer.px_total_offset_y) as f32,
+                        );
+                        layer_entity.insert(IntGridLayer::new(&project.project, level, layer, origin));
+                    }
+                    layer_entity.with_children(|builder| match layer_type {
+                        ldtk::Type::Tiles | ldtk::Type::AutoLayer | ldtk::Type::IntGrid
+                            if !cfg.tile_entities && layer.tileset_def_uid.is_some() =>
+                        {
+                            debug!("\t\t{} Generating Tile Chunks", idx);
+                            let chunks = project.tile_chunks.get(&(level.uid, layer_uid));
+                            for chunk in chunks.into_iter().flatten() {
+                                display_chunk(&level_info, layer_info, chunk, builder);
+                            }
+                        }
+                        ldtk::Type::Tiles => {
                             debug!("\t\t{} Generating Tile Layer", idx);
                             for tile in layer.grid_tiles.iter() {
                                 display_tile(
@@ -254,7 +494,7 @@ fn render_single_ldtk_level(
                                 );
                             }
                         }
-                        "AutoLayer" => {
+                        ldtk::Type::AutoLayer => {
                             debug!("\t\t{} Generating AutoTile Layer", idx);
                             for tile in layer.auto_layer_tiles.iter() {
                                 display_tile(
@@ -269,7 +509,7 @@ fn render_single_ldtk_level(
                                 );
                             }
                         }
-                        "IntGrid" => match layer.tileset_def_uid {
+                        ldtk::Type::IntGrid => match layer.tileset_def_uid {
                             Some(i) => {
                                 debug!("\t\t{} Generating IntGrid Layer w/ Tiles", idx);
                                 for tile in layer.auto_layer_tiles.iter() {
@@ -298,44 +538,60 @@ fn render_single_ldtk_level(
                                 }
                             }
                         },
-                        "Entities" => {
+                        ldtk::Type::Entities => {
                             debug!("\t\t{} Generating Entities Layer", idx);
-                            for entity in &layer.entity_instances {
+                            for (index, entity) in layer.entity_instances.iter().enumerate() {
+                                let entity_ref = ldtk::EntityRef {
+                                    level_uid: level.uid,
+                                    layer_def_uid: layer_uid,
+                                    index,
+                                };
+                                if let (true, Some(quads)) = (cfg.entity_tiles, project.entity_tiles.get(&entity_ref)) {
+                                    display_entity_tiles(&level_info, layer_info, layer, quads, builder, project);
+                                }
+                                if cfg.placeholders {
+                                    display_placeholder(
+                                        &level_info,
+                                        layer_info,
+                                        layer,
+                                        entity,
+                                        builder,
+                                        project,
+                                        cfg.placeholder_font.as_ref(),
+                                    );
+                                }
+
                                 let mut extra_ent_defs = ExtraEntDefs::default();
-                                for ent in &project.project.defs.entities {
-                                    if ent.uid == entity.def_uid {
-                                        extra_ent_defs.__tile_id = 0;
-                                        extra_ent_defs.__width = ent.width as i32;
-                                        extra_ent_defs.__height = ent.height as i32;
-                                    }
-                                    if let ldtk::RenderMode::Tile = ent.render_mode {
-                                        extra_ent_defs.__tile_id = ent.tile_id.unwrap() as i32;
-                                        for ts in &project.project.defs.tilesets {
-                                            if ts.uid == ent.tileset_id.unwrap() {
-                                                extra_ent_defs.__scale = ent.width as f32 / ts.tile_grid_size as f32;
-                                            }
-                                        }
-                                    }
+                                let defs = &project.project.defs.entities;
+                                if let Some(ent) = defs.iter().find(|e| e.uid == entity.def_uid) {
+                                    extra_ent_defs.__width = ent.width as i32;
+                                    extra_ent_defs.__height = ent.height as i32;
                                 }
 
+                                let offset = Vec2::new(
+                                    (level_info.world_x as i64 - level.world_x) as f32,
+                                    (level_info.world_y as i64 - level.world_y) as f32,
+                                );
+                                let fields = FieldComponents::new(&project.project, level, layer, entity, offset);
+
                                 display_entity(
                                     &level_info,
                                     layer_info,
                                     entity,
                                     builder,
+                                    level_entity,
                                     &extra_ent_defs,
+                                    fields,
                                     entity_spawner,
                                     project_handle.clone(),
                                 );
                             }
                         }
-                        _ => {
-                            error!("\t\t{} Not implemented", idx);
-                        }
-                    }
+                    });
                 }
             }
-        });
+        })
+        .id()
 }
 
 fn display_tile(
@@ -383,12 +639,148 @@ fn display_tile(
     });
 }
 
+fn display_chunk(level_info: &LevelInfo, layer_info: LayerInfo, chunk: &TileChunk, builder: &mut ChildBuilder) {
+    builder.spawn().insert_bundle(SpriteBundle {
+        mesh: chunk.mesh.clone(),
+        material: chunk.material.clone(),
+        // Chunk meshes are in pixels, and must not be resized to the tileset texture
+        sprite: Sprite {
+            size: Vec2::ONE,
+            resize_mode: SpriteResizeMode::Manual,
+            ..Default::default()
+        },
+        transform: Transform::from_translation(Vec3::new(
+            level_info.world_x as f32,
+            -level_info.world_y as f32,
+            layer_info.z_index as f32,
+        )),
+        ..Default::default()
+    });
+}
+
+fn display_background(
+    level_info: &LevelInfo,
+    z_index: i32,
+    texture_atlas: Handle<TextureAtlas>,
+    background: &ldtk::LevelBackground,
+    builder: &mut ChildBuilder,
+) {
+    let [width, height] = background.size();
+    let [x, y] = background.top_left;
+    builder.spawn().insert_bundle(SpriteSheetBundle {
+        transform: Transform {
+            translation: Vec3::new(
+                (x + width / 2.) as f32 + level_info.world_x as f32,
+                -((y + height / 2.) as f32 + level_info.world_y as f32),
+                z_index as f32,
+            ),
+            scale: Vec3::new(background.scale[0] as f32, background.scale[1] as f32, 1.),
+            ..Default::default()
+        },
+        texture_atlas,
+        ..Default::default()
+    });
+}
+
+fn display_entity_tiles(
+    level_info: &LevelInfo,
+    layer_info: LayerInfo,
+    layer: &ldtk::LayerInstance,
+    quads: &[(u32, ldtk::TileQuad)],
+    builder: &mut ChildBuilder,
+    project: &LdtkProject,
+) {
+    for (index, quad) in quads {
+        let (src, dst) = (quad.src, quad.dst);
+        let x = dst.x as f32 + dst.width as f32 / 2. + (layer.px_total_offset_x + level_info.world_x as i64) as f32;
+        let y = dst.y as f32 + dst.height as f32 / 2. + (layer.px_total_offset_y + level_info.world_y as i64) as f32;
+        builder.spawn().insert_bundle(SpriteSheetBundle {
+            transform: Transform {
+                translation: Vec3::new(x, -y, layer_info.z_index as f32),
+                scale: Vec3::new(
+                    dst.width as f32 / src.width as f32,
+                    dst.height as f32 / src.height as f32,
+                    1.,
+                ),
+                ..Default::default()
+            },
+            sprite: TextureAtlasSprite {
+                index: *index,
+                ..Default::default()
+            },
+            texture_atlas: project.spritesheets[&quad.tileset_uid].clone(),
+            ..Default::default()
+        });
+    }
+}
+
+fn display_placeholder(
+    level_info: &LevelInfo,
+    layer_info: LayerInfo,
+    layer: &ldtk::LayerInstance,
+    entity: &EntityInstance,
+    builder: &mut ChildBuilder,
+    project: &LdtkProject,
+    font: Option<&Handle<Font>>,
+) {
+    let def = match project.project.defs.entities.iter().find(|d| d.uid == entity.def_uid) {
+        Some(def) => def,
+        None => return,
+    };
+    let bounds = entity.bounds();
+    let x = bounds.x as f32 + bounds.width as f32 / 2. + (layer.px_total_offset_x + level_info.world_x as i64) as f32;
+    let y = bounds.y as f32 + bounds.height as f32 / 2. + (layer.px_total_offset_y + level_info.world_y as i64) as f32;
+    let z = layer_info.z_index as f32;
+
+    let (fill, line) = match project.placeholder_meshes.get(&(def.uid, entity.width, entity.height)) {
+        Some(meshes) => meshes,
+        None => return,
+    };
+    let parts = [
+        (fill, &project.entity_fill_colors, z),
+        (line, &project.entity_colors, z + 0.01),
+    ];
+    for (mesh, materials, z) in parts.iter() {
+        if let (Some(mesh), Some(material)) = (mesh, materials.get(&def.uid)) {
+            builder.spawn().insert_bundle(SpriteBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                sprite: Sprite::new(Vec2::ONE),
+                transform: Transform::from_translation(Vec3::new(x, -y, *z)),
+                ..Default::default()
+            });
+        }
+    }
+
+    if let (true, Some(font)) = (def.show_name, font) {
+        builder.spawn().insert_bundle(Text2dBundle {
+            text: Text::with_section(
+                def.identifier.clone(),
+                TextStyle {
+                    font: font.clone(),
+                    font_size: PLACEHOLDER_FONT_SIZE,
+                    color: def.color.into(),
+                },
+                TextAlignment {
+                    vertical: VerticalAlign::Bottom,
+                    horizontal: HorizontalAlign::Center,
+                },
+            ),
+            // Above the entity, as in the editor
+            transform: Transform::from_translation(Vec3::new(x, -y + bounds.height as f32 / 2. + 2., z + 0.02)),
+            ..Default::default()
+        });
+    }
+}
+
 fn display_entity(
     level_info: &LevelInfo,
     layer_info: LayerInfo,
     entity: &EntityInstance,
     builder: &mut ChildBuilder,
+    level_entity: Entity,
     extra_ent_defs: &ExtraEntDefs,
+    fields: FieldComponents,
     entity_spawner: &mut EventWriter<EntitySpawn>,
     project_handle: Handle<LdtkProject>,
 ) {
@@ -404,6 +796,9 @@ fn display_entity(
         entity: (*entity).clone(),
         project: project_handle,
         parent_id: builder.parent_entity().id(),
+        parent: builder.parent_entity(),
+        level: level_entity,
+        fields,
     })
 }
 
@@ -442,3 +837,47 @@ fn convert_to_world(width: i32, height: i32, x: i32, y: i32, z: i32) -> Vec3 {
     let world_z = z as f32;
     Vec3::new(world_x, -world_y, world_z)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_view_in_ldtk_pixels() {
+        let projection = OrthographicProjection {
+            left: -160.,
+            right: 160.,
+            bottom: -90.,
+            top: 90.,
+            scale: 0.5,
+            ..Default::default()
+        };
+        let mut camera_transform = GlobalTransform::from_translation(Vec3::new(300., -200., 999.));
+        camera_transform.scale = Vec3::new(2., 4., 1.);
+        let transform = Transform::from_translation(Vec3::new(100., 50., 0.));
+
+        // 320x180 window pixels, scaled by 0.5 * (2, 4), centered on (200, -250) in Bevy coordinates
+        assert_eq!(
+            camera_view(&projection, &camera_transform, &transform),
+            ldtk::Rect::new(40, 70, 320, 360)
+        );
+    }
+
+    #[test]
+    fn streaming_hysteresis() {
+        let view = ldtk::Rect::new(0, 0, 320, 180);
+        let level = |x| ldtk::Rect::new(x, 0, 100, 100);
+
+        // Within the radius of 64 pixels, levels come in
+        assert_eq!(stream_level(&level(320 + 63), false, &view, 64.), Some(Stream::In));
+        assert_eq!(stream_level(&level(-100 - 63), false, &view, 64.), Some(Stream::In));
+        assert_eq!(stream_level(&level(320 + 64), false, &view, 64.), None);
+        assert_eq!(stream_level(&level(100), true, &view, 64.), None);
+
+        // Spawned levels stay until farther than twice the radius
+        assert_eq!(stream_level(&level(320 + 64), true, &view, 64.), None);
+        assert_eq!(stream_level(&level(320 + 127), true, &view, 64.), None);
+        assert_eq!(stream_level(&level(320 + 128), true, &view, 64.), Some(Stream::Out));
+        assert_eq!(stream_level(&level(-100 - 128), true, &view, 64.), Some(Stream::Out));
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{LdtkProject, LdtkProjectCfg, LdtkRenderType};
+use bevy::prelude::*;
+
+/// Marks the entity driving the level transitions of `project`, from its `GlobalTransform`.
+///
+/// With `LdtkRenderType::SingleLevel`, crossing into a neighbour level makes it the rendered level, and the tracked
+/// entity is moved along so that it keeps its place in the world. Other render types already show neighbours at
+/// their world position, so transitions are only reported.
+///
+/// A tracked entity spawned as a child of a level, such as by the entity registry, is moved out of it when the
+/// level is despawned for a transition, and lives on at the root of the hierarchy.
+#[derive(Clone, Debug, Default)]
+pub struct LdtkLevelTracker {
+    pub project: Handle<LdtkProject>,
+    /// Uid of the level holding the entity, found on the first update. Always the rendered level with
+    /// `LdtkRenderType::SingleLevel`. Otherwise, it follows the entity to levels that are not neighbours of the
+    /// previous one, such as after a teleport, without sending a `LevelTransition`.
+    pub level_uid: Option<i64>,
+}
+
+impl LdtkLevelTracker {
+    pub fn new(project: Handle<LdtkProject>) -> Self {
+        Self {
+            project,
+            level_uid: None,
+        }
+    }
+}
+
+/// Side of a level on which a neighbour lies.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum NeighbourDirection {
+    North,
+    South,
+    East,
+    West,
+}
+
+impl NeighbourDirection {
+    /// The direction of a `NeighbourLevel`, from its `dir` character.
+    pub fn from_dir(dir: &str) -> Option<Self> {
+        match dir {
+            "n" => Some(NeighbourDirection::North),
+            "s" => Some(NeighbourDirection::South),
+            "e" => Some(NeighbourDirection::East),
+            "w" => Some(NeighbourDirection::West),
+            _ => None,
+        }
+    }
+}
+
+/// Sent when a tracked entity crosses into a neighbour level.
+pub struct LevelTransition {
+    pub project: Handle<LdtkProject>,
+    pub tracked: Entity,
+    pub direction: NeighbourDirection,
+    pub from: String,
+    pub to: String,
+}
+
+pub fn track_levels(
+    mut trackers: Query<(Entity, &mut LdtkLevelTracker, &GlobalTransform, &mut Transform), Without<LdtkProjectCfg>>,
+    mut projects: Query<(&Handle<LdtkProject>, &mut LdtkProjectCfg, &Transform)>,
+    mut transitions: EventWriter<LevelTransition>,
+    project_assets: Res<Assets<LdtkProject>>,
+) {
+    for (tracked, mut tracker, global_transform, mut transform) in trackers.iter_mut() {
+        let project = match project_assets.get(tracker.project.clone()) {
+            Some(project) => &project.project,
+            None => continue,
+        };
+        let (mut project_cfg, project_transform) = match projects.iter_mut().find(|(h, _, _)| **h == tracker.project) {
+            Some((_, project_cfg, project_transform)) => (project_cfg, project_transform),
+            None => continue,
+        };
+
+        // Single levels are rendered at the project origin instead of their world position
+        let single_level = match project_cfg.render_type {
+            LdtkRenderType::SingleLevel(idx) => project.levels.get(idx),
+            _ => None,
+        };
+        let origin = single_level.map_or((0, 0), |level| (level.world_x, level.world_y));
+        let offset = global_transform.translation - project_transform.translation;
+        let (x, y) = (
+            offset.x.floor() as i64 + origin.0,
+            (-offset.y).floor() as i64 + origin.1,
+        );
+
+        let current = match (single_level, tracker.level_uid) {
+            (Some(level), _) => Some(level),
+            (None, Some(uid)) => project.levels.iter().find(|l| l.uid == uid),
+            (None, None) => project.level_at(x, y),
+        };
+        let current = match current {
+            Some(level) => level,
+            None => continue,
+        };
+        tracker.level_uid = Some(current.uid);
+        if current.world_bounds().contains(x, y) {
+            continue;
+        }
+
+        let (idx, next, direction) = match (find_neighbour(project, current, x, y), single_level) {
+            (Some(neighbour), _) => neighbour,
+            // Moved to a level that is not a neighbour: follow it, without a direction to report a transition
+            (None, None) => {
+                if let Some(level) = project.level_at(x, y) {
+                    debug!(
+                        "Tracked entity moved from {} to {}",
+                        current.identifier, level.identifier
+                    );
+                    tracker.level_uid = Some(level.uid);
+                }
+                continue;
+            }
+            (None, Some(_)) => continue,
+        };
+
+        debug!("Level transition from {} to {}", current.identifier, next.identifier);
+        if single_level.is_some() {
+            transform.translation += transition_offset(current, next).extend(0.);
+            project_cfg.render_type = LdtkRenderType::SingleLevel(idx);
+        }
+        tracker.level_uid = Some(next.uid);
+        transitions.send(LevelTransition {
+            project: tracker.project.clone(),
+            tracked,
+            direction,
+            from: current.identifier.clone(),
+            to: next.identifier.clone(),
+        });
+    }
+}
+
+/// The neighbour of `current` holding the world pixel (`x`, `y`), with its index in the project and its direction.
+fn find_neighbour<'a>(
+    project: &'a ldtk::Project,
+    current: &ldtk::Level,
+    x: i64,
+    y: i64,
+) -> Option<(usize, &'a ldtk::Level, NeighbourDirection)> {
+    current.neighbours.iter().find_map(|neighbour| {
+        let (idx, level) = project
+            .levels
+            .iter()
+            .enumerate()
+            .find(|(_, l)| l.uid == neighbour.level_uid)?;
+        let direction = NeighbourDirection::from_dir(&neighbour.dir)?;
+        if level.world_bounds().contains(x, y) {
+            Some((idx, level, direction))
+        } else {
+            None
+        }
+    })
+}
+
+/// Translation keeping an entity at its world position when the single level rendered at the project origin goes
+/// from `current` to `next`.
+fn transition_offset(current: &ldtk::Level, next: &ldtk::Level) -> Vec2 {
+    Vec2::new(
+        (current.world_x - next.world_x) as f32,
+        -(current.world_y - next.world_y) as f32,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ldtk::ProjectBuilder;
+
+    /// A hub with a level on each side, and a level touching its bottom-right corner only.
+    fn project() -> ldtk::Project {
+        let mut builder = ProjectBuilder::new();
+        for (identifier, x, y) in &[
+            ("Hub", 0, 0),
+            ("North", 0, -128),
+            ("South", -64, 128),
+            ("East", 256, -64),
+            ("West", -256, 0),
+            ("Corner", 256, 128),
+        ] {
+            let mut level = builder.level(identifier, 256, 128);
+            level.world_position(*x, *y);
+            level.finish();
+        }
+        let mut project = builder.build();
+        project.update_neighbours();
+        project
+    }
+
+    #[test]
+    fn directions() {
+        assert_eq!(NeighbourDirection::from_dir("n"), Some(NeighbourDirection::North));
+        assert_eq!(NeighbourDirection::from_dir("s"), Some(NeighbourDirection::South));
+        assert_eq!(NeighbourDirection::from_dir("e"), Some(NeighbourDirection::East));
+        assert_eq!(NeighbourDirection::from_dir("w"), Some(NeighbourDirection::West));
+        assert_eq!(NeighbourDirection::from_dir("x"), None);
+    }
+
+    #[test]
+    fn neighbours() {
+        let project = project();
+        let hub = &project.levels[0];
+        let neighbour =
+            |x, y| find_neighbour(&project, hub, x, y).map(|(idx, level, dir)| (idx, &level.identifier[..], dir));
+
+        assert_eq!(neighbour(10, -1), Some((1, "North", NeighbourDirection::North)));
+        assert_eq!(neighbour(100, 128), Some((2, "South", NeighbourDirection::South)));
+        assert_eq!(neighbour(256, 0), Some((3, "East", NeighbourDirection::East)));
+        assert_eq!(neighbour(-1, 127), Some((4, "West", NeighbourDirection::West)));
+        // Not a neighbour, or no level at all
+        assert_eq!(neighbour(256, 128), None);
+        assert_eq!(neighbour(200, 128), None);
+        assert_eq!(neighbour(10, 10), None);
+    }
+
+    #[test]
+    fn translation() {
+        let project = project();
+        let (hub, east, west) = (&project.levels[0], &project.levels[3], &project.levels[4]);
+
+        // Leaving the hub at (260, 10) in the world, that is (4, 74) in the east level
+        let offset = transition_offset(hub, east);
+        assert_eq!(Vec2::new(260., -10.) + offset, Vec2::new(4., -74.));
+        assert_eq!(transition_offset(east, hub), -offset);
+        assert_eq!(transition_offset(hub, west), Vec2::new(256., 0.));
+    }
+}
@@ -0,0 +1,512 @@
+//! Evaluation of auto-layer rules, so that `autoLayerTiles` can be kept up to date when IntGrid values change at
+//! runtime.
+//!
+//! Rules are matched the way the editor does it, using the same coordinate hash for rule chances and random tiles,
+//! so a full rebuild gives back what the editor saved. Perlin filtering isn't supported: rules using it are
+//! applied everywhere.
+//!
+//! Updates only touch the cells around the edited ones, within the radius of the largest rule pattern, and return
+//! the tiles that appeared and disappeared so renderers can patch what they display.
+
+use crate::*;
+use std::collections::HashMap;
+
+/// Value of a pattern cell matching any non-empty cell, or any empty cell when negated.
+const ANYTHING: i64 = 1_000_001;
+
+/// Tiles added to and removed from the auto-layer tiles of a layer.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AutoTileDelta {
+    pub layer_def_uid: i64,
+    pub added: Vec<TileInstance>,
+    pub removed: Vec<TileInstance>,
+}
+
+impl AutoTileDelta {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// Recomputes the auto-layer tiles of every layer of level `level_uid` fed by the IntGrid layer `source_layer_uid`,
+/// after the IntGrid cells in `dirty` changed. Returns the changes of every layer that actually changed.
+pub fn update(project: &mut Project, level_uid: i64, source_layer_uid: i64, dirty: Rect) -> Vec<AutoTileDelta> {
+    update_layers(
+        project,
+        level_uid,
+        |def| def_source(def) == Some(source_layer_uid),
+        Some(dirty),
+    )
+}
+
+/// Recomputes all auto-layer tiles of level `level_uid` from scratch.
+pub fn rebuild(project: &mut Project, level_uid: i64) -> Vec<AutoTileDelta> {
+    update_layers(project, level_uid, |def| def_source(def).is_some(), None)
+}
+
+/// The IntGrid layer a layer's rules read from, if it has any rules.
+fn def_source(def: &LayerDefinition) -> Option<i64> {
+    if def.auto_rule_groups.is_empty() {
+        return None;
+    }
+    match def.purple_type {
+        Type::IntGrid => Some(def.uid),
+        _ => def.auto_source_layer_def_uid,
+    }
+}
+
+/// IntGrid values of a layer, copied so that layers of the same level can be updated while reading them.
+struct Source {
+    c_wid: i64,
+    c_hei: i64,
+    values: Vec<i64>,
+}
+
+impl Source {
+    /// The value of cell (`cx`, `cy`), `None` outside of the layer, or past the end of a too short CSV.
+    fn get(&self, cx: i64, cy: i64) -> Option<i64> {
+        if cx >= 0 && cx < self.c_wid && cy >= 0 && cy < self.c_hei {
+            self.values.get((cy * self.c_wid + cx) as usize).copied()
+        } else {
+            None
+        }
+    }
+}
+
+fn update_layers(
+    project: &mut Project,
+    level_uid: i64,
+    filter: impl Fn(&LayerDefinition) -> bool,
+    dirty: Option<Rect>,
+) -> Vec<AutoTileDelta> {
+    let Project { defs, levels, .. } = project;
+    let level = match levels.iter_mut().find(|l| l.uid == level_uid) {
+        Some(level) => level,
+        None => return Vec::new(),
+    };
+    let layers = match &mut level.layer_instances {
+        Some(layers) => layers,
+        None => return Vec::new(),
+    };
+
+    let mut deltas = Vec::new();
+    for i in 0..layers.len() {
+        let def = match defs.layers.iter().find(|d| d.uid == layers[i].layer_def_uid) {
+            Some(def) if filter(def) => def,
+            _ => continue,
+        };
+        let source = match layers.iter().find(|l| Some(l.layer_def_uid) == def_source(def)) {
+            Some(l) => Source {
+                c_wid: l.c_wid,
+                c_hei: l.c_hei,
+                values: l.int_grid_csv.clone(),
+            },
+            None => continue,
+        };
+        let layer = &mut layers[i];
+        let tileset = layer
+            .override_tileset_uid
+            .or(def.auto_tileset_def_uid)
+            .and_then(|uid| defs.tilesets.iter().find(|t| t.uid == uid));
+        let tileset = match tileset {
+            Some(tileset) => tileset,
+            None => continue,
+        };
+
+        let delta = update_layer(def, tileset, &source, layer, dirty);
+        if !delta.is_empty() {
+            deltas.push(delta);
+        }
+    }
+    deltas
+}
+
+fn update_layer(
+    def: &LayerDefinition,
+    tileset: &TilesetDefinition,
+    source: &Source,
+    layer: &mut LayerInstance,
+    dirty: Option<Rect>,
+) -> AutoTileDelta {
+    let rules: Vec<&AutoLayerRuleDefinition> = def
+        .auto_rule_groups
+        .iter()
+        .filter(|g| g.active)
+        .flat_map(|g| g.rules.iter().filter(|r| r.active))
+        .collect();
+    let bounds = Rect::new(0, 0, layer.c_wid, layer.c_hei);
+    let region = match dirty {
+        Some(dirty) => {
+            let radius = rules.iter().map(|r| r.size / 2).max().unwrap_or(0);
+            let grown = Rect::new(
+                dirty.x - radius,
+                dirty.y - radius,
+                dirty.width + 2 * radius,
+                dirty.height + 2 * radius,
+            );
+            grown.intersection(&bounds)
+        }
+        None => Some(bounds),
+    };
+    let region = match region {
+        Some(region) => region,
+        None => return AutoTileDelta::default(),
+    };
+
+    let c_wid = layer.c_wid.max(1);
+    let in_region = |tile: &TileInstance| match tile.d[..] {
+        [_, coord] => region.contains(coord % c_wid, coord / c_wid),
+        _ => false,
+    };
+    let (mut removed, kept): (Vec<_>, Vec<_>) = layer.auto_layer_tiles.drain(..).partition(|t| in_region(t));
+
+    let mut added = Vec::new();
+    for cy in region.y..region.bottom() {
+        for cx in region.x..region.right() {
+            apply_rules_at(def, tileset, source, layer, &rules, cx, cy, &mut added);
+        }
+    }
+
+    // Tiles are listed in drawing order: the rule evaluated first is drawn last, on top.
+    let rank: HashMap<i64, usize> = rules
+        .iter()
+        .enumerate()
+        .map(|(i, r)| (r.uid, rules.len() - i))
+        .collect();
+    layer.auto_layer_tiles = kept;
+    layer.auto_layer_tiles.extend(added.iter().cloned());
+    layer.auto_layer_tiles.sort_by_key(|t| {
+        let rank = t.d.first().and_then(|uid| rank.get(uid)).copied().unwrap_or(0);
+        (rank, t.d.get(1).copied().unwrap_or(0))
+    });
+
+    // Tiles that were recomputed identically are not part of the delta.
+    added.retain(|tile| match removed.iter().position(|t| t == tile) {
+        Some(i) => {
+            removed.swap_remove(i);
+            false
+        }
+        None => true,
+    });
+    AutoTileDelta {
+        layer_def_uid: layer.layer_def_uid,
+        added,
+        removed,
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn apply_rules_at(
+    def: &LayerDefinition,
+    tileset: &TilesetDefinition,
+    source: &Source,
+    layer: &LayerInstance,
+    rules: &[&AutoLayerRuleDefinition],
+    cx: i64,
+    cy: i64,
+    tiles: &mut Vec<TileInstance>,
+) {
+    for rule in rules {
+        if !modulo_matches(rule, cx, cy) {
+            continue;
+        }
+
+        let mut matched = false;
+        for &(flip_x, flip_y) in &[(false, false), (true, false), (false, true), (true, true)] {
+            if (flip_x && !rule.flip_x) || (flip_y && !rule.flip_y) || (matched && rule.break_on_match) {
+                continue;
+            }
+            if rule_matches(rule, layer.seed, source, cx, cy, flip_x, flip_y) {
+                add_rule_tiles(def, tileset, layer, rule, cx, cy, flip_x, flip_y, tiles);
+                matched = true;
+            }
+        }
+        if matched && rule.break_on_match {
+            break;
+        }
+    }
+}
+
+fn modulo_matches(rule: &AutoLayerRuleDefinition, cx: i64, cy: i64) -> bool {
+    let (x_modulo, y_modulo) = (rule.x_modulo.max(1), rule.y_modulo.max(1));
+    let x_ok = match rule.checker {
+        Checker::Horizontal => (cx + (cy / y_modulo) % 2) % x_modulo == 0,
+        _ => cx % x_modulo == 0,
+    };
+    let y_ok = match rule.checker {
+        Checker::Vertical => (cy + (cx / x_modulo) % 2) % y_modulo == 0,
+        _ => cy % y_modulo == 0,
+    };
+    x_ok && y_ok
+}
+
+fn rule_matches(
+    rule: &AutoLayerRuleDefinition,
+    seed: i64,
+    source: &Source,
+    cx: i64,
+    cy: i64,
+    flip_x: bool,
+    flip_y: bool,
+) -> bool {
+    if rule.tile_ids.is_empty() {
+        return false;
+    }
+    if rule.chance <= 0.
+        || (rule.chance < 1. && rand_seed_coords(seed + rule.uid, cx, cy, 100) as f64 >= rule.chance * 100.)
+    {
+        return false;
+    }
+
+    let radius = rule.size / 2;
+    let (dir_x, dir_y) = (if flip_x { -1 } else { 1 }, if flip_y { -1 } else { 1 });
+    for py in 0..rule.size {
+        for px in 0..rule.size {
+            let expected = rule.pattern.get((px + py * rule.size) as usize).copied().unwrap_or(0);
+            if expected == 0 {
+                continue;
+            }
+            let value = source.get(cx + dir_x * (px - radius), cy + dir_y * (py - radius));
+            let value = match value.or(rule.out_of_bounds_value) {
+                Some(value) => value,
+                None => return false,
+            };
+            let ok = if expected.abs() == ANYTHING {
+                (expected > 0) == (value != 0)
+            } else if expected > 0 {
+                value == expected
+            } else {
+                value != -expected
+            };
+            if !ok {
+                return false;
+            }
+        }
+    }
+    true
+}
+
+#[allow(clippy::too_many_arguments)]
+fn add_rule_tiles(
+    def: &LayerDefinition,
+    tileset: &TilesetDefinition,
+    layer: &LayerInstance,
+    rule: &AutoLayerRuleDefinition,
+    cx: i64,
+    cy: i64,
+    flip_x: bool,
+    flip_y: bool,
+    tiles: &mut Vec<TileInstance>,
+) {
+    let grid = def.grid_size;
+    let f = flip_x as i64 | (flip_y as i64) << 1;
+    let coord = cx + cy * layer.c_wid;
+    let tile = |t: i64, x_off: i64, y_off: i64| TileInstance {
+        d: vec![rule.uid, coord],
+        f,
+        px: vec![cx * grid + x_off, cy * grid + y_off],
+        src: tileset.tile_src(t).to_vec(),
+        t,
+        ..Default::default()
+    };
+
+    match rule.tile_mode {
+        TileMode::Single => {
+            let count = rule.tile_ids.len() as i64;
+            let i = rand_seed_coords(layer.seed + rule.uid, cx, cy, count).rem_euclid(count.max(1));
+            if let Some(&t) = rule.tile_ids.get(i as usize) {
+                tiles.push(tile(t, 0, 0));
+            }
+        }
+        TileMode::Stamp => {
+            // Stamps keep the layout the tiles have in the tileset, positioned around the rule pivot.
+            let c_wid = tileset.c_wid.max(1);
+            let xs = rule.tile_ids.iter().map(|t| t % c_wid);
+            let ys = rule.tile_ids.iter().map(|t| t / c_wid);
+            let (left, right) = (xs.clone().min().unwrap_or(0), xs.max().unwrap_or(0));
+            let (top, bottom) = (ys.clone().min().unwrap_or(0), ys.max().unwrap_or(0));
+            for &t in &rule.tile_ids {
+                let x_off =
+                    ((t % c_wid - left) as f64 - rule.pivot_x * (right - left) as f64 + def.tile_pivot_x) * grid as f64;
+                let y_off =
+                    ((t / c_wid - top) as f64 - rule.pivot_y * (bottom - top) as f64 + def.tile_pivot_y) * grid as f64;
+                let x_off = if flip_x { -x_off } else { x_off } as i64;
+                let y_off = if flip_y { -y_off } else { y_off } as i64;
+                tiles.push(tile(t, x_off, y_off));
+            }
+        }
+    }
+}
+
+/// The editor's coordinate hash, used for rule chances and random tile picks. Computed with 32 bit integers like the
+/// editor, so the result can be negative.
+fn rand_seed_coords(seed: i64, x: i64, y: i64, max: i64) -> i64 {
+    let h = (seed as i32)
+        .wrapping_add((x as i32).wrapping_mul(374_761_393))
+        .wrapping_add((y as i32).wrapping_mul(668_265_263));
+    let h = (h ^ (h >> 13)).wrapping_mul(1_274_126_177);
+    ((h ^ (h >> 16)) % max.max(1) as i32) as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(size: i64, pattern: Vec<i64>, tile_id: i64, break_on_match: bool) -> AutoLayerRuleDefinition {
+        AutoLayerRuleDefinition {
+            active: true,
+            break_on_match,
+            chance: 1.,
+            checker: Checker::None,
+            flip_x: false,
+            flip_y: false,
+            out_of_bounds_value: None,
+            pattern,
+            perlin_active: false,
+            perlin_octaves: 2.,
+            perlin_scale: 0.2,
+            perlin_seed: 0.,
+            pivot_x: 0.,
+            pivot_y: 0.,
+            size,
+            tile_ids: vec![tile_id],
+            tile_mode: TileMode::Single,
+            uid: 0,
+            x_modulo: 1,
+            y_modulo: 1,
+            unknown_fields: Default::default(),
+        }
+    }
+
+    /// A 3x2 IntGrid layer with two rules: tile 1 on cells with a wall on their right, and tile 5 on other walls.
+    fn project() -> (Project, i64, i64) {
+        let mut builder = ProjectBuilder::new();
+        let tileset = builder.add_tileset(TilesetDefinition {
+            identifier: "Tiles".into(),
+            tile_grid_size: 16,
+            px_wid: 64,
+            px_hei: 64,
+            ..Default::default()
+        });
+        let layer = builder.add_layer(LayerDefinition {
+            identifier: "Walls".into(),
+            purple_type: Type::IntGrid,
+            grid_size: 16,
+            auto_tileset_def_uid: Some(tileset),
+            auto_rule_groups: vec![AutoLayerRuleGroup {
+                active: true,
+                collapsed: false,
+                is_optional: false,
+                name: "Walls".into(),
+                rules: vec![
+                    rule(3, vec![0, 0, 0, 0, 1, 1, 0, 0, 0], 1, true),
+                    rule(1, vec![1], 5, false),
+                ],
+                uid: 0,
+                unknown_fields: Default::default(),
+            }],
+            ..Default::default()
+        });
+        let mut level = builder.level("Level", 48, 32);
+        level.int_grid("Walls", 0, 0, 1);
+        level.int_grid("Walls", 1, 0, 1);
+        level.int_grid("Walls", 2, 1, 1);
+        let level = level.finish();
+        (builder.build(), level, layer)
+    }
+
+    fn tile(t: i64, cx: i64, cy: i64, rule: i64) -> TileInstance {
+        TileInstance {
+            d: vec![rule, cx + cy * 3],
+            px: vec![cx * 16, cy * 16],
+            src: vec![t % 4 * 16, t / 4 * 16],
+            t,
+            ..Default::default()
+        }
+    }
+
+    fn rule_uids(project: &Project) -> (i64, i64) {
+        let rules = &project.defs.layers[0].auto_rule_groups[0].rules;
+        (rules[0].uid, rules[1].uid)
+    }
+
+    #[test]
+    fn rebuild_applies_rules() {
+        let (mut project, level, layer) = project();
+        let (edge, wall) = rule_uids(&project);
+
+        let deltas = rebuild(&mut project, level);
+        assert_eq!(deltas.len(), 1);
+        assert_eq!(deltas[0].layer_def_uid, layer);
+        assert_eq!(
+            deltas[0].added,
+            vec![tile(1, 0, 0, edge), tile(5, 1, 0, wall), tile(5, 2, 1, wall)]
+        );
+        assert!(deltas[0].removed.is_empty());
+        // The first rule breaks on match and is drawn last, on top.
+        assert_eq!(
+            project.levels[0].layer_instances.as_ref().unwrap()[0].auto_layer_tiles,
+            vec![tile(5, 1, 0, wall), tile(5, 2, 1, wall), tile(1, 0, 0, edge)]
+        );
+    }
+
+    #[test]
+    fn update_around_changed_cells() {
+        let (mut project, level, layer) = project();
+        let (edge, wall) = rule_uids(&project);
+        rebuild(&mut project, level);
+
+        project.levels[0].layer_instances.as_mut().unwrap()[0].int_grid_csv[2] = 1;
+        let deltas = update(&mut project, level, layer, Rect::new(2, 0, 1, 1));
+        assert_eq!(
+            deltas,
+            vec![AutoTileDelta {
+                layer_def_uid: layer,
+                added: vec![tile(1, 1, 0, edge), tile(5, 2, 0, wall)],
+                removed: vec![tile(5, 1, 0, wall)],
+            }]
+        );
+        assert_eq!(
+            project.levels[0].layer_instances.as_ref().unwrap()[0].auto_layer_tiles,
+            vec![
+                tile(5, 2, 0, wall),
+                tile(5, 2, 1, wall),
+                tile(1, 0, 0, edge),
+                tile(1, 1, 0, edge)
+            ]
+        );
+    }
+
+    #[test]
+    fn malformed_tile_data() {
+        let (mut project, level, _) = project();
+        let layer = &mut project.levels[0].layer_instances.as_mut().unwrap()[0];
+        let malformed = TileInstance {
+            d: Vec::new(),
+            ..Default::default()
+        };
+        layer.auto_layer_tiles.push(malformed.clone());
+        let (edge, wall) = rule_uids(&project);
+
+        // Tiles without a cell are neither replaced nor reported, and are drawn first.
+        let deltas = rebuild(&mut project, level);
+        assert_eq!(deltas.len(), 1);
+        assert!(deltas[0].removed.is_empty());
+        assert_eq!(
+            project.levels[0].layer_instances.as_ref().unwrap()[0].auto_layer_tiles,
+            vec![malformed, tile(5, 1, 0, wall), tile(5, 2, 1, wall), tile(1, 0, 0, edge)]
+        );
+    }
+
+    #[test]
+    fn short_int_grid() {
+        let (mut project, level, _) = project();
+        let layer = &mut project.levels[0].layer_instances.as_mut().unwrap()[0];
+        layer.int_grid_csv.truncate(2);
+        let (edge, wall) = rule_uids(&project);
+
+        // Missing cells read as outside of the layer
+        let deltas = rebuild(&mut project, level);
+        assert_eq!(deltas[0].added, vec![tile(1, 0, 0, edge), tile(5, 1, 0, wall)]);
+    }
+}
@@ -0,0 +1,157 @@
+//! Placement of level background images, following the level's `bgPos` mode and pivot.
+
+use crate::*;
+use std::path::{Path, PathBuf};
+
+/// A level background image and where it goes in the level.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LevelBackground {
+    /// Path of the image, `bgRelPath` resolved against the project directory.
+    pub path: PathBuf,
+    /// Part of the image that is displayed, in image pixels: `[x, y, width, height]`.
+    pub crop: [f64; 4],
+    /// Scale of the cropped image on each axis.
+    pub scale: [f64; 2],
+    /// Top-left corner of the scaled image, in level pixels.
+    pub top_left: [f64; 2],
+}
+
+impl LevelBackground {
+    /// Size of the displayed image in level pixels.
+    pub fn size(&self) -> [f64; 2] {
+        [self.crop[2] * self.scale[0], self.crop[3] * self.scale[1]]
+    }
+}
+
+impl Level {
+    /// Computes the background of the level for an image of `image_width` by `image_height` pixels, the same way
+    /// the editor computes `__bgPos`. `base_dir` is the directory holding the project file. Returns `None` when the
+    /// level has no background image, or when the image is empty.
+    pub fn background<P: AsRef<Path>>(
+        &self,
+        base_dir: P,
+        image_width: f64,
+        image_height: f64,
+    ) -> Option<LevelBackground> {
+        let rel_path = self.bg_rel_path.as_ref()?;
+        if !(image_width > 0. && image_height > 0.) {
+            return None;
+        }
+        let (width, height) = (self.px_wid as f64, self.px_hei as f64);
+        let (scale_x, scale_y) = match self.level_bg_pos {
+            None | Some(BgPos::Unscaled) => (1., 1.),
+            Some(BgPos::Contain) => {
+                let scale = (width / image_width).min(height / image_height);
+                (scale, scale)
+            }
+            Some(BgPos::Cover) => {
+                let scale = (width / image_width).max(height / image_height);
+                (scale, scale)
+            }
+            Some(BgPos::CoverDirty) => (width / image_width, height / image_height),
+        };
+
+        // The image is cropped to the level, keeping the part around the pivot.
+        let crop_width = image_width.min(width / scale_x);
+        let crop_height = image_height.min(height / scale_y);
+        let crop = [
+            self.bg_pivot_x * (image_width - crop_width),
+            self.bg_pivot_y * (image_height - crop_height),
+            crop_width,
+            crop_height,
+        ];
+        let top_left = [
+            (self.bg_pivot_x * (width - crop_width * scale_x)).trunc(),
+            (self.bg_pivot_y * (height - crop_height * scale_y)).trunc(),
+        ];
+
+        Some(LevelBackground {
+            path: base_dir.as_ref().join(rel_path),
+            crop,
+            scale: [scale_x, scale_y],
+            top_left,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Checks the background computed for a 256x128 level against the `__bgPos` written by the editor.
+    fn check(bg_pos: BgPos, pivot: [f64; 2], image: [f64; 2], editor: serde_json::Value) {
+        let level = Level {
+            px_wid: 256,
+            px_hei: 128,
+            bg_rel_path: Some("bg.png".into()),
+            level_bg_pos: Some(bg_pos),
+            bg_pivot_x: pivot[0],
+            bg_pivot_y: pivot[1],
+            ..Default::default()
+        };
+        let editor: LevelBackgroundPosition = serde_json::from_value(editor).unwrap();
+        let background = level.background("project", image[0], image[1]).unwrap();
+        assert_eq!(background.path, Path::new("project/bg.png"));
+        assert_eq!(&background.crop[..], &editor.crop_rect[..]);
+        assert_eq!(&background.scale[..], &editor.scale[..]);
+        let top_left: Vec<i64> = background.top_left.iter().map(|&v| v as i64).collect();
+        assert_eq!(top_left, editor.top_left_px);
+    }
+
+    #[test]
+    fn unscaled() {
+        check(
+            BgPos::Unscaled,
+            [0.5, 0.5],
+            [512., 64.],
+            json!({ "cropRect": [128., 0., 256., 64.], "scale": [1., 1.], "topLeftPx": [0, 32] }),
+        );
+        check(
+            BgPos::Unscaled,
+            [0.25, 0.25],
+            [100., 50.],
+            json!({ "cropRect": [0., 0., 100., 50.], "scale": [1., 1.], "topLeftPx": [39, 19] }),
+        );
+    }
+
+    #[test]
+    fn contain() {
+        check(
+            BgPos::Contain,
+            [1., 0.5],
+            [64., 64.],
+            json!({ "cropRect": [0., 0., 64., 64.], "scale": [2., 2.], "topLeftPx": [128, 0] }),
+        );
+    }
+
+    #[test]
+    fn cover() {
+        check(
+            BgPos::Cover,
+            [0.5, 0.25],
+            [64., 64.],
+            json!({ "cropRect": [0., 8., 64., 32.], "scale": [4., 4.], "topLeftPx": [0, 0] }),
+        );
+    }
+
+    #[test]
+    fn cover_dirty() {
+        check(
+            BgPos::CoverDirty,
+            [0.5, 0.5],
+            [128., 32.],
+            json!({ "cropRect": [0., 0., 128., 32.], "scale": [2., 4.], "topLeftPx": [0, 0] }),
+        );
+    }
+
+    #[test]
+    fn no_background() {
+        let mut level = Level::default();
+        assert_eq!(level.background("", 64., 64.), None);
+        level.bg_rel_path = Some("bg.png".into());
+        assert!(level.background("", 64., 64.).is_some());
+        assert_eq!(level.background("", 0., 64.), None);
+        assert_eq!(level.background("", 64., 0.), None);
+    }
+}
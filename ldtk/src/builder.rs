@@ -0,0 +1,251 @@
+//! Programmatic construction of projects and levels, for procedural generation tools.
+//!
+//! Builders take care of uid allocation and of all the derived `__` fields, so that the result can be opened in the
+//! editor as is.
+
+use crate::*;
+use serde_json::Value;
+
+/// Builds a project, either from scratch or on top of an existing one.
+///
+/// Definitions are passed in full, usually with `..Default::default()`, and get their uids from here. Layers are
+/// listed from top to bottom, so layers added later are drawn below the earlier ones.
+pub struct ProjectBuilder {
+    project: Project,
+}
+
+impl Default for ProjectBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ProjectBuilder {
+    pub fn new() -> Self {
+        Self::from_project(Project::default())
+    }
+
+    pub fn from_project(project: Project) -> Self {
+        Self { project }
+    }
+
+    pub fn project(&self) -> &Project {
+        &self.project
+    }
+
+    /// Adds a tileset, computing its size in tiles from its image size if not set. Returns its uid.
+    pub fn add_tileset(&mut self, mut def: TilesetDefinition) -> i64 {
+        def.uid = self.project.allocate_uid();
+        let step = def.tile_grid_size + def.spacing;
+        if def.c_wid == 0 && step > 0 {
+            def.c_wid = (def.px_wid - 2 * def.padding + def.spacing) / step;
+        }
+        if def.c_hei == 0 && step > 0 {
+            def.c_hei = (def.px_hei - 2 * def.padding + def.spacing) / step;
+        }
+        let uid = def.uid;
+        self.project.defs.tilesets.push(def);
+        uid
+    }
+
+    /// Adds a layer below the existing ones, and an empty instance of it to every existing level. Returns its uid.
+    pub fn add_layer(&mut self, mut def: LayerDefinition) -> i64 {
+        def.uid = self.project.allocate_uid();
+        def.layer_definition_type = def.purple_type.name().into();
+        for group in &mut def.auto_rule_groups {
+            group.uid = self.project.allocate_uid();
+            for rule in &mut group.rules {
+                rule.uid = self.project.allocate_uid();
+            }
+        }
+
+        let mut levels = std::mem::take(&mut self.project.levels);
+        for level in &mut levels {
+            let layer = LayerInstance::new(&self.project, &def, level);
+            level.layer_instances.get_or_insert_with(Vec::new).push(layer);
+        }
+        self.project.levels = levels;
+
+        let uid = def.uid;
+        self.project.defs.layers.push(def);
+        uid
+    }
+
+    /// Adds an entity, allocating uids for its fields too. Returns its uid.
+    pub fn add_entity(&mut self, mut def: EntityDefinition) -> i64 {
+        def.uid = self.project.allocate_uid();
+        for field in &mut def.field_defs {
+            field.uid = self.project.allocate_uid();
+        }
+        let uid = def.uid;
+        self.project.defs.entities.push(def);
+        uid
+    }
+
+    /// Adds a level field, set to its default value in every existing level. Returns its uid.
+    pub fn add_level_field(&mut self, mut def: FieldDefinition) -> i64 {
+        def.uid = self.project.allocate_uid();
+        for level in &mut self.project.levels {
+            level.field_instances.push(FieldInstance::new(&def));
+        }
+        let uid = def.uid;
+        self.project.defs.level_fields.push(def);
+        uid
+    }
+
+    /// Starts a new level of `px_wid` by `px_hei` pixels, with an empty instance of every layer and every level
+    /// field set to its default. The level is added to the project by [`LevelBuilder::finish`].
+    pub fn level(&mut self, identifier: &str, px_wid: i64, px_hei: i64) -> LevelBuilder<'_> {
+        let project = &mut self.project;
+        let mut level = Level {
+            bg_color: project.default_level_bg_color,
+            identifier: identifier.into(),
+            px_hei,
+            px_wid,
+            uid: project.allocate_uid(),
+            use_auto_identifier: false,
+            field_instances: project.defs.level_fields.iter().map(FieldInstance::new).collect(),
+            ..Default::default()
+        };
+        let layers = project
+            .defs
+            .layers
+            .iter()
+            .map(|def| LayerInstance::new(project, def, &level))
+            .collect();
+        level.layer_instances = Some(layers);
+
+        LevelBuilder { project, level }
+    }
+
+    pub fn build(self) -> Project {
+        self.project
+    }
+}
+
+/// Fills in a level started with [`ProjectBuilder::level`]. Layers and entities are looked up by identifier, and
+/// unknown identifiers or out of bounds cells panic, as they are mistakes in the generator itself.
+pub struct LevelBuilder<'a> {
+    project: &'a mut Project,
+    level: Level,
+}
+
+impl<'a> LevelBuilder<'a> {
+    pub fn level(&self) -> &Level {
+        &self.level
+    }
+
+    pub fn world_position(&mut self, x: i64, y: i64) -> &mut Self {
+        self.level.world_x = x;
+        self.level.world_y = y;
+        self
+    }
+
+    pub fn bg_color(&mut self, color: Color) -> &mut Self {
+        self.level.bg_color = color;
+        self.level.level_bg_color = Some(color);
+        self
+    }
+
+    /// Sets the value of a level field.
+    pub fn field(&mut self, identifier: &str, value: Value) -> &mut Self {
+        let level = &self.level.identifier;
+        self.level
+            .field_instances
+            .iter_mut()
+            .find(|f| f.identifier == identifier)
+            .unwrap_or_else(|| panic!("level `{}` has no field `{}`", level, identifier))
+            .set_value(value);
+        self
+    }
+
+    /// Sets the IntGrid value of cell (`cx`, `cy`), 0 meaning empty.
+    pub fn int_grid(&mut self, layer: &str, cx: i64, cy: i64, value: i64) -> &mut Self {
+        let layer = self.layer(layer);
+        assert!(
+            cx >= 0 && cx < layer.c_wid && cy >= 0 && cy < layer.c_hei,
+            "cell ({}, {}) is outside of layer `{}`",
+            cx,
+            cy,
+            layer.identifier
+        );
+        layer.int_grid_csv[(cy * layer.c_wid + cx) as usize] = value;
+        self
+    }
+
+    /// Sets the IntGrid value of every cell in `cells`, clipped to the layer.
+    pub fn fill_int_grid(&mut self, layer: &str, cells: Rect, value: i64) -> &mut Self {
+        let layer = self.layer(layer);
+        if let Some(cells) = cells.intersection(&Rect::new(0, 0, layer.c_wid, layer.c_hei)) {
+            for cy in cells.y..cells.bottom() {
+                for cx in cells.x..cells.right() {
+                    layer.int_grid_csv[(cy * layer.c_wid + cx) as usize] = value;
+                }
+            }
+        }
+        self
+    }
+
+    /// Places tile `tile_id` of the layer's tileset at cell (`cx`, `cy`), on top of any tile already there.
+    pub fn tile(&mut self, layer: &str, cx: i64, cy: i64, tile_id: i64) -> &mut Self {
+        let tilesets = &self.project.defs.tilesets;
+        let layer = find_layer(&mut self.level, layer);
+        assert!(
+            cx >= 0 && cx < layer.c_wid && cy >= 0 && cy < layer.c_hei,
+            "cell ({}, {}) is outside of layer `{}`",
+            cx,
+            cy,
+            layer.identifier
+        );
+        let tileset = layer
+            .tileset_def_uid
+            .and_then(|uid| tilesets.iter().find(|t| t.uid == uid))
+            .unwrap_or_else(|| panic!("layer `{}` has no tileset", layer.identifier));
+
+        layer.grid_tiles.push(TileInstance {
+            d: vec![cx + cy * layer.c_wid],
+            px: vec![cx * layer.grid_size, cy * layer.grid_size],
+            src: tileset.tile_src(tile_id).to_vec(),
+            t: tile_id,
+            ..Default::default()
+        });
+        self
+    }
+
+    /// Places an entity with its pivot at (`x`, `y`) pixels, with all of its fields set to their defaults. The
+    /// returned instance can be tweaked further, e.g. with [`FieldInstance::set_value`].
+    pub fn entity(&mut self, layer: &str, identifier: &str, x: i64, y: i64) -> &mut EntityInstance {
+        let def = self
+            .project
+            .defs
+            .entities
+            .iter()
+            .find(|d| d.identifier == identifier)
+            .unwrap_or_else(|| panic!("no entity `{}`", identifier));
+        let layer = find_layer(&mut self.level, layer);
+        let entity = EntityInstance::new(self.project, def, layer, x, y);
+        layer.entity_instances.push(entity);
+        layer.entity_instances.last_mut().unwrap()
+    }
+
+    /// Adds the level to the project, returning its uid.
+    pub fn finish(self) -> i64 {
+        let uid = self.level.uid;
+        self.project.levels.push(self.level);
+        uid
+    }
+
+    fn layer(&mut self, identifier: &str) -> &mut LayerInstance {
+        find_layer(&mut self.level, identifier)
+    }
+}
+
+fn find_layer<'a>(level: &'a mut Level, identifier: &str) -> &'a mut LayerInstance {
+    let name = &level.identifier;
+    level
+        .layer_instances
+        .iter_mut()
+        .flatten()
+        .find(|l| l.identifier == identifier)
+        .unwrap_or_else(|| panic!("level `{}` has no layer `{}`", name, identifier))
+}
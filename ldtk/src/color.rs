@@ -0,0 +1,211 @@
+//! Colors, stored as `#RRGGBB` strings in most of the schema, and as `0xRRGGBB` integers for enum values.
+
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{error::Error, fmt, str::FromStr};
+
+/// An 8 bit per channel color, with alpha.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Color {
+    pub const BLACK: Color = Color::rgb(0, 0, 0);
+
+    pub const fn rgb(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Self { r, g, b, a }
+    }
+
+    /// Opaque color from a `0xRRGGBB` integer, as found in enum values.
+    pub const fn from_rgb_u32(rgb: u32) -> Self {
+        Self::rgb((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8)
+    }
+
+    /// The color as a `0xRRGGBB` integer, ignoring alpha.
+    pub fn to_rgb_u32(self) -> u32 {
+        (self.r as u32) << 16 | (self.g as u32) << 8 | self.b as u32
+    }
+
+    /// The color as a `0xRRGGBBAA` integer.
+    pub fn to_rgba_u32(self) -> u32 {
+        self.to_rgb_u32() << 8 | self.a as u32
+    }
+
+    pub fn to_rgba(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+
+    /// Parses a `#RRGGBB` or `#RRGGBBAA` color. The leading `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<Self, ParseColorError> {
+        let digits = hex.strip_prefix('#').unwrap_or(hex);
+        let value = match digits.len() {
+            6 | 8 if digits.bytes().all(|b| b.is_ascii_hexdigit()) => u32::from_str_radix(digits, 16).ok(),
+            _ => None,
+        };
+        match (value, digits.len()) {
+            (Some(rgb), 6) => Ok(Self::from_rgb_u32(rgb)),
+            (Some(rgba), _) => Ok(Self::from_rgb_u32(rgba >> 8).with_alpha(rgba as u8)),
+            (None, _) => Err(ParseColorError(hex.into())),
+        }
+    }
+
+    pub fn with_alpha(self, a: u8) -> Self {
+        Self { a, ..self }
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Self::BLACK
+    }
+}
+
+/// Written as `#RRGGBB`, the way the editor does, or `#RRGGBBAA` for translucent colors.
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02X}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Color {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_hex(s)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ParseColorError(String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid color `{}`, expected #RRGGBB", self.0)
+    }
+}
+
+impl Error for ParseColorError {}
+
+impl Serialize for Color {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Color {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+        Self::from_hex(&hex).map_err(de::Error::custom)
+    }
+}
+
+/// (De)serializes colors as `0xRRGGBB` integers, for `#[serde(with)]`.
+pub mod int {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(color: &Color, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u32(color.to_rgb_u32())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Color, D::Error> {
+        Ok(Color::from_rgb_u32(u32::deserialize(deserializer)?))
+    }
+}
+
+#[cfg(feature = "bevy")]
+impl From<Color> for bevy::render::color::Color {
+    fn from(color: Color) -> Self {
+        Self::rgba_u8(color.r, color.g, color.b, color.a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn from_hex() {
+        assert_eq!(Color::from_hex("#1A2b3C"), Ok(Color::rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!(Color::from_hex("1a2b3c"), Ok(Color::rgb(0x1a, 0x2b, 0x3c)));
+        assert_eq!(Color::from_hex("#1a2b3c80"), Ok(Color::rgba(0x1a, 0x2b, 0x3c, 0x80)));
+        assert_eq!("#000000".parse(), Ok(Color::BLACK));
+        for invalid in &[
+            "",
+            "#",
+            "#12345",
+            "#1234567",
+            "#123456789",
+            "#12345g",
+            "##123456",
+            "+12345",
+            "#1a2b3c ",
+        ] {
+            assert_eq!(
+                Color::from_hex(invalid),
+                Err(ParseColorError(invalid.to_string())),
+                "{}",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn display_round_trip() {
+        for color in &[
+            Color::BLACK,
+            Color::rgb(0x1a, 0x2b, 0x3c),
+            Color::rgba(0xff, 0, 0x80, 0x7f),
+        ] {
+            assert_eq!(color.to_string().parse(), Ok(*color));
+        }
+        assert_eq!(Color::rgb(0x1a, 0x2b, 0x3c).to_string(), "#1A2B3C");
+        assert_eq!(Color::rgba(0x1a, 0x2b, 0x3c, 0).to_string(), "#1A2B3C00");
+    }
+
+    #[test]
+    fn serde() {
+        let color = Color::rgb(0x1a, 0x2b, 0x3c);
+        assert_eq!(serde_json::to_value(color).unwrap(), json!("#1A2B3C"));
+        assert_eq!(serde_json::from_value::<Color>(json!("#1a2b3c")).unwrap(), color);
+        assert!(serde_json::from_value::<Color>(json!("red")).is_err());
+    }
+
+    #[test]
+    fn serde_int() {
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        struct Value {
+            #[serde(with = "int")]
+            color: Color,
+        }
+
+        let value = Value {
+            color: Color::rgb(0x1a, 0x2b, 0x3c),
+        };
+        assert_eq!(serde_json::to_value(&value).unwrap(), json!({ "color": 0x1a2b3c }));
+        assert_eq!(
+            serde_json::from_value::<Value>(json!({ "color": 0x1a2b3c })).unwrap(),
+            value
+        );
+        // Alpha can't be written as an integer.
+        let translucent = Value {
+            color: Color::rgba(0x1a, 0x2b, 0x3c, 0x80),
+        };
+        assert_eq!(
+            serde_json::to_value(&translucent).unwrap(),
+            json!({ "color": 0x1a2b3c })
+        );
+        assert!(serde_json::from_value::<Value>(json!({ "color": "#1a2b3c" })).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+//! Default values for schema types, matching what the editor uses for newly created objects.
+//!
+//! These are kept out of `schema.rs`, which is generated, hence the manual impls.
+#![allow(clippy::derivable_impls)]
+
+use crate::*;
+
+impl Default for Project {
+    fn default() -> Self {
+        Self {
+            backup_limit: 10,
+            backup_on_save: false,
+            bg_color: Color::from_rgb_u32(0x40465B),
+            default_grid_size: 16,
+            default_level_bg_color: Color::from_rgb_u32(0x696A79),
+            default_level_height: 256,
+            default_level_width: 256,
+            default_pivot_x: 0.,
+            default_pivot_y: 0.,
+            defs: Definitions::default(),
+            export_png: None,
+            export_tiled: false,
+            external_levels: false,
+            flags: Vec::new(),
+            image_export_mode: ImageExportMode::default(),
+            json_version: "0.9.3".into(),
+            level_name_pattern: "Level_%idx".into(),
+            levels: Vec::new(),
+            minify_json: false,
+            next_uid: 0,
+            png_file_pattern: None,
+            world_grid_height: 256,
+            world_grid_width: 256,
+            world_layout: WorldLayout::default(),
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for Definitions {
+    fn default() -> Self {
+        Self {
+            entities: Vec::new(),
+            enums: Vec::new(),
+            external_enums: Vec::new(),
+            layers: Vec::new(),
+            level_fields: Vec::new(),
+            tilesets: Vec::new(),
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for EntityDefinition {
+    fn default() -> Self {
+        Self {
+            color: Color::from_rgb_u32(0x94D9B3),
+            field_defs: Vec::new(),
+            fill_opacity: 1.,
+            height: 16,
+            hollow: false,
+            identifier: "Entity".into(),
+            keep_aspect_ratio: false,
+            limit_behavior: LimitBehavior::default(),
+            limit_scope: LimitScope::default(),
+            line_opacity: 1.,
+            max_count: 0,
+            pivot_x: 0.,
+            pivot_y: 0.,
+            render_mode: RenderMode::default(),
+            resizable_x: false,
+            resizable_y: false,
+            show_name: true,
+            tags: Vec::new(),
+            tile_id: None,
+            tile_render_mode: TileRenderMode::default(),
+            tileset_id: None,
+            uid: 0,
+            width: 16,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for FieldDefinition {
+    fn default() -> Self {
+        Self {
+            field_definition_type: "Int".into(),
+            accept_file_types: None,
+            array_max_length: None,
+            array_min_length: None,
+            can_be_null: false,
+            default_override: None,
+            editor_always_show: false,
+            editor_cut_long_values: true,
+            editor_display_mode: EditorDisplayMode::default(),
+            editor_display_pos: EditorDisplayPos::default(),
+            identifier: "Field".into(),
+            is_array: false,
+            max: None,
+            min: None,
+            regex: None,
+            text_language_mode: None,
+            purple_type: Some(serde_json::json!({"id": "F_Int", "params": []})),
+            uid: 0,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for LayerDefinition {
+    fn default() -> Self {
+        Self {
+            layer_definition_type: "IntGrid".into(),
+            auto_rule_groups: Vec::new(),
+            auto_source_layer_def_uid: None,
+            auto_tileset_def_uid: None,
+            display_opacity: 1.,
+            excluded_tags: Vec::new(),
+            grid_size: 16,
+            identifier: "Layer".into(),
+            int_grid_values: Vec::new(),
+            px_offset_x: 0,
+            px_offset_y: 0,
+            required_tags: Vec::new(),
+            tile_pivot_x: 0.,
+            tile_pivot_y: 0.,
+            tileset_def_uid: None,
+            purple_type: Type::default(),
+            uid: 0,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for IntGridValueDefinition {
+    fn default() -> Self {
+        Self {
+            color: Color::BLACK,
+            identifier: None,
+            value: 1,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for TilesetDefinition {
+    fn default() -> Self {
+        Self {
+            c_hei: 0,
+            c_wid: 0,
+            cached_pixel_data: None,
+            custom_data: Vec::new(),
+            enum_tags: Vec::new(),
+            identifier: "Tileset".into(),
+            padding: 0,
+            px_hei: 0,
+            px_wid: 0,
+            rel_path: String::new(),
+            saved_selections: Vec::new(),
+            spacing: 0,
+            tags_source_enum_uid: None,
+            tile_grid_size: 16,
+            uid: 0,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            bg_color: Color::from_rgb_u32(0x696A79),
+            bg_pos: None,
+            neighbours: Vec::new(),
+            level_bg_color: None,
+            bg_pivot_x: 0.5,
+            bg_pivot_y: 0.5,
+            level_bg_pos: None,
+            bg_rel_path: None,
+            external_rel_path: None,
+            field_instances: Vec::new(),
+            identifier: "Level".into(),
+            layer_instances: Some(Vec::new()),
+            px_hei: 256,
+            px_wid: 256,
+            uid: 0,
+            use_auto_identifier: true,
+            world_x: 0,
+            world_y: 0,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for FieldInstance {
+    fn default() -> Self {
+        Self {
+            identifier: String::new(),
+            field_instance_type: "Int".into(),
+            value: None,
+            def_uid: 0,
+            real_editor_values: Vec::new(),
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for LayerInstance {
+    fn default() -> Self {
+        Self {
+            c_hei: 0,
+            c_wid: 0,
+            grid_size: 16,
+            identifier: String::new(),
+            opacity: 1.,
+            px_total_offset_x: 0,
+            px_total_offset_y: 0,
+            tileset_def_uid: None,
+            tileset_rel_path: None,
+            layer_instance_type: "IntGrid".into(),
+            auto_layer_tiles: Vec::new(),
+            entity_instances: Vec::new(),
+            grid_tiles: Vec::new(),
+            int_grid: None,
+            int_grid_csv: Vec::new(),
+            layer_def_uid: 0,
+            level_id: 0,
+            optional_rules: Vec::new(),
+            override_tileset_uid: None,
+            px_offset_x: 0,
+            px_offset_y: 0,
+            seed: 0,
+            visible: true,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for TileInstance {
+    fn default() -> Self {
+        Self {
+            d: Vec::new(),
+            f: 0,
+            px: vec![0, 0],
+            src: vec![0, 0],
+            t: 0,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for EntityInstance {
+    fn default() -> Self {
+        Self {
+            grid: vec![0, 0],
+            identifier: String::new(),
+            pivot: vec![0., 0.],
+            tile: None,
+            def_uid: 0,
+            field_instances: Vec::new(),
+            height: 16,
+            px: vec![0, 0],
+            width: 16,
+            unknown_fields: serde_json::Map::new(),
+        }
+    }
+}
+
+impl Default for EditorDisplayMode {
+    fn default() -> Self {
+        EditorDisplayMode::ValueOnly
+    }
+}
+
+impl Default for EditorDisplayPos {
+    fn default() -> Self {
+        EditorDisplayPos::Above
+    }
+}
+
+impl Default for LimitBehavior {
+    fn default() -> Self {
+        LimitBehavior::MoveLastOne
+    }
+}
+
+impl Default for LimitScope {
+    fn default() -> Self {
+        LimitScope::PerLevel
+    }
+}
+
+impl Default for RenderMode {
+    fn default() -> Self {
+        RenderMode::Rectangle
+    }
+}
+
+impl Default for TileRenderMode {
+    fn default() -> Self {
+        TileRenderMode::FitInside
+    }
+}
+
+impl Default for Type {
+    fn default() -> Self {
+        Type::IntGrid
+    }
+}
+
+impl Default for ImageExportMode {
+    fn default() -> Self {
+        ImageExportMode::None
+    }
+}
+
+impl Default for WorldLayout {
+    fn default() -> Self {
+        WorldLayout::Free
+    }
+}
@@ -0,0 +1,749 @@
+//! Semantic comparison of two projects, for reviewing level changes without reading raw JSON diffs.
+
+use crate::*;
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+/// The kind of definition a [`Change::DefinitionAdded`] and friends refer to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize)]
+pub enum DefinitionKind {
+    Layer,
+    Entity,
+    Enum,
+    Tileset,
+    LevelField,
+}
+
+impl fmt::Display for DefinitionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DefinitionKind::Layer => "layer",
+            DefinitionKind::Entity => "entity",
+            DefinitionKind::Enum => "enum",
+            DefinitionKind::Tileset => "tileset",
+            DefinitionKind::LevelField => "level field",
+        })
+    }
+}
+
+/// A single difference between two projects. Levels and layers are named by their identifier in the newer
+/// project, positions are in level pixels and cell bounds are in grid cells.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "change")]
+pub enum Change {
+    LevelAdded {
+        level: String,
+    },
+    LevelRemoved {
+        level: String,
+    },
+    LevelRenamed {
+        from: String,
+        to: String,
+    },
+    LevelMoved {
+        level: String,
+        from: (i64, i64),
+        to: (i64, i64),
+    },
+    LevelResized {
+        level: String,
+        from: (i64, i64),
+        to: (i64, i64),
+    },
+    LevelFieldChanged {
+        level: String,
+        field: String,
+        from: Option<Value>,
+        to: Option<Value>,
+    },
+    /// IntGrid values or grid tiles changed in `count` cells, all within `bounds`.
+    CellsChanged {
+        level: String,
+        layer: String,
+        count: usize,
+        bounds: Rect,
+    },
+    EntityAdded {
+        level: String,
+        layer: String,
+        entity: String,
+        at: (i64, i64),
+    },
+    EntityRemoved {
+        level: String,
+        layer: String,
+        entity: String,
+        at: (i64, i64),
+    },
+    EntityMoved {
+        level: String,
+        layer: String,
+        entity: String,
+        from: (i64, i64),
+        to: (i64, i64),
+    },
+    EntityFieldChanged {
+        level: String,
+        layer: String,
+        entity: String,
+        at: (i64, i64),
+        field: String,
+        from: Option<Value>,
+        to: Option<Value>,
+    },
+    DefinitionAdded {
+        kind: DefinitionKind,
+        identifier: String,
+    },
+    DefinitionRemoved {
+        kind: DefinitionKind,
+        identifier: String,
+    },
+    DefinitionEdited {
+        kind: DefinitionKind,
+        identifier: String,
+    },
+    /// A project-wide setting, named by its JSON key.
+    SettingChanged {
+        setting: String,
+    },
+}
+
+impl Change {
+    /// The level this change happened in, if it is local to an existing level.
+    pub fn level(&self) -> Option<&str> {
+        match self {
+            Change::LevelMoved { level, .. }
+            | Change::LevelResized { level, .. }
+            | Change::LevelFieldChanged { level, .. }
+            | Change::CellsChanged { level, .. }
+            | Change::EntityAdded { level, .. }
+            | Change::EntityRemoved { level, .. }
+            | Change::EntityMoved { level, .. }
+            | Change::EntityFieldChanged { level, .. } => Some(level),
+            _ => None,
+        }
+    }
+}
+
+fn value_str(value: &Option<Value>) -> String {
+    value.as_ref().unwrap_or(&Value::Null).to_string()
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::LevelAdded { level } => write!(f, "Added level `{}`", level),
+            Change::LevelRemoved { level } => write!(f, "Removed level `{}`", level),
+            Change::LevelRenamed { from, to } => write!(f, "Renamed level `{}` to `{}`", from, to),
+            Change::LevelMoved { from, to, .. } => write!(f, "Moved from {:?} to {:?}", from, to),
+            Change::LevelResized { from, to, .. } => {
+                write!(f, "Resized from {}x{} to {}x{} px", from.0, from.1, to.0, to.1)
+            }
+            Change::LevelFieldChanged { field, from, to, .. } => write!(
+                f,
+                "Field `{}` changed from {} to {}",
+                field,
+                value_str(from),
+                value_str(to)
+            ),
+            Change::CellsChanged {
+                layer, count, bounds, ..
+            } => write!(
+                f,
+                "`{}`: {} cell{} changed within {}x{} cells at ({}, {})",
+                layer,
+                count,
+                if *count == 1 { "" } else { "s" },
+                bounds.width,
+                bounds.height,
+                bounds.x,
+                bounds.y
+            ),
+            Change::EntityAdded { layer, entity, at, .. } => {
+                write!(f, "`{}`: added `{}` at {:?}", layer, entity, at)
+            }
+            Change::EntityRemoved { layer, entity, at, .. } => {
+                write!(f, "`{}`: removed `{}` at {:?}", layer, entity, at)
+            }
+            Change::EntityMoved {
+                layer,
+                entity,
+                from,
+                to,
+                ..
+            } => write!(f, "`{}`: moved `{}` from {:?} to {:?}", layer, entity, from, to),
+            Change::EntityFieldChanged {
+                layer,
+                entity,
+                at,
+                field,
+                from,
+                to,
+                ..
+            } => write!(
+                f,
+                "`{}`: `{}` at {:?} changed `{}` from {} to {}",
+                layer,
+                entity,
+                at,
+                field,
+                value_str(from),
+                value_str(to)
+            ),
+            Change::DefinitionAdded { kind, identifier } => write!(f, "Added {} definition `{}`", kind, identifier),
+            Change::DefinitionRemoved { kind, identifier } => {
+                write!(f, "Removed {} definition `{}`", kind, identifier)
+            }
+            Change::DefinitionEdited { kind, identifier } => write!(f, "Edited {} definition `{}`", kind, identifier),
+            Change::SettingChanged { setting } => write!(f, "Changed project setting `{}`", setting),
+        }
+    }
+}
+
+/// All changes between two projects, in a stable order: project-wide changes first, then per level in the
+/// order levels appear in the newer project.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct ProjectDiff {
+    pub changes: Vec<Change>,
+}
+
+impl ProjectDiff {
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Renders the changes as a Markdown list grouped by level, suitable for pull request comments.
+impl fmt::Display for ProjectDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No changes.");
+        }
+
+        let mut current = None;
+        for (i, change) in self.changes.iter().enumerate() {
+            let level = change.level();
+            if i == 0 || level != current {
+                if i != 0 {
+                    writeln!(f)?;
+                }
+                match level {
+                    Some(level) => writeln!(f, "### Level `{}`", level)?,
+                    None => writeln!(f, "### Project")?,
+                }
+                current = level;
+            }
+            writeln!(f, "- {}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compares `old` against `new`. Levels and definitions are matched by uid; entities have no uid in this
+/// format, so they are paired up by definition, then position, preferring unchanged entities.
+pub fn diff(old: &Project, new: &Project) -> ProjectDiff {
+    let mut changes = Vec::new();
+
+    diff_settings(old, new, &mut changes);
+    diff_defs(
+        DefinitionKind::Layer,
+        &old.defs.layers,
+        &new.defs.layers,
+        |d| (d.uid, &d.identifier),
+        &mut changes,
+    );
+    diff_defs(
+        DefinitionKind::Entity,
+        &old.defs.entities,
+        &new.defs.entities,
+        |d| (d.uid, &d.identifier),
+        &mut changes,
+    );
+    diff_defs(
+        DefinitionKind::Enum,
+        &old.defs.enums,
+        &new.defs.enums,
+        |d| (d.uid, &d.identifier),
+        &mut changes,
+    );
+    diff_defs(
+        DefinitionKind::Tileset,
+        &old.defs.tilesets,
+        &new.defs.tilesets,
+        |d| (d.uid, &d.identifier),
+        &mut changes,
+    );
+    diff_defs(
+        DefinitionKind::LevelField,
+        &old.defs.level_fields,
+        &new.defs.level_fields,
+        |d| (d.uid, &d.identifier),
+        &mut changes,
+    );
+
+    for level in &old.levels {
+        if new.get_level(level.uid).is_none() {
+            changes.push(Change::LevelRemoved {
+                level: level.identifier.clone(),
+            });
+        }
+    }
+    for level in &new.levels {
+        match old.get_level(level.uid) {
+            None => changes.push(Change::LevelAdded {
+                level: level.identifier.clone(),
+            }),
+            Some(previous) if previous.identifier != level.identifier => changes.push(Change::LevelRenamed {
+                from: previous.identifier.clone(),
+                to: level.identifier.clone(),
+            }),
+            _ => (),
+        }
+    }
+    for level in &new.levels {
+        if let Some(previous) = old.get_level(level.uid) {
+            diff_level(previous, level, &mut changes);
+        }
+    }
+
+    ProjectDiff { changes }
+}
+
+fn diff_settings(old: &Project, new: &Project, changes: &mut Vec<Change>) {
+    let (old, new) = match (serde_json::to_value(old), serde_json::to_value(new)) {
+        (Ok(Value::Object(old)), Ok(Value::Object(new))) => (old, new),
+        _ => return,
+    };
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        // Definitions and levels are compared on their own; the uid counter moves with every addition.
+        if key == "defs" || key == "levels" || key == "nextUid" {
+            continue;
+        }
+        if old.get(key) != new.get(key) {
+            changes.push(Change::SettingChanged { setting: key.clone() });
+        }
+    }
+}
+
+fn diff_defs<T: PartialEq>(
+    kind: DefinitionKind,
+    old: &[T],
+    new: &[T],
+    key: impl Fn(&T) -> (i64, &String),
+    changes: &mut Vec<Change>,
+) {
+    for def in old {
+        if !new.iter().any(|d| key(d).0 == key(def).0) {
+            changes.push(Change::DefinitionRemoved {
+                kind,
+                identifier: key(def).1.clone(),
+            });
+        }
+    }
+    for def in new {
+        match old.iter().find(|d| key(d).0 == key(def).0) {
+            None => changes.push(Change::DefinitionAdded {
+                kind,
+                identifier: key(def).1.clone(),
+            }),
+            Some(previous) if previous != def => changes.push(Change::DefinitionEdited {
+                kind,
+                identifier: key(def).1.clone(),
+            }),
+            _ => (),
+        }
+    }
+}
+
+fn diff_level(old: &Level, new: &Level, changes: &mut Vec<Change>) {
+    let level = &new.identifier;
+
+    if (old.world_x, old.world_y) != (new.world_x, new.world_y) {
+        changes.push(Change::LevelMoved {
+            level: level.clone(),
+            from: (old.world_x, old.world_y),
+            to: (new.world_x, new.world_y),
+        });
+    }
+    if (old.px_wid, old.px_hei) != (new.px_wid, new.px_hei) {
+        changes.push(Change::LevelResized {
+            level: level.clone(),
+            from: (old.px_wid, old.px_hei),
+            to: (new.px_wid, new.px_hei),
+        });
+    }
+    for (field, from, to) in diff_fields(&old.field_instances, &new.field_instances) {
+        changes.push(Change::LevelFieldChanged {
+            level: level.clone(),
+            field,
+            from,
+            to,
+        });
+    }
+
+    let empty = Vec::new();
+    let old_layers = old.layer_instances.as_ref().unwrap_or(&empty);
+    for layer in new.layer_instances.as_ref().unwrap_or(&empty) {
+        // Layers only appear or disappear along with their definition, which is reported already.
+        if let Some(previous) = old_layers.iter().find(|l| l.layer_def_uid == layer.layer_def_uid) {
+            diff_layer(level, previous, layer, changes);
+        }
+    }
+}
+
+fn diff_layer(level: &str, old: &LayerInstance, new: &LayerInstance, changes: &mut Vec<Change>) {
+    let cells = match new.layer_instance_type.as_str() {
+        "IntGrid" => diff_int_grid(old, new),
+        // Auto-layer tiles are derived from IntGrid values, so only hand-placed tiles are compared.
+        "Tiles" => diff_tiles(old, new),
+        _ => Vec::new(),
+    };
+    if let Some(&(x, y)) = cells.first() {
+        let (mut left, mut top, mut right, mut bottom) = (x, y, x, y);
+        for &(x, y) in &cells {
+            left = left.min(x);
+            top = top.min(y);
+            right = right.max(x);
+            bottom = bottom.max(y);
+        }
+        changes.push(Change::CellsChanged {
+            level: level.into(),
+            layer: new.identifier.clone(),
+            count: cells.len(),
+            bounds: Rect::new(left, top, right - left + 1, bottom - top + 1),
+        });
+    }
+
+    for (o, n) in match_entities(&old.entity_instances, &new.entity_instances) {
+        let entity = |e: &EntityInstance| (e.identifier.clone(), (e.px[0], e.px[1]));
+        match (o.map(|i| &old.entity_instances[i]), n.map(|i| &new.entity_instances[i])) {
+            (Some(o), None) => {
+                let (entity, at) = entity(o);
+                changes.push(Change::EntityRemoved {
+                    level: level.into(),
+                    layer: new.identifier.clone(),
+                    entity,
+                    at,
+                });
+            }
+            (None, Some(n)) => {
+                let (entity, at) = entity(n);
+                changes.push(Change::EntityAdded {
+                    level: level.into(),
+                    layer: new.identifier.clone(),
+                    entity,
+                    at,
+                });
+            }
+            (Some(o), Some(n)) => {
+                let (identifier, at) = entity(n);
+                if o.px != n.px {
+                    changes.push(Change::EntityMoved {
+                        level: level.into(),
+                        layer: new.identifier.clone(),
+                        entity: identifier.clone(),
+                        from: (o.px[0], o.px[1]),
+                        to: at,
+                    });
+                }
+                for (field, from, to) in diff_fields(&o.field_instances, &n.field_instances) {
+                    changes.push(Change::EntityFieldChanged {
+                        level: level.into(),
+                        layer: new.identifier.clone(),
+                        entity: identifier.clone(),
+                        at,
+                        field,
+                        from,
+                        to,
+                    });
+                }
+            }
+            (None, None) => (),
+        }
+    }
+}
+
+/// Cells whose IntGrid value differs, comparing over the larger of both grids.
+fn diff_int_grid(old: &LayerInstance, new: &LayerInstance) -> Vec<(i64, i64)> {
+    let value = |layer: &LayerInstance, x: i64, y: i64| {
+        if x < layer.c_wid && y < layer.c_hei {
+            layer
+                .int_grid_csv
+                .get((y * layer.c_wid + x) as usize)
+                .copied()
+                .unwrap_or(0)
+        } else {
+            0
+        }
+    };
+
+    let mut cells = Vec::new();
+    for y in 0..old.c_hei.max(new.c_hei) {
+        for x in 0..old.c_wid.max(new.c_wid) {
+            if value(old, x, y) != value(new, x, y) {
+                cells.push((x, y));
+            }
+        }
+    }
+    cells
+}
+
+/// Cells whose stack of grid tiles differs.
+fn diff_tiles(old: &LayerInstance, new: &LayerInstance) -> Vec<(i64, i64)> {
+    fn by_cell(layer: &LayerInstance) -> BTreeMap<(i64, i64), Vec<(i64, i64)>> {
+        let mut cells: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for tile in &layer.grid_tiles {
+            let cell = (tile.px[1] / layer.grid_size, tile.px[0] / layer.grid_size);
+            cells.entry(cell).or_default().push((tile.t, tile.f));
+        }
+        cells
+    }
+
+    let (old, new) = (by_cell(old), by_cell(new));
+    let keys: BTreeSet<_> = old.keys().chain(new.keys()).collect();
+    keys.into_iter()
+        .filter(|&cell| old.get(cell) != new.get(cell))
+        .map(|&(y, x)| (x, y))
+        .collect()
+}
+
+/// `(identifier, old value, new value)` for every field whose value differs, matched by definition.
+fn diff_fields(old: &[FieldInstance], new: &[FieldInstance]) -> Vec<(String, Option<Value>, Option<Value>)> {
+    let mut fields = Vec::new();
+    for field in new {
+        let previous = old
+            .iter()
+            .find(|f| f.def_uid == field.def_uid)
+            .and_then(|f| f.value.clone());
+        if previous != field.value {
+            fields.push((field.identifier.clone(), previous, field.value.clone()));
+        }
+    }
+    for field in old {
+        if field.value.is_some() && !new.iter().any(|f| f.def_uid == field.def_uid) {
+            fields.push((field.identifier.clone(), field.value.clone(), None));
+        }
+    }
+    fields
+}
+
+/// Pairs up entities of two versions of a layer, as `(old index, new index)`. Identical entities are matched
+/// first, then entities of the same definition at the same position, then the nearest remaining entity of the
+/// same definition. Unmatched entities are returned with `None` on the other side.
+pub(crate) fn match_entities(old: &[EntityInstance], new: &[EntityInstance]) -> Vec<(Option<usize>, Option<usize>)> {
+    let mut old_used = vec![false; old.len()];
+    let mut new_used = vec![false; new.len()];
+    let mut pairs = Vec::new();
+
+    let passes: [fn(&EntityInstance, &EntityInstance) -> bool; 3] = [
+        |a, b| a == b,
+        |a, b| a.def_uid == b.def_uid && a.px == b.px,
+        |a, b| a.def_uid == b.def_uid,
+    ];
+    for matches in passes.iter() {
+        for (i, a) in old.iter().enumerate() {
+            if old_used[i] {
+                continue;
+            }
+            let distance = |b: &EntityInstance| (a.px[0] - b.px[0]).pow(2) + (a.px[1] - b.px[1]).pow(2);
+            let nearest = new
+                .iter()
+                .enumerate()
+                .filter(|&(j, b)| !new_used[j] && matches(a, b))
+                .min_by_key(|&(_, b)| distance(b));
+            if let Some((j, _)) = nearest {
+                old_used[i] = true;
+                new_used[j] = true;
+                pairs.push((Some(i), Some(j)));
+            }
+        }
+    }
+
+    pairs.extend((0..old.len()).filter(|&i| !old_used[i]).map(|i| (Some(i), None)));
+    pairs.extend((0..new.len()).filter(|&j| !new_used[j]).map(|j| (None, Some(j))));
+    pairs.sort_by_key(|&(o, n)| (n.unwrap_or(usize::MAX), o));
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> Project {
+        let mut builder = ProjectBuilder::new();
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Walls".into(),
+            purple_type: Type::IntGrid,
+            grid_size: 16,
+            int_grid_values: vec![IntGridValueDefinition {
+                value: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Player".into(),
+            width: 16,
+            height: 16,
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Chest".into(),
+            width: 16,
+            height: 16,
+            ..Default::default()
+        });
+
+        let mut level = builder.level("Start", 64, 64);
+        level.int_grid("Walls", 0, 0, 1);
+        level.entity("Entities", "Player", 16, 16);
+        level.finish();
+        let mut level = builder.level("End", 64, 64);
+        level.world_position(64, 0);
+        level.finish();
+        builder.build()
+    }
+
+    fn level_mut<'a>(project: &'a mut Project, identifier: &str) -> &'a mut Level {
+        project.levels.iter_mut().find(|l| l.identifier == identifier).unwrap()
+    }
+
+    fn layer_mut<'a>(level: &'a mut Level, identifier: &str) -> &'a mut LayerInstance {
+        level
+            .layer_instances
+            .iter_mut()
+            .flatten()
+            .find(|l| l.identifier == identifier)
+            .unwrap()
+    }
+
+    #[test]
+    fn identical_projects() {
+        assert!(diff(&project(), &project()).is_empty());
+    }
+
+    #[test]
+    fn definitions() {
+        let old = project();
+        let mut builder = ProjectBuilder::from_project(old.clone());
+        builder.add_tileset(TilesetDefinition {
+            identifier: "Tiles".into(),
+            tile_grid_size: 16,
+            px_wid: 64,
+            px_hei: 64,
+            ..Default::default()
+        });
+        let mut new = builder.build();
+        new.defs.entities.retain(|d| d.identifier != "Chest");
+        new.defs.layers[1].display_opacity = 0.5;
+
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![
+                Change::DefinitionEdited {
+                    kind: DefinitionKind::Layer,
+                    identifier: "Walls".into(),
+                },
+                Change::DefinitionRemoved {
+                    kind: DefinitionKind::Entity,
+                    identifier: "Chest".into(),
+                },
+                Change::DefinitionAdded {
+                    kind: DefinitionKind::Tileset,
+                    identifier: "Tiles".into(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn levels() {
+        let old = project();
+        let mut builder = ProjectBuilder::from_project(old.clone());
+        let mut level = builder.level("Secret", 32, 32);
+        level.world_position(0, 64);
+        level.finish();
+        let mut new = builder.build();
+        new.levels.retain(|l| l.identifier != "End");
+        level_mut(&mut new, "Start").identifier = "Entrance".into();
+
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![
+                Change::LevelRemoved { level: "End".into() },
+                Change::LevelRenamed {
+                    from: "Start".into(),
+                    to: "Entrance".into(),
+                },
+                Change::LevelAdded { level: "Secret".into() },
+            ]
+        );
+    }
+
+    #[test]
+    fn level_contents() {
+        let old = project();
+        let mut new = old.clone();
+        let level = level_mut(&mut new, "Start");
+        level.world_x = -64;
+        level.px_hei = 80;
+        let walls = layer_mut(level, "Walls");
+        walls.int_grid_csv[0] = 0;
+        walls.int_grid_csv[5] = 1;
+        let entities = layer_mut(level, "Entities");
+        entities.entity_instances[0].px = vec![32, 16];
+        let mut chest = entities.entity_instances[0].clone();
+        chest.identifier = "Chest".into();
+        chest.def_uid = old.defs.entities[1].uid;
+        chest.px = vec![48, 48];
+        entities.entity_instances.push(chest);
+
+        assert_eq!(
+            diff(&old, &new).changes,
+            vec![
+                Change::LevelMoved {
+                    level: "Start".into(),
+                    from: (0, 0),
+                    to: (-64, 0),
+                },
+                Change::LevelResized {
+                    level: "Start".into(),
+                    from: (64, 64),
+                    to: (64, 80),
+                },
+                Change::EntityMoved {
+                    level: "Start".into(),
+                    layer: "Entities".into(),
+                    entity: "Player".into(),
+                    from: (16, 16),
+                    to: (32, 16),
+                },
+                Change::EntityAdded {
+                    level: "Start".into(),
+                    layer: "Entities".into(),
+                    entity: "Chest".into(),
+                    at: (48, 48),
+                },
+                Change::CellsChanged {
+                    level: "Start".into(),
+                    layer: "Walls".into(),
+                    count: 2,
+                    bounds: Rect::new(0, 0, 2, 2),
+                },
+            ]
+        );
+    }
+}
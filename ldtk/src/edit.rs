@@ -0,0 +1,966 @@
+//! Editing of levels that keeps the project consistent, with undo and redo.
+//!
+//! Levels are designated by uid and layers by identifier. Entities have no uid in this format, so they are
+//! designated by their index in their layer. Editing IntGrid values doesn't update auto-layer tiles, which is done
+//! by [`auto_layer::update`](crate::auto_layer::update).
+
+use crate::*;
+use serde_json::Value;
+use std::{collections::BTreeMap, fmt};
+
+#[derive(Debug)]
+pub enum EditError {
+    NoLevel(i64),
+    NoLayer(String),
+    /// No entity definition with this identifier.
+    NoEntity(String),
+    /// No entity at this index in the layer.
+    NoEntityAt(String, usize),
+    NoField(String),
+    /// The layer isn't of the type the operation needs.
+    WrongLayerType(String, &'static str),
+    /// The IntGrid value isn't defined by the layer.
+    InvalidValue(String, i64),
+    NoTileset(String),
+    /// The position, in cells or pixels depending on the operation, is outside of the layer.
+    OutOfBounds(String, i64, i64),
+    /// The entity is excluded from the layer by its tags.
+    NotAllowed(String, String),
+    /// The entity has reached its `maxCount` and prevents adding more.
+    LimitReached(String),
+    /// The level size, in pixels, isn't positive or isn't a multiple of the grid of every layer.
+    InvalidSize(i64, i64),
+    /// The IntGrid layer doesn't have one value per cell, as in projects saved by old editor versions.
+    InvalidIntGrid(String, usize, i64),
+}
+
+impl fmt::Display for EditError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EditError::NoLevel(uid) => write!(f, "no level with uid {}", uid),
+            EditError::NoLayer(layer) => write!(f, "no layer `{}`", layer),
+            EditError::NoEntity(entity) => write!(f, "no entity `{}`", entity),
+            EditError::NoEntityAt(layer, index) => write!(f, "no entity {} in layer `{}`", index, layer),
+            EditError::NoField(field) => write!(f, "no field `{}`", field),
+            EditError::WrongLayerType(layer, ty) => write!(f, "layer `{}` is not of type {}", layer, ty),
+            EditError::InvalidValue(layer, value) => write!(f, "layer `{}` has no IntGrid value {}", layer, value),
+            EditError::NoTileset(layer) => write!(f, "layer `{}` has no tileset", layer),
+            EditError::OutOfBounds(layer, x, y) => write!(f, "({}, {}) is outside of layer `{}`", x, y, layer),
+            EditError::NotAllowed(entity, layer) => {
+                write!(f, "entity `{}` is not allowed in layer `{}`", entity, layer)
+            }
+            EditError::LimitReached(entity) => write!(f, "entity `{}` has reached its maximum count", entity),
+            EditError::InvalidSize(px_wid, px_hei) => write!(f, "invalid level size {}x{} px", px_wid, px_hei),
+            EditError::InvalidIntGrid(layer, len, cells) => {
+                write!(f, "layer `{}` has {} IntGrid values instead of {}", layer, len, cells)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// The part of the project changed by an operation, before and after it.
+#[derive(Clone, Debug)]
+enum Patch {
+    /// Changed cells, as `(index, before, after)`.
+    IntGrid {
+        level: i64,
+        layer: i64,
+        cells: Vec<(usize, i64, i64)>,
+    },
+    Tiles {
+        level: i64,
+        layer: i64,
+        before: Vec<TileInstance>,
+        after: Vec<TileInstance>,
+    },
+    Entities {
+        level: i64,
+        layer: i64,
+        before: Vec<EntityInstance>,
+        after: Vec<EntityInstance>,
+    },
+    LevelFields {
+        level: i64,
+        before: Vec<FieldInstance>,
+        after: Vec<FieldInstance>,
+    },
+    Level {
+        before: Box<Level>,
+        after: Box<Level>,
+    },
+}
+
+impl Patch {
+    fn apply(&self, project: &mut Project, forward: bool) {
+        fn pick<T>(forward: bool, before: T, after: T) -> T {
+            if forward {
+                after
+            } else {
+                before
+            }
+        }
+
+        match self {
+            Patch::IntGrid {
+                level,
+                layer: uid,
+                cells,
+            } => {
+                if let Some(layer) = find_layer_by_uid_mut(&mut project.levels, *level, *uid) {
+                    for &(i, before, after) in cells {
+                        if let Some(cell) = layer.int_grid_csv.get_mut(i) {
+                            *cell = pick(forward, before, after);
+                        }
+                    }
+                }
+            }
+            Patch::Tiles {
+                level,
+                layer: uid,
+                before,
+                after,
+            } => {
+                if let Some(layer) = find_layer_by_uid_mut(&mut project.levels, *level, *uid) {
+                    layer.grid_tiles = pick(forward, before, after).clone();
+                }
+            }
+            Patch::Entities {
+                level,
+                layer: uid,
+                before,
+                after,
+            } => {
+                if let Some(layer) = find_layer_by_uid_mut(&mut project.levels, *level, *uid) {
+                    layer.entity_instances = pick(forward, before, after).clone();
+                }
+            }
+            Patch::LevelFields { level, before, after } => {
+                if let Some(level) = project.levels.iter_mut().find(|l| l.uid == *level) {
+                    level.field_instances = pick(forward, before, after).clone();
+                }
+            }
+            Patch::Level { before, after } => {
+                let level = pick(forward, before, after);
+                if let Some(l) = project.levels.iter_mut().find(|l| l.uid == level.uid) {
+                    *l = (**level).clone();
+                }
+            }
+        }
+    }
+}
+
+/// Undo and redo stacks of the operations done through an [`Editor`].
+#[derive(Clone, Debug, Default)]
+pub struct History {
+    done: Vec<Vec<Patch>>,
+    undone: Vec<Vec<Patch>>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.done.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Reverts the last operation on `project`, returning false if there was nothing to undo.
+    pub fn undo(&mut self, project: &mut Project) -> bool {
+        match self.done.pop() {
+            Some(operation) => {
+                for patch in operation.iter().rev() {
+                    patch.apply(project, false);
+                }
+                self.undone.push(operation);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Applies the last undone operation on `project` again, returning false if there was nothing to redo.
+    pub fn redo(&mut self, project: &mut Project) -> bool {
+        match self.undone.pop() {
+            Some(operation) => {
+                for patch in &operation {
+                    patch.apply(project, true);
+                }
+                self.done.push(operation);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.done.clear();
+        self.undone.clear();
+    }
+
+    fn record(&mut self, operation: Vec<Patch>) {
+        if !operation.is_empty() {
+            self.done.push(operation);
+            self.undone.clear();
+        }
+    }
+}
+
+/// Edits `project`, recording every operation into `history`. Operations either fully succeed or leave the project
+/// untouched.
+pub struct Editor<'a> {
+    project: &'a mut Project,
+    history: &'a mut History,
+}
+
+impl<'a> Editor<'a> {
+    pub fn new(project: &'a mut Project, history: &'a mut History) -> Self {
+        Self { project, history }
+    }
+
+    pub fn project(&self) -> &Project {
+        self.project
+    }
+
+    /// Sets the IntGrid value of cell (`cx`, `cy`), 0 meaning empty.
+    pub fn set_int_grid_cell(
+        &mut self,
+        level: i64,
+        layer: &str,
+        cx: i64,
+        cy: i64,
+        value: i64,
+    ) -> Result<(), EditError> {
+        let instance = find_layer(&self.project.levels, level, layer)?;
+        if !Rect::new(0, 0, instance.c_wid, instance.c_hei).contains(cx, cy) {
+            return Err(EditError::OutOfBounds(layer.into(), cx, cy));
+        }
+        self.fill_rect(level, layer, Rect::new(cx, cy, 1, 1), value)
+    }
+
+    /// Sets the IntGrid value of every cell in `cells`, clipped to the layer.
+    pub fn fill_rect(&mut self, level: i64, layer: &str, cells: Rect, value: i64) -> Result<(), EditError> {
+        let def = self.layer_def(level, layer, "IntGrid")?;
+        if value != 0 && !def.int_grid_values.iter().any(|v| v.value == value) {
+            return Err(EditError::InvalidValue(layer.into(), value));
+        }
+
+        let instance = find_layer_mut(&mut self.project.levels, level, layer)?;
+        check_int_grid(instance)?;
+        let mut changed = Vec::new();
+        if let Some(cells) = cells.intersection(&Rect::new(0, 0, instance.c_wid, instance.c_hei)) {
+            for cy in cells.y..cells.bottom() {
+                for cx in cells.x..cells.right() {
+                    let i = (cy * instance.c_wid + cx) as usize;
+                    if instance.int_grid_csv[i] != value {
+                        changed.push((i, instance.int_grid_csv[i], value));
+                        instance.int_grid_csv[i] = value;
+                    }
+                }
+            }
+        }
+
+        if !changed.is_empty() {
+            let layer = instance.layer_def_uid;
+            self.history.record(vec![Patch::IntGrid {
+                level,
+                layer,
+                cells: changed,
+            }]);
+        }
+        Ok(())
+    }
+
+    /// Places tile `tile_id` of the layer's tileset at cell (`cx`, `cy`), replacing the tiles already there.
+    pub fn place_tile(&mut self, level: i64, layer: &str, cx: i64, cy: i64, tile_id: i64) -> Result<(), EditError> {
+        self.layer_def(level, layer, "Tiles")?;
+        let instance = find_layer(&self.project.levels, level, layer)?;
+        if !Rect::new(0, 0, instance.c_wid, instance.c_hei).contains(cx, cy) {
+            return Err(EditError::OutOfBounds(layer.into(), cx, cy));
+        }
+        let tileset = instance
+            .override_tileset_uid
+            .or(instance.tileset_def_uid)
+            .and_then(|uid| self.project.defs.tilesets.iter().find(|t| t.uid == uid))
+            .ok_or_else(|| EditError::NoTileset(layer.into()))?;
+        let tile = TileInstance {
+            d: vec![cx + cy * instance.c_wid],
+            px: vec![cx * instance.grid_size, cy * instance.grid_size],
+            src: tileset.tile_src(tile_id).to_vec(),
+            t: tile_id,
+            ..Default::default()
+        };
+
+        self.edit_tiles(level, layer, |tiles, grid| {
+            tiles.retain(|t| cell(&t.px, grid) != Some((cx, cy)));
+            tiles.push(tile);
+        })
+    }
+
+    /// Removes all tiles at cell (`cx`, `cy`), returning how many there were.
+    pub fn remove_tile_at(&mut self, level: i64, layer: &str, cx: i64, cy: i64) -> Result<usize, EditError> {
+        self.layer_def(level, layer, "Tiles")?;
+        let mut removed = 0;
+        self.edit_tiles(level, layer, |tiles, grid| {
+            let count = tiles.len();
+            tiles.retain(|t| cell(&t.px, grid) != Some((cx, cy)));
+            removed = count - tiles.len();
+        })?;
+        Ok(removed)
+    }
+
+    /// Adds an entity with its pivot at (`x`, `y`) pixels and default field values, returning its index in the
+    /// layer.
+    ///
+    /// The layer's required and excluded tags are checked, and the entity's `maxCount` is enforced within its
+    /// `limitScope` the way its `limitBehavior` says: the oldest instances are discarded, the last one is moved
+    /// here keeping its fields, or adding fails.
+    pub fn add_entity(
+        &mut self,
+        level: i64,
+        layer: &str,
+        identifier: &str,
+        x: i64,
+        y: i64,
+    ) -> Result<usize, EditError> {
+        let layer_def = self.layer_def(level, layer, "Entities")?;
+        let def = self
+            .project
+            .defs
+            .entities
+            .iter()
+            .find(|d| d.identifier == identifier)
+            .ok_or_else(|| EditError::NoEntity(identifier.into()))?;
+        let has_tag = |tags: &[String]| tags.iter().any(|t| def.tags.contains(t));
+        if (!layer_def.required_tags.is_empty() && !has_tag(&layer_def.required_tags))
+            || has_tag(&layer_def.excluded_tags)
+        {
+            return Err(EditError::NotAllowed(identifier.into(), layer.into()));
+        }
+
+        let instance = find_layer(&self.project.levels, level, layer)?;
+        let target = (level, instance.layer_def_uid);
+        let bounds = Rect::new(
+            0,
+            0,
+            instance.c_wid * instance.grid_size,
+            instance.c_hei * instance.grid_size,
+        );
+        if !bounds.contains(x, y) {
+            return Err(EditError::OutOfBounds(layer.into(), x, y));
+        }
+        let mut entity = EntityInstance::new(self.project, def, instance, x, y);
+
+        // Instances of the same entity within the limit scope, in (level, layer, index) order.
+        let mut existing = Vec::new();
+        for l in &self.project.levels {
+            for i in l.layer_instances.iter().flatten() {
+                let in_scope = match def.limit_scope {
+                    LimitScope::PerLayer => (l.uid, i.layer_def_uid) == target,
+                    LimitScope::PerLevel => l.uid == level,
+                    LimitScope::PerWorld => true,
+                };
+                if in_scope {
+                    let indices = i
+                        .entity_instances
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, e)| e.def_uid == def.uid);
+                    existing.extend(indices.map(|(index, _)| (l.uid, i.layer_def_uid, index)));
+                }
+            }
+        }
+        let max_count = def.max_count as usize;
+        let removed = if def.max_count > 0 && existing.len() >= max_count {
+            match def.limit_behavior {
+                LimitBehavior::PreventAdding => return Err(EditError::LimitReached(identifier.into())),
+                LimitBehavior::MoveLastOne => existing.split_off(existing.len() - 1),
+                LimitBehavior::DiscardOldOnes => existing[..existing.len() + 1 - max_count].to_vec(),
+            }
+        } else {
+            Vec::new()
+        };
+        let moved = def.limit_behavior == LimitBehavior::MoveLastOne && !removed.is_empty();
+
+        let mut layers = BTreeMap::new();
+        layers.insert(target, Vec::new());
+        for &(l, i, index) in &removed {
+            layers.entry((l, i)).or_insert_with(Vec::new).push(index);
+        }
+        let mut patches = Vec::new();
+        for ((l, i), mut indices) in layers {
+            let instance = find_layer_by_uid_mut(&mut self.project.levels, l, i)
+                .expect("entity scope only contains existing layers");
+            let before = instance.entity_instances.clone();
+            indices.sort_unstable();
+            for &index in indices.iter().rev() {
+                let old = instance.entity_instances.remove(index);
+                if moved {
+                    entity.field_instances = old.field_instances;
+                }
+            }
+            patches.push((l, i, before));
+        }
+
+        let instance = find_layer_mut(&mut self.project.levels, level, layer)?;
+        instance.entity_instances.push(entity);
+        let index = instance.entity_instances.len() - 1;
+
+        let operation = patches
+            .into_iter()
+            .map(|(l, i, before)| {
+                let after = find_layer_by_uid(&self.project.levels, l, i).entity_instances.clone();
+                Patch::Entities {
+                    level: l,
+                    layer: i,
+                    before,
+                    after,
+                }
+            })
+            .collect();
+        self.history.record(operation);
+        Ok(index)
+    }
+
+    /// Moves the entity at `index` so that its pivot is at (`x`, `y`) pixels.
+    pub fn move_entity(&mut self, level: i64, layer: &str, index: usize, x: i64, y: i64) -> Result<(), EditError> {
+        self.layer_def(level, layer, "Entities")?;
+        let instance = find_layer(&self.project.levels, level, layer)?;
+        if !Rect::new(
+            0,
+            0,
+            instance.c_wid * instance.grid_size,
+            instance.c_hei * instance.grid_size,
+        )
+        .contains(x, y)
+        {
+            return Err(EditError::OutOfBounds(layer.into(), x, y));
+        }
+        self.edit_entity(level, layer, index, |entity, grid| {
+            entity.px = vec![x, y];
+            entity.grid = vec![x / grid, y / grid];
+            Ok(())
+        })
+    }
+
+    /// Sets the value of field `identifier` of the entity at `index`.
+    pub fn set_field(
+        &mut self,
+        level: i64,
+        layer: &str,
+        index: usize,
+        identifier: &str,
+        value: Value,
+    ) -> Result<(), EditError> {
+        self.edit_entity(level, layer, index, |entity, _| {
+            let field = entity.field_instances.iter_mut().find(|f| f.identifier == identifier);
+            field
+                .ok_or_else(|| EditError::NoField(identifier.into()))?
+                .set_value(value);
+            Ok(())
+        })
+    }
+
+    /// Sets the value of the level field `identifier`.
+    pub fn set_level_field(&mut self, level: i64, identifier: &str, value: Value) -> Result<(), EditError> {
+        let l = find_level_mut(&mut self.project.levels, level)?;
+        let before = l.field_instances.clone();
+        let field = l.field_instances.iter_mut().find(|f| f.identifier == identifier);
+        field
+            .ok_or_else(|| EditError::NoField(identifier.into()))?
+            .set_value(value);
+
+        if l.field_instances != before {
+            let after = l.field_instances.clone();
+            self.history.record(vec![Patch::LevelFields { level, before, after }]);
+        }
+        Ok(())
+    }
+
+    /// Resizes the level to `px_wid` by `px_hei` pixels, keeping its top-left corner in place. Cells, tiles and
+    /// entities that end up outside of the level are removed. The size must be a multiple of the grid of every layer.
+    pub fn resize_level(&mut self, level: i64, px_wid: i64, px_hei: i64) -> Result<(), EditError> {
+        let l = find_level_mut(&mut self.project.levels, level)?;
+        for layer in l.layer_instances.iter().flatten() {
+            if layer.layer_instance_type == "IntGrid" {
+                check_int_grid(layer)?;
+            }
+        }
+        let valid = px_wid > 0
+            && px_hei > 0
+            && l.layer_instances.iter().flatten().all(|layer| {
+                let grid = layer.grid_size.max(1);
+                px_wid % grid == 0 && px_hei % grid == 0 && (px_wid / grid).checked_mul(px_hei / grid).is_some()
+            });
+        if !valid {
+            return Err(EditError::InvalidSize(px_wid, px_hei));
+        }
+        let before = Box::new(l.clone());
+
+        l.px_wid = px_wid;
+        l.px_hei = px_hei;
+        for layer in l.layer_instances.iter_mut().flatten() {
+            let grid = layer.grid_size.max(1);
+            let (c_wid, c_hei) = (px_wid / grid, px_hei / grid);
+            if layer.layer_instance_type == "IntGrid" {
+                let mut csv = vec![0; (c_wid * c_hei) as usize];
+                for cy in 0..c_hei.min(layer.c_hei) {
+                    for cx in 0..c_wid.min(layer.c_wid) {
+                        csv[(cy * c_wid + cx) as usize] = layer.int_grid_csv[(cy * layer.c_wid + cx) as usize];
+                    }
+                }
+                layer.int_grid_csv = csv;
+            }
+            layer.c_wid = c_wid;
+            layer.c_hei = c_hei;
+
+            // Malformed positions are left for `Project::validate` to report
+            let bounds = Rect::new(0, 0, c_wid * grid, c_hei * grid);
+            let inside = |px: &[i64]| match *px {
+                [x, y] => bounds.contains(x, y),
+                _ => true,
+            };
+            layer.grid_tiles.retain(|t| inside(&t.px));
+            layer.auto_layer_tiles.retain(|t| inside(&t.px));
+            for tile in layer.grid_tiles.iter_mut().chain(&mut layer.auto_layer_tiles) {
+                // The last element of tile data is the cell index, which depends on the width.
+                if let (Some(coord), Some((cx, cy))) = (tile.d.last_mut(), cell(&tile.px, grid)) {
+                    *coord = cx + cy * c_wid;
+                }
+            }
+            layer.entity_instances.retain(|e| inside(&e.px));
+        }
+
+        let after = Box::new(l.clone());
+        self.history.record(vec![Patch::Level { before, after }]);
+        Ok(())
+    }
+
+    /// The definition of `layer`, checking that it has the type `ty`.
+    fn layer_def(&self, level: i64, layer: &str, ty: &'static str) -> Result<&LayerDefinition, EditError> {
+        let instance = find_layer(&self.project.levels, level, layer)?;
+        let def = self
+            .project
+            .defs
+            .layers
+            .iter()
+            .find(|d| d.uid == instance.layer_def_uid);
+        match def {
+            Some(def) if def.purple_type.name() == ty => Ok(def),
+            Some(_) => Err(EditError::WrongLayerType(layer.into(), ty)),
+            None => Err(EditError::NoLayer(layer.into())),
+        }
+    }
+
+    fn edit_tiles(
+        &mut self,
+        level: i64,
+        layer: &str,
+        edit: impl FnOnce(&mut Vec<TileInstance>, i64),
+    ) -> Result<(), EditError> {
+        let instance = find_layer_mut(&mut self.project.levels, level, layer)?;
+        let before = instance.grid_tiles.clone();
+        edit(&mut instance.grid_tiles, instance.grid_size.max(1));
+        if instance.grid_tiles != before {
+            let after = instance.grid_tiles.clone();
+            let layer = instance.layer_def_uid;
+            self.history.record(vec![Patch::Tiles {
+                level,
+                layer,
+                before,
+                after,
+            }]);
+        }
+        Ok(())
+    }
+
+    fn edit_entity(
+        &mut self,
+        level: i64,
+        layer: &str,
+        index: usize,
+        edit: impl FnOnce(&mut EntityInstance, i64) -> Result<(), EditError>,
+    ) -> Result<(), EditError> {
+        let instance = find_layer_mut(&mut self.project.levels, level, layer)?;
+        let grid = instance.grid_size.max(1);
+        let mut entity = instance
+            .entity_instances
+            .get(index)
+            .cloned()
+            .ok_or_else(|| EditError::NoEntityAt(layer.into(), index))?;
+        edit(&mut entity, grid)?;
+        if entity == instance.entity_instances[index] {
+            return Ok(());
+        }
+
+        let before = instance.entity_instances.clone();
+        instance.entity_instances[index] = entity;
+        let after = instance.entity_instances.clone();
+        let layer = instance.layer_def_uid;
+        self.history.record(vec![Patch::Entities {
+            level,
+            layer,
+            before,
+            after,
+        }]);
+        Ok(())
+    }
+}
+
+/// The cell of the pixel position `px`, `None` if it isn't a valid position.
+fn cell(px: &[i64], grid: i64) -> Option<(i64, i64)> {
+    match *px {
+        [x, y] => Some((x.div_euclid(grid), y.div_euclid(grid))),
+        _ => None,
+    }
+}
+
+fn check_int_grid(layer: &LayerInstance) -> Result<(), EditError> {
+    let cells = layer.c_wid * layer.c_hei;
+    if layer.int_grid_csv.len() as i64 != cells {
+        return Err(EditError::InvalidIntGrid(
+            layer.identifier.clone(),
+            layer.int_grid_csv.len(),
+            cells,
+        ));
+    }
+    Ok(())
+}
+
+fn find_level_mut(levels: &mut [Level], uid: i64) -> Result<&mut Level, EditError> {
+    levels.iter_mut().find(|l| l.uid == uid).ok_or(EditError::NoLevel(uid))
+}
+
+fn find_layer<'a>(levels: &'a [Level], level: i64, layer: &str) -> Result<&'a LayerInstance, EditError> {
+    let l = levels
+        .iter()
+        .find(|l| l.uid == level)
+        .ok_or(EditError::NoLevel(level))?;
+    l.layer_instances
+        .iter()
+        .flatten()
+        .find(|l| l.identifier == layer)
+        .ok_or_else(|| EditError::NoLayer(layer.into()))
+}
+
+fn find_layer_mut<'a>(levels: &'a mut [Level], level: i64, layer: &str) -> Result<&'a mut LayerInstance, EditError> {
+    find_level_mut(levels, level)?
+        .layer_instances
+        .iter_mut()
+        .flatten()
+        .find(|l| l.identifier == layer)
+        .ok_or_else(|| EditError::NoLayer(layer.into()))
+}
+
+fn find_layer_by_uid(levels: &[Level], level: i64, layer: i64) -> &LayerInstance {
+    levels
+        .iter()
+        .find(|l| l.uid == level)
+        .and_then(|l| l.layer_instances.as_ref())
+        .and_then(|layers| layers.iter().find(|l| l.layer_def_uid == layer))
+        .expect("patched layers exist")
+}
+
+fn find_layer_by_uid_mut(levels: &mut [Level], level: i64, layer: i64) -> Option<&mut LayerInstance> {
+    levels
+        .iter_mut()
+        .find(|l| l.uid == level)
+        .and_then(|l| l.layer_instances.as_mut())
+        .and_then(|layers| layers.iter_mut().find(|l| l.layer_def_uid == layer))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Two 64x64 levels. Coins and keys are items, only allowed on the Items layer.
+    fn project() -> (Project, i64, i64) {
+        let mut builder = ProjectBuilder::new();
+        let tileset = builder.add_tileset(TilesetDefinition {
+            identifier: "Tiles".into(),
+            tile_grid_size: 16,
+            px_wid: 64,
+            px_hei: 64,
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            excluded_tags: vec!["item".into()],
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Items".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            required_tags: vec!["item".into()],
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Ground".into(),
+            purple_type: Type::Tiles,
+            grid_size: 16,
+            tileset_def_uid: Some(tileset),
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Walls".into(),
+            purple_type: Type::IntGrid,
+            grid_size: 16,
+            int_grid_values: vec![
+                IntGridValueDefinition {
+                    value: 1,
+                    ..Default::default()
+                },
+                IntGridValueDefinition {
+                    value: 2,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Player".into(),
+            width: 16,
+            height: 16,
+            max_count: 1,
+            limit_scope: LimitScope::PerWorld,
+            limit_behavior: LimitBehavior::MoveLastOne,
+            field_defs: vec![FieldDefinition {
+                identifier: "hp".into(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Coin".into(),
+            width: 16,
+            height: 16,
+            tags: vec!["item".into()],
+            max_count: 2,
+            limit_scope: LimitScope::PerLevel,
+            limit_behavior: LimitBehavior::DiscardOldOnes,
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Key".into(),
+            width: 16,
+            height: 16,
+            tags: vec!["item".into()],
+            max_count: 1,
+            limit_scope: LimitScope::PerLayer,
+            limit_behavior: LimitBehavior::PreventAdding,
+            ..Default::default()
+        });
+        builder.add_level_field(FieldDefinition {
+            identifier: "difficulty".into(),
+            ..Default::default()
+        });
+        let first = builder.level("First", 64, 64).finish();
+        let second = builder.level("Second", 64, 64).finish();
+        (builder.build(), first, second)
+    }
+
+    fn layer<'a>(project: &'a Project, level: i64, identifier: &str) -> &'a LayerInstance {
+        find_layer(&project.levels, level, identifier).unwrap()
+    }
+
+    fn positions(project: &Project, level: i64, identifier: &str) -> Vec<Vec<i64>> {
+        let layer = layer(project, level, identifier);
+        layer.entity_instances.iter().map(|e| e.px.clone()).collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let (mut project, first, second) = project();
+        let original = project.clone();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        editor.fill_rect(first, "Walls", Rect::new(1, 1, 2, 2), 1).unwrap();
+        editor.set_int_grid_cell(first, "Walls", 1, 1, 2).unwrap();
+        editor.place_tile(first, "Ground", 3, 3, 5).unwrap();
+        editor.add_entity(first, "Entities", "Player", 16, 16).unwrap();
+        editor.set_field(first, "Entities", 0, "hp", json!(3)).unwrap();
+        editor.add_entity(second, "Entities", "Player", 32, 32).unwrap();
+        editor.set_level_field(second, "difficulty", json!(2)).unwrap();
+        editor.resize_level(first, 48, 32).unwrap();
+        let edited = project.clone();
+
+        let mut undone = 0;
+        while history.undo(&mut project) {
+            undone += 1;
+        }
+        assert_eq!(undone, 8);
+        assert_eq!(project, original);
+        assert!(!history.can_undo());
+
+        while history.redo(&mut project) {}
+        assert_eq!(project, edited);
+        assert!(!history.can_redo());
+
+        // A new operation drops the undone ones
+        history.undo(&mut project);
+        Editor::new(&mut project, &mut history)
+            .remove_tile_at(first, "Ground", 3, 3)
+            .unwrap();
+        assert!(history.can_undo());
+        assert!(!history.can_redo());
+    }
+
+    #[test]
+    fn discard_old_ones() {
+        let (mut project, first, second) = project();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        assert_eq!(editor.add_entity(first, "Items", "Coin", 0, 0).unwrap(), 0);
+        assert_eq!(editor.add_entity(first, "Items", "Coin", 16, 0).unwrap(), 1);
+        assert_eq!(editor.add_entity(second, "Items", "Coin", 48, 0).unwrap(), 0);
+        assert_eq!(editor.add_entity(first, "Items", "Coin", 32, 0).unwrap(), 1);
+        assert_eq!(positions(&project, first, "Items"), vec![vec![16, 0], vec![32, 0]]);
+        assert_eq!(positions(&project, second, "Items"), vec![vec![48, 0]]);
+
+        history.undo(&mut project);
+        assert_eq!(positions(&project, first, "Items"), vec![vec![0, 0], vec![16, 0]]);
+    }
+
+    #[test]
+    fn move_last_one() {
+        let (mut project, first, second) = project();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        editor.add_entity(first, "Entities", "Player", 16, 16).unwrap();
+        editor.set_field(first, "Entities", 0, "hp", json!(3)).unwrap();
+        assert_eq!(editor.add_entity(second, "Entities", "Player", 32, 32).unwrap(), 0);
+
+        assert!(positions(&project, first, "Entities").is_empty());
+        let player = &layer(&project, second, "Entities").entity_instances[0];
+        assert_eq!(player.px, vec![32, 32]);
+        assert_eq!(player.field_instances[0].value, Some(json!(3)));
+
+        // Both levels are restored by a single undo
+        history.undo(&mut project);
+        assert_eq!(positions(&project, first, "Entities"), vec![vec![16, 16]]);
+        assert!(positions(&project, second, "Entities").is_empty());
+    }
+
+    #[test]
+    fn prevent_adding() {
+        let (mut project, first, second) = project();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        editor.add_entity(first, "Items", "Key", 0, 0).unwrap();
+        assert!(matches!(
+            editor.add_entity(first, "Items", "Key", 16, 0),
+            Err(EditError::LimitReached(_))
+        ));
+        editor.add_entity(second, "Items", "Key", 16, 0).unwrap();
+        assert_eq!(positions(&project, first, "Items"), vec![vec![0, 0]]);
+        assert_eq!(positions(&project, second, "Items"), vec![vec![16, 0]]);
+    }
+
+    #[test]
+    fn layer_tags() {
+        let (mut project, first, _) = project();
+        let original = project.clone();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        assert!(matches!(
+            editor.add_entity(first, "Entities", "Coin", 0, 0),
+            Err(EditError::NotAllowed(..))
+        ));
+        assert!(matches!(
+            editor.add_entity(first, "Items", "Player", 0, 0),
+            Err(EditError::NotAllowed(..))
+        ));
+        assert_eq!(project, original);
+        assert!(!history.can_undo());
+    }
+
+    #[test]
+    fn resize_level() {
+        let (mut project, first, _) = project();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        editor.set_int_grid_cell(first, "Walls", 1, 1, 1).unwrap();
+        editor.set_int_grid_cell(first, "Walls", 3, 0, 2).unwrap();
+        editor.place_tile(first, "Ground", 1, 1, 5).unwrap();
+        editor.place_tile(first, "Ground", 3, 0, 6).unwrap();
+        editor.add_entity(first, "Entities", "Player", 56, 8).unwrap();
+
+        editor.resize_level(first, 80, 32).unwrap();
+        let walls = layer(&project, first, "Walls");
+        assert_eq!((walls.c_wid, walls.c_hei), (5, 2));
+        assert_eq!(walls.int_grid_csv, vec![0, 0, 0, 2, 0, 0, 1, 0, 0, 0]);
+        let ground = layer(&project, first, "Ground");
+        assert_eq!(ground.grid_tiles.iter().map(|t| t.d[0]).collect::<Vec<_>>(), vec![6, 3]);
+
+        let mut editor = Editor::new(&mut project, &mut history);
+        editor.resize_level(first, 32, 16).unwrap();
+        let walls = layer(&project, first, "Walls");
+        assert_eq!(walls.int_grid_csv.len() as i64, walls.c_wid * walls.c_hei);
+        assert_eq!(walls.int_grid_csv, vec![0, 0]);
+        assert!(layer(&project, first, "Ground").grid_tiles.is_empty());
+        assert!(positions(&project, first, "Entities").is_empty());
+
+        let mut editor = Editor::new(&mut project, &mut history);
+        assert!(matches!(
+            editor.resize_level(first, 40, 16),
+            Err(EditError::InvalidSize(40, 16))
+        ));
+        assert!(matches!(
+            editor.resize_level(first, 0, 16),
+            Err(EditError::InvalidSize(0, 16))
+        ));
+    }
+
+    #[test]
+    fn malformed_layers() {
+        let (mut project, first, _) = project();
+        let layers = project.levels[0].layer_instances.as_mut().unwrap();
+        let walls = layers.iter_mut().find(|l| l.identifier == "Walls").unwrap();
+        walls.int_grid_csv.clear();
+        let ground = layers.iter_mut().find(|l| l.identifier == "Ground").unwrap();
+        ground.grid_tiles.push(TileInstance {
+            px: vec![16],
+            ..Default::default()
+        });
+
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        assert!(matches!(
+            editor.set_int_grid_cell(first, "Walls", 0, 0, 1),
+            Err(EditError::InvalidIntGrid(_, 0, 16))
+        ));
+        assert!(matches!(
+            editor.resize_level(first, 32, 32),
+            Err(EditError::InvalidIntGrid(_, 0, 16))
+        ));
+        editor.place_tile(first, "Ground", 1, 0, 5).unwrap();
+        assert_eq!(editor.remove_tile_at(first, "Ground", 1, 0).unwrap(), 1);
+        assert_eq!(layer(&project, first, "Ground").grid_tiles.len(), 1);
+    }
+
+    #[test]
+    fn unchanged_values() {
+        let (mut project, first, _) = project();
+        let mut history = History::new();
+        let mut editor = Editor::new(&mut project, &mut history);
+        editor.set_level_field(first, "difficulty", json!(2)).unwrap();
+        editor.set_level_field(first, "difficulty", json!(2)).unwrap();
+        editor.add_entity(first, "Entities", "Player", 16, 16).unwrap();
+        editor.move_entity(first, "Entities", 0, 16, 16).unwrap();
+        editor.fill_rect(first, "Walls", Rect::new(0, 0, 2, 2), 0).unwrap();
+
+        assert!(history.undo(&mut project));
+        assert!(history.undo(&mut project));
+        assert!(!history.undo(&mut project));
+    }
+}
@@ -0,0 +1,187 @@
+//! Placement of entity tiles inside entity bounds, following the definition `tileRenderMode`.
+
+use crate::*;
+
+/// A part of a tileset image drawn for an entity. `src` is in tileset pixels, `dst` in level pixels like
+/// [`EntityInstance::bounds`], so layer offsets are not included.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct TileQuad {
+    pub tileset_uid: i64,
+    pub src: Rect,
+    pub dst: Rect,
+}
+
+impl EntityInstance {
+    /// Quads drawing the entity tile inside the entity bounds, the way the editor does for `def`:
+    ///
+    /// - `Stretch` fills the bounds. When `def` keeps its aspect ratio the tile is scaled uniformly instead, as
+    ///   with `FitInside`.
+    /// - `FitInside` scales the tile uniformly to fit in the bounds, aligned on the entity pivot.
+    /// - `Cover` scales the tile uniformly to cover the bounds, aligned on the entity pivot and cropped.
+    /// - `Repeat` repeats the tile at its own size from the top-left corner, cropping the last row and column.
+    ///
+    /// Returns no quad for entities without a tile.
+    pub fn tile_quads(&self, def: &EntityDefinition) -> Vec<TileQuad> {
+        let tile = match &self.tile {
+            Some(tile) => tile,
+            None => return Vec::new(),
+        };
+        let src = match tile.src_rect[..] {
+            [x, y, width, height] => Rect::new(x, y, width, height),
+            _ => return Vec::new(),
+        };
+        let bounds = self.bounds();
+        if src.is_empty() || bounds.is_empty() {
+            return Vec::new();
+        }
+
+        let quad = |src: Rect, dst: Rect| TileQuad {
+            tileset_uid: tile.tileset_uid,
+            src,
+            dst,
+        };
+        let (pivot_x, pivot_y) = match self.pivot[..] {
+            [x, y] => (x, y),
+            _ => (0., 0.),
+        };
+        let scale_x = bounds.width as f64 / src.width as f64;
+        let scale_y = bounds.height as f64 / src.height as f64;
+
+        match def.tile_render_mode {
+            TileRenderMode::Stretch if !def.keep_aspect_ratio => vec![quad(src, bounds)],
+            TileRenderMode::Stretch | TileRenderMode::FitInside => {
+                let scale = scale_x.min(scale_y);
+                let width = (src.width as f64 * scale).round() as i64;
+                let height = (src.height as f64 * scale).round() as i64;
+                let dst = Rect::new(
+                    bounds.x + ((bounds.width - width) as f64 * pivot_x).round() as i64,
+                    bounds.y + ((bounds.height - height) as f64 * pivot_y).round() as i64,
+                    width,
+                    height,
+                );
+                vec![quad(src, dst)]
+            }
+            TileRenderMode::Cover => {
+                let scale = scale_x.max(scale_y);
+                let width = ((bounds.width as f64 / scale).round() as i64).clamp(1, src.width);
+                let height = ((bounds.height as f64 / scale).round() as i64).clamp(1, src.height);
+                let cropped = Rect::new(
+                    src.x + ((src.width - width) as f64 * pivot_x).round() as i64,
+                    src.y + ((src.height - height) as f64 * pivot_y).round() as i64,
+                    width,
+                    height,
+                );
+                vec![quad(cropped, bounds)]
+            }
+            TileRenderMode::Repeat => {
+                let mut quads = Vec::new();
+                for y in (0..bounds.height).step_by(src.height as usize) {
+                    for x in (0..bounds.width).step_by(src.width as usize) {
+                        let width = src.width.min(bounds.width - x);
+                        let height = src.height.min(bounds.height - y);
+                        quads.push(quad(
+                            Rect::new(src.x, src.y, width, height),
+                            Rect::new(bounds.x + x, bounds.y + y, width, height),
+                        ));
+                    }
+                }
+                quads
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 40x20 entity at (80, 180), pivoted on its bottom center, showing a 16x16 tile.
+    fn entity() -> EntityInstance {
+        EntityInstance {
+            pivot: vec![0.5, 1.],
+            px: vec![100, 200],
+            width: 40,
+            height: 20,
+            tile: Some(EntityInstanceTile {
+                src_rect: vec![16, 32, 16, 16],
+                tileset_uid: 7,
+                unknown_fields: Default::default(),
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn quads(mode: TileRenderMode, keep_aspect_ratio: bool) -> Vec<(Rect, Rect)> {
+        let def = EntityDefinition {
+            tile_render_mode: mode,
+            keep_aspect_ratio,
+            ..Default::default()
+        };
+        entity()
+            .tile_quads(&def)
+            .into_iter()
+            .map(|quad| {
+                assert_eq!(quad.tileset_uid, 7);
+                (quad.src, quad.dst)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn stretch() {
+        let src = Rect::new(16, 32, 16, 16);
+        assert_eq!(
+            quads(TileRenderMode::Stretch, false),
+            vec![(src, Rect::new(80, 180, 40, 20))]
+        );
+        assert_eq!(
+            quads(TileRenderMode::Stretch, true),
+            vec![(src, Rect::new(90, 180, 20, 20))]
+        );
+    }
+
+    #[test]
+    fn fit_inside() {
+        let src = Rect::new(16, 32, 16, 16);
+        assert_eq!(
+            quads(TileRenderMode::FitInside, false),
+            vec![(src, Rect::new(90, 180, 20, 20))]
+        );
+    }
+
+    #[test]
+    fn cover() {
+        // Scaled by 2.5 to cover the width, keeping the bottom half of the tile as the pivot is at the bottom.
+        assert_eq!(
+            quads(TileRenderMode::Cover, false),
+            vec![(Rect::new(16, 40, 16, 8), Rect::new(80, 180, 40, 20))]
+        );
+    }
+
+    #[test]
+    fn repeat() {
+        let quad = |x: i64, y: i64, width, height| {
+            (
+                Rect::new(16, 32, width, height),
+                Rect::new(80 + x, 180 + y, width, height),
+            )
+        };
+        assert_eq!(
+            quads(TileRenderMode::Repeat, false),
+            vec![
+                quad(0, 0, 16, 16),
+                quad(16, 0, 16, 16),
+                quad(32, 0, 8, 16),
+                quad(0, 16, 16, 4),
+                quad(16, 16, 16, 4),
+                quad(32, 16, 8, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn no_tile() {
+        let entity = EntityInstance { tile: None, ..entity() };
+        assert!(entity.tile_quads(&EntityDefinition::default()).is_empty());
+    }
+}
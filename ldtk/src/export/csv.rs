@@ -0,0 +1,38 @@
+//! Export of IntGrid layers as CSV files, one row per line, like the editor's "super simple export".
+
+use crate::Project;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Writes every IntGrid layer of every level as `<level>/<layer>.csv` into `out_dir`. Returns the paths of all
+/// written files.
+pub fn export_project<O: AsRef<Path>>(project: &Project, out_dir: O) -> io::Result<Vec<PathBuf>> {
+    let mut written = Vec::new();
+
+    for level in &project.levels {
+        let level_dir = out_dir.as_ref().join(&level.identifier);
+        for layer in level.layer_instances.iter().flatten() {
+            if layer.layer_instance_type != "IntGrid" || layer.c_wid <= 0 {
+                continue;
+            }
+
+            let mut csv = String::new();
+            for row in layer.int_grid_csv.chunks(layer.c_wid as usize) {
+                for value in row {
+                    csv += &value.to_string();
+                    csv.push(',');
+                }
+                csv.push('\n');
+            }
+
+            std::fs::create_dir_all(&level_dir)?;
+            let path = level_dir.join(format!("{}.csv", layer.identifier));
+            std::fs::write(&path, csv)?;
+            written.push(path);
+        }
+    }
+
+    Ok(written)
+}
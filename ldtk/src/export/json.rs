@@ -0,0 +1,28 @@
+//! Export of each level as a standalone JSON file, in the same format the editor uses for external levels.
+
+use crate::Project;
+use std::{
+    io,
+    path::{Path, PathBuf},
+};
+
+/// Writes every level as `<level>.json` into `out_dir`, minified if the project asks for it. Returns the paths of
+/// all written files.
+pub fn export_levels<O: AsRef<Path>>(project: &Project, out_dir: O) -> io::Result<Vec<PathBuf>> {
+    let out_dir = out_dir.as_ref();
+    std::fs::create_dir_all(out_dir)?;
+
+    let mut written = Vec::new();
+    for level in &project.levels {
+        let json = if project.minify_json {
+            serde_json::to_string(level)?
+        } else {
+            serde_json::to_string_pretty(level)?
+        };
+        let path = out_dir.join(format!("{}.json", level.identifier));
+        std::fs::write(&path, json)?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
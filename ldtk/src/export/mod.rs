@@ -0,0 +1,5 @@
+//! Converters writing LDtk projects to other formats.
+
+pub mod csv;
+pub mod json;
+pub mod tiled;
@@ -0,0 +1,466 @@
+//! Export to the [Tiled](https://www.mapeditor.org) TMX/TSX formats.
+//!
+//! Every level becomes a `.tmx` map and every tileset a `.tsx` file referencing the original image:
+//!
+//! - Tile and auto-layer layers become tile layers. Tiled only holds one tile per cell, so stacked tiles are spread
+//!   over extra layers suffixed with `_2`, `_3`, etc.
+//! - IntGrid layers become CSV tile layers using a generated `<Layer>_IntGrid.tsx` tileset, made of one colored tile
+//!   per IntGrid value. Their auto-layer tiles, if any, are exported as an extra `<Layer>_auto` tile layer.
+//! - Entity layers become object groups. Entity fields are written as custom properties, as are level fields on
+//!   the map itself.
+//!
+//! The map tile size is the project `defaultGridSize`. Tiled maps have a single tile size, so projects with tile,
+//! auto-layer or IntGrid layers on another grid are rejected.
+
+use crate::{relative_path, FieldInstance, LayerDefinition, LayerInstance, Level, Project, TileInstance};
+use image::{Rgba, RgbaImage};
+use serde_json::Value;
+use std::{
+    fmt::Write as _,
+    io,
+    path::{Path, PathBuf},
+};
+
+static TMX_VERSION: &str = "1.4";
+static TILED_VERSION: &str = "1.4.3";
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+
+enum TilesetSource {
+    Tileset(i64),
+    /// IntGrid layer definition UID, along with its values in tile order.
+    IntGrid(i64, Vec<i64>),
+}
+
+struct TilesetRef {
+    source: TilesetSource,
+    first_gid: i64,
+    file_name: String,
+}
+
+/// Writes every level of `project` as `<level>.tmx` into `out_dir`, along with the `.tsx` files of all tilesets.
+///
+/// `project_dir` is the directory containing the project file, used to resolve tileset images. Returns the paths of
+/// all written files. This doesn't check `Project::export_tiled`, which only tells whether the editor exports on save.
+///
+/// Fails with `io::ErrorKind::InvalidInput`, before writing anything, if a tile layer isn't on the project grid.
+pub fn export_project<P: AsRef<Path>, O: AsRef<Path>>(
+    project: &Project,
+    project_dir: P,
+    out_dir: O,
+) -> io::Result<Vec<PathBuf>> {
+    check_grids(project)?;
+    let out_dir = out_dir.as_ref();
+    std::fs::create_dir_all(out_dir)?;
+
+    let mut written = Vec::new();
+    let mut tilesets = Vec::new();
+    let mut next_gid = 1;
+
+    for tileset in &project.defs.tilesets {
+        let file_name = format!("{}.tsx", tileset.identifier);
+        let image = project_dir.as_ref().join(&tileset.rel_path);
+        let image_source = relative_path(out_dir, &image).to_string_lossy().replace('\\', "/");
+
+        let mut tsx = String::new();
+        let _ = writeln!(tsx, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        let _ = writeln!(
+            tsx,
+            r#"<tileset version="{}" tiledversion="{}" name="{}" tilewidth="{grid}" tileheight="{grid}" spacing="{}" margin="{}" tilecount="{}" columns="{}">"#,
+            TMX_VERSION,
+            TILED_VERSION,
+            escape(&tileset.identifier),
+            tileset.spacing,
+            tileset.padding,
+            tileset.c_wid * tileset.c_hei,
+            tileset.c_wid,
+            grid = tileset.tile_grid_size,
+        );
+        let _ = writeln!(
+            tsx,
+            r#" <image source="{}" width="{}" height="{}"/>"#,
+            escape(&image_source),
+            tileset.px_wid,
+            tileset.px_hei
+        );
+        tsx.push_str("</tileset>\n");
+
+        let path = out_dir.join(&file_name);
+        std::fs::write(&path, tsx)?;
+        written.push(path);
+
+        tilesets.push(TilesetRef {
+            source: TilesetSource::Tileset(tileset.uid),
+            first_gid: next_gid,
+            file_name,
+        });
+        next_gid += tileset.c_wid * tileset.c_hei;
+    }
+
+    for layer in project.defs.layers.iter().filter(|l| !l.int_grid_values.is_empty()) {
+        let file_name = format!("{}_IntGrid.tsx", layer.identifier);
+        written.extend(write_int_grid_tileset(layer, out_dir, &file_name)?);
+
+        tilesets.push(TilesetRef {
+            source: TilesetSource::IntGrid(layer.uid, layer.int_grid_values.iter().map(|v| v.value).collect()),
+            first_gid: next_gid,
+            file_name,
+        });
+        next_gid += layer.int_grid_values.len() as i64;
+    }
+
+    for level in &project.levels {
+        let path = out_dir.join(format!("{}.tmx", level.identifier));
+        std::fs::write(&path, level_tmx(project, level, &tilesets))?;
+        written.push(path);
+    }
+
+    Ok(written)
+}
+
+/// Checks that every layer written as a tile layer uses the map tile size.
+fn check_grids(project: &Project) -> io::Result<()> {
+    let grid = project.default_grid_size;
+    if grid <= 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("invalid default grid size {}", grid),
+        ));
+    }
+    for level in &project.levels {
+        for layer in level.layer_instances.iter().flatten() {
+            if layer.layer_instance_type != "Entities" && layer.grid_size != grid {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "layer {} of level {} has a grid size of {}, Tiled maps only support the project grid size {}",
+                        layer.identifier, level.identifier, layer.grid_size, grid
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes a tileset (and its image) with one tile per IntGrid value, filled with the value color.
+fn write_int_grid_tileset(layer: &LayerDefinition, out_dir: &Path, file_name: &str) -> io::Result<Vec<PathBuf>> {
+    let grid = layer.grid_size as u32;
+    let count = layer.int_grid_values.len() as u32;
+    let image_name = format!("{}_IntGrid.png", layer.identifier);
+
+    let mut image = RgbaImage::new(grid * count, grid);
+    for (idx, value) in layer.int_grid_values.iter().enumerate() {
+        let color = Rgba(value.color.to_rgba());
+        for y in 0..grid {
+            for x in 0..grid {
+                image.put_pixel(idx as u32 * grid + x, y, color);
+            }
+        }
+    }
+    let image_path = out_dir.join(&image_name);
+    image.save(&image_path).map_err(io::Error::other)?;
+
+    let mut tsx = String::new();
+    let _ = writeln!(tsx, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        tsx,
+        r#"<tileset version="{}" tiledversion="{}" name="{}_IntGrid" tilewidth="{grid}" tileheight="{grid}" tilecount="{}" columns="{}">"#,
+        TMX_VERSION,
+        TILED_VERSION,
+        escape(&layer.identifier),
+        count,
+        count,
+        grid = grid,
+    );
+    let _ = writeln!(
+        tsx,
+        r#" <image source="{}" width="{}" height="{}"/>"#,
+        escape(&image_name),
+        grid * count,
+        grid
+    );
+    for (idx, value) in layer.int_grid_values.iter().enumerate() {
+        let _ = writeln!(tsx, r#" <tile id="{}">"#, idx);
+        tsx.push_str("  <properties>\n");
+        let _ = writeln!(tsx, r#"   <property name="value" type="int" value="{}"/>"#, value.value);
+        if let Some(identifier) = &value.identifier {
+            let _ = writeln!(
+                tsx,
+                r#"   <property name="identifier" value="{}"/>"#,
+                escape(identifier)
+            );
+        }
+        let _ = writeln!(
+            tsx,
+            r##"   <property name="color" type="color" value="#{:02x}{:06x}"/>"##,
+            value.color.a,
+            value.color.to_rgb_u32()
+        );
+        tsx.push_str("  </properties>\n");
+        tsx.push_str(" </tile>\n");
+    }
+    tsx.push_str("</tileset>\n");
+
+    let path = out_dir.join(file_name);
+    std::fs::write(&path, tsx)?;
+    Ok(vec![image_path, path])
+}
+
+fn level_tmx(project: &Project, level: &Level, tilesets: &[TilesetRef]) -> String {
+    let grid = project.default_grid_size;
+    let mut next_layer_id = 1;
+    let mut next_object_id = 1;
+
+    let mut body = String::new();
+    for layer in level.layer_instances.iter().flatten().rev() {
+        match &layer.layer_instance_type[..] {
+            "Tiles" => write_tile_layers(
+                &mut body,
+                &mut next_layer_id,
+                layer,
+                &layer.identifier,
+                &layer.grid_tiles,
+                tilesets,
+            ),
+            "AutoLayer" => write_tile_layers(
+                &mut body,
+                &mut next_layer_id,
+                layer,
+                &layer.identifier,
+                &layer.auto_layer_tiles,
+                tilesets,
+            ),
+            "IntGrid" => {
+                write_int_grid_layer(&mut body, &mut next_layer_id, layer, tilesets);
+                write_tile_layers(
+                    &mut body,
+                    &mut next_layer_id,
+                    layer,
+                    &format!("{}_auto", layer.identifier),
+                    &layer.auto_layer_tiles,
+                    tilesets,
+                );
+            }
+            "Entities" => write_object_group(&mut body, &mut next_layer_id, &mut next_object_id, layer),
+            _ => {}
+        }
+    }
+
+    let mut tmx = String::new();
+    let _ = writeln!(tmx, r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    let _ = writeln!(
+        tmx,
+        r#"<map version="{}" tiledversion="{}" orientation="orthogonal" renderorder="right-down" width="{}" height="{}" tilewidth="{grid}" tileheight="{grid}" infinite="0" backgroundcolor="{}" nextlayerid="{}" nextobjectid="{}">"#,
+        TMX_VERSION,
+        TILED_VERSION,
+        level.px_wid / grid,
+        level.px_hei / grid,
+        level.bg_color,
+        next_layer_id,
+        next_object_id,
+        grid = grid,
+    );
+    write_properties(&mut tmx, " ", &level.field_instances);
+    for tileset in tilesets {
+        let _ = writeln!(
+            tmx,
+            r#" <tileset firstgid="{}" source="{}"/>"#,
+            tileset.first_gid,
+            escape(&tileset.file_name)
+        );
+    }
+    tmx.push_str(&body);
+    tmx.push_str("</map>\n");
+    tmx
+}
+
+fn write_tile_layers(
+    out: &mut String,
+    next_layer_id: &mut i64,
+    layer: &LayerInstance,
+    name: &str,
+    tiles: &[TileInstance],
+    tilesets: &[TilesetRef],
+) {
+    let first_gid = match tilesets.iter().find_map(|t| match t.source {
+        TilesetSource::Tileset(uid) if Some(uid) == layer.tileset_def_uid => Some(t.first_gid),
+        _ => None,
+    }) {
+        Some(first_gid) => first_gid,
+        None => return,
+    };
+
+    let size = (layer.c_wid * layer.c_hei) as usize;
+    let mut stacks: Vec<Vec<u32>> = Vec::new();
+    for tile in tiles {
+        let (cx, cy) = (tile.px[0] / layer.grid_size, tile.px[1] / layer.grid_size);
+        if cx < 0 || cy < 0 || cx >= layer.c_wid || cy >= layer.c_hei {
+            continue;
+        }
+        let idx = (cx + cy * layer.c_wid) as usize;
+
+        let mut gid = (first_gid + tile.t) as u32;
+        if tile.f & 1 != 0 {
+            gid |= FLIPPED_HORIZONTALLY;
+        }
+        if tile.f & 2 != 0 {
+            gid |= FLIPPED_VERTICALLY;
+        }
+
+        match stacks.iter_mut().find(|s| s[idx] == 0) {
+            Some(stack) => stack[idx] = gid,
+            None => {
+                let mut stack = vec![0; size];
+                stack[idx] = gid;
+                stacks.push(stack);
+            }
+        }
+    }
+
+    for (idx, stack) in stacks.iter().enumerate() {
+        let name = if idx == 0 {
+            name.to_string()
+        } else {
+            format!("{}_{}", name, idx + 1)
+        };
+        write_csv_layer(out, next_layer_id, layer, &name, stack);
+    }
+}
+
+fn write_int_grid_layer(out: &mut String, next_layer_id: &mut i64, layer: &LayerInstance, tilesets: &[TilesetRef]) {
+    let (first_gid, values) = match tilesets.iter().find_map(|t| match &t.source {
+        TilesetSource::IntGrid(uid, values) if *uid == layer.layer_def_uid => Some((t.first_gid, values)),
+        _ => None,
+    }) {
+        Some(found) => found,
+        None => return,
+    };
+
+    let gids: Vec<u32> = layer
+        .int_grid_csv
+        .iter()
+        .map(|v| match values.iter().position(|value| value == v) {
+            Some(idx) => (first_gid + idx as i64) as u32,
+            None => 0,
+        })
+        .collect();
+    write_csv_layer(out, next_layer_id, layer, &layer.identifier, &gids);
+}
+
+fn write_csv_layer(out: &mut String, next_layer_id: &mut i64, layer: &LayerInstance, name: &str, gids: &[u32]) {
+    let _ = writeln!(
+        out,
+        r#" <layer id="{}" name="{}" width="{}" height="{}"{}>"#,
+        next_layer_id,
+        escape(name),
+        layer.c_wid,
+        layer.c_hei,
+        layer_attributes(layer)
+    );
+    *next_layer_id += 1;
+
+    out.push_str("  <data encoding=\"csv\">\n");
+    let rows: Vec<String> = gids
+        .chunks(layer.c_wid.max(1) as usize)
+        .map(|row| row.iter().map(|g| g.to_string()).collect::<Vec<_>>().join(","))
+        .collect();
+    out.push_str(&rows.join(",\n"));
+    out.push_str("\n  </data>\n");
+    out.push_str(" </layer>\n");
+}
+
+fn write_object_group(out: &mut String, next_layer_id: &mut i64, next_object_id: &mut i64, layer: &LayerInstance) {
+    let _ = writeln!(
+        out,
+        r#" <objectgroup id="{}" name="{}"{}>"#,
+        next_layer_id,
+        escape(&layer.identifier),
+        layer_attributes(layer)
+    );
+    *next_layer_id += 1;
+
+    for entity in &layer.entity_instances {
+        let bounds = entity.bounds();
+        let _ = writeln!(
+            out,
+            r#"  <object id="{}" name="{ident}" type="{ident}" x="{}" y="{}" width="{}" height="{}">"#,
+            next_object_id,
+            bounds.x,
+            bounds.y,
+            bounds.width,
+            bounds.height,
+            ident = escape(&entity.identifier),
+        );
+        *next_object_id += 1;
+        write_properties(out, "   ", &entity.field_instances);
+        out.push_str("  </object>\n");
+    }
+    out.push_str(" </objectgroup>\n");
+}
+
+fn layer_attributes(layer: &LayerInstance) -> String {
+    let mut attrs = String::new();
+    if layer.opacity < 1. {
+        let _ = write!(attrs, r#" opacity="{}""#, layer.opacity);
+    }
+    if !layer.visible {
+        attrs.push_str(r#" visible="0""#);
+    }
+    if layer.px_total_offset_x != 0 {
+        let _ = write!(attrs, r#" offsetx="{}""#, layer.px_total_offset_x);
+    }
+    if layer.px_total_offset_y != 0 {
+        let _ = write!(attrs, r#" offsety="{}""#, layer.px_total_offset_y);
+    }
+    attrs
+}
+
+fn write_properties(out: &mut String, indent: &str, fields: &[FieldInstance]) {
+    let properties: Vec<_> = fields.iter().filter_map(property).collect();
+    if properties.is_empty() {
+        return;
+    }
+
+    let _ = writeln!(out, "{}<properties>", indent);
+    for (name, ty, value) in properties {
+        let _ = writeln!(
+            out,
+            r#"{} <property name="{}" type="{}" value="{}"/>"#,
+            indent,
+            escape(name),
+            ty,
+            escape(&value)
+        );
+    }
+    let _ = writeln!(out, "{}</properties>", indent);
+}
+
+/// Converts a field instance to a Tiled `(name, type, value)` property. Null fields are skipped, and values with no
+/// Tiled equivalent (enums, points, arrays) are written as strings.
+fn property(field: &FieldInstance) -> Option<(&str, &'static str, String)> {
+    let value = match &field.value {
+        None | Some(Value::Null) => return None,
+        Some(value) => value,
+    };
+
+    let (ty, value) = match (&field.field_instance_type[..], value) {
+        ("Int", v) => ("int", v.to_string()),
+        ("Float", v) => ("float", v.to_string()),
+        ("Bool", v) => ("bool", v.to_string()),
+        ("Color", Value::String(s)) => ("color", format!("#ff{}", s.trim_start_matches('#').to_lowercase())),
+        ("FilePath", Value::String(s)) => ("file", s.clone()),
+        (_, Value::String(s)) => ("string", s.clone()),
+        (_, v) => ("string", v.to_string()),
+    };
+    Some((&field.identifier, ty, value))
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
@@ -0,0 +1,3 @@
+//! Converters reading other formats into LDtk projects.
+
+pub mod tiled;
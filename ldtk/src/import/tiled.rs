@@ -0,0 +1,1035 @@
+//! Import of [Tiled](https://www.mapeditor.org) TMX/TSX maps.
+//!
+//! Each map becomes a level, laid out left to right in a `LinearHorizontal` world:
+//!
+//! - Tile layers become Tiles layers. LDtk layers use a single tileset, so tiles from other tilesets are moved to
+//!   extra layers named `<Layer>_<Tileset>`. Tiles using a tileset whose tiles all have an integer `value` property
+//!   (like the ones written by [`crate::export::tiled`]) become IntGrid layers instead.
+//! - Object groups become Entities layers. Entity definitions are inferred from the object types (or names), and
+//!   custom properties become entity fields.
+//! - Map properties become level fields.
+//!
+//! Layers, tilesets and entities are shared between maps by name, and all UIDs are allocated from
+//! `Project::next_uid`. Levels are named after their map file, with a numeric suffix for maps sharing a name. Only
+//! orthogonal, finite maps with square tiles, tilesets of the same tile size and uncompressed layer data are
+//! supported.
+
+use crate::{
+    relative_path, Color, EntityDefinition, EntityInstance, FieldDefinition, FieldInstance, IntGridValueDefinition,
+    LayerDefinition, LayerInstance, Level, Project, TileInstance, TilesetDefinition, Type, WorldLayout,
+};
+use roxmltree::Node;
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::{Path, PathBuf},
+};
+
+const FLIPPED_HORIZONTALLY: u32 = 0x8000_0000;
+const FLIPPED_VERTICALLY: u32 = 0x4000_0000;
+const FLIPPED_DIAGONALLY: u32 = 0x2000_0000;
+
+#[derive(Debug)]
+pub enum ImportError {
+    Io(PathBuf, io::Error),
+    Xml(PathBuf, roxmltree::Error),
+    Unsupported(PathBuf, String),
+    /// A value that can't be converted, such as a zero tile size.
+    Invalid(PathBuf, String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(path, _) => write!(f, "failed to read {}", path.display()),
+            ImportError::Xml(path, _) => write!(f, "failed to parse {}", path.display()),
+            ImportError::Unsupported(path, what) => write!(f, "{}: {} are not supported", path.display(), what),
+            ImportError::Invalid(path, what) => write!(f, "{}: invalid {}", path.display(), what),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ImportError::Io(_, e) => Some(e),
+            ImportError::Xml(_, e) => Some(e),
+            ImportError::Unsupported(..) | ImportError::Invalid(..) => None,
+        }
+    }
+}
+
+/// Imports all `maps` into a new project that will be saved in `project_dir`.
+pub fn import_maps<P: AsRef<Path>, I: IntoIterator<Item = M>, M: AsRef<Path>>(
+    project_dir: P,
+    maps: I,
+) -> Result<Project, ImportError> {
+    let mut importer = Importer::new(project_dir);
+    for map in maps {
+        importer.import_map(map)?;
+    }
+    Ok(importer.finish())
+}
+
+#[derive(Clone)]
+enum GidTarget {
+    Tileset {
+        uid: i64,
+        identifier: String,
+        columns: u32,
+        c_wid: i64,
+        grid: i64,
+        spacing: i64,
+        padding: i64,
+    },
+    IntGrid {
+        identifier: String,
+        values: HashMap<u32, i64>,
+        defs: Vec<IntGridValueDefinition>,
+    },
+}
+
+impl GidTarget {
+    fn identifier(&self) -> &str {
+        match self {
+            GidTarget::Tileset { identifier, .. } | GidTarget::IntGrid { identifier, .. } => identifier,
+        }
+    }
+}
+
+/// Incremental importer, for converting maps one by one into the same project.
+pub struct Importer {
+    project: Project,
+    project_dir: PathBuf,
+    tilesets: HashMap<PathBuf, GidTarget>,
+    layer_defs: HashMap<String, i64>,
+    entity_defs: HashMap<String, i64>,
+    levels: Vec<(Level, HashMap<i64, LayerInstance>)>,
+    next_world_x: i64,
+}
+
+impl Importer {
+    /// Creates an importer for a project that will be saved in `project_dir`, which tileset paths are relative to.
+    pub fn new<P: AsRef<Path>>(project_dir: P) -> Self {
+        Self {
+            project: Project {
+                world_layout: WorldLayout::LinearHorizontal,
+                ..Default::default()
+            },
+            project_dir: project_dir.as_ref().to_path_buf(),
+            tilesets: HashMap::default(),
+            layer_defs: HashMap::default(),
+            entity_defs: HashMap::default(),
+            levels: Vec::new(),
+            next_world_x: 0,
+        }
+    }
+
+    /// Converts the map at `path` into a new level, returning its UID.
+    pub fn import_map<P: AsRef<Path>>(&mut self, path: P) -> Result<i64, ImportError> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| ImportError::Io(path.into(), e))?;
+        let doc = roxmltree::Document::parse(&text).map_err(|e| ImportError::Xml(path.into(), e))?;
+        let map = doc.root_element();
+        let unsupported = |what: &str| ImportError::Unsupported(path.into(), what.into());
+        let invalid = |what: String| ImportError::Invalid(path.into(), what);
+
+        if map.tag_name().name() != "map" {
+            return Err(unsupported("files other than TMX maps"));
+        }
+        if map.attribute("orientation").unwrap_or("orthogonal") != "orthogonal" {
+            return Err(unsupported("non-orthogonal maps"));
+        }
+        if map.attribute("infinite") == Some("1") {
+            return Err(unsupported("infinite maps"));
+        }
+        let grid = int_attr(map, "tilewidth", 16);
+        if grid <= 0 {
+            return Err(invalid(format!("tile width {}", grid)));
+        }
+        if int_attr(map, "tileheight", grid) != grid {
+            return Err(unsupported("non-square tiles"));
+        }
+        let (width, height) = (int_attr(map, "width", 0), int_attr(map, "height", 0));
+        if width <= 0 || height <= 0 {
+            return Err(invalid(format!("map size {}x{}", width, height)));
+        }
+
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        let mut gids = Vec::new();
+        for tileset in map.children().filter(|n| n.has_tag_name("tileset")) {
+            let first_gid = int_attr(tileset, "firstgid", 1) as u32;
+            let target = match tileset.attribute("source") {
+                Some(source) => self.import_external_tileset(&dir.join(source))?,
+                None => {
+                    let key = path.join(tileset.attribute("name").unwrap_or_default());
+                    self.import_tileset(tileset, dir, key, path)?
+                }
+            };
+            if let GidTarget::Tileset { grid: tile_size, .. } = target {
+                if tile_size != grid {
+                    return Err(unsupported(&format!(
+                        "tilesets with {} px tiles on a {} px grid",
+                        tile_size, grid
+                    )));
+                }
+            }
+            gids.push((first_gid, target));
+        }
+        gids.sort_by_key(|(first_gid, _)| *first_gid);
+
+        // Level identifiers are unique, maps from different directories may share a name
+        let name = sanitize(&path.file_stem().unwrap_or_default().to_string_lossy());
+        let mut identifier = name.clone();
+        let mut suffix = 1;
+        while self.levels.iter().any(|(level, _)| level.identifier == identifier) {
+            suffix += 1;
+            identifier = format!("{}_{}", name, suffix);
+        }
+
+        let uid = self.project.allocate_uid();
+        let mut level = Level {
+            identifier,
+            uid,
+            px_wid: width * grid,
+            px_hei: height * grid,
+            world_x: self.next_world_x,
+            ..Default::default()
+        };
+        if let Some(color) = map.attribute("backgroundcolor").and_then(rgb_color) {
+            level.bg_color = color;
+            level.level_bg_color = Some(color);
+        } else {
+            level.bg_color = self.project.default_level_bg_color;
+        }
+        for (name, ty, value) in properties(map) {
+            let field = self.level_field(&name, ty);
+            level.field_instances.push(field_instance(field, value));
+        }
+        self.next_world_x += level.px_wid;
+
+        let mut instances = HashMap::new();
+        let layers = LayerContext {
+            level: &level,
+            gids: &gids,
+            grid,
+            offset: (0, 0),
+            opacity: 1.,
+            visible: true,
+        };
+        self.import_layers(map, &layers, &mut instances, path)?;
+
+        self.levels.push((level, instances));
+        Ok(uid)
+    }
+
+    /// Finalizes the project: layer definitions are put in LDtk display order (top-most first), and every level gets
+    /// an instance of every layer, and of every field.
+    pub fn finish(self) -> Project {
+        let mut project = self.project;
+        project.defs.layers.reverse();
+
+        for (mut level, mut instances) in self.levels {
+            let mut layers = Vec::new();
+            for def in &project.defs.layers {
+                let mut layer = instances
+                    .remove(&def.uid)
+                    .unwrap_or_else(|| LayerInstance::new(&project, def, &level));
+                for entity in &mut layer.entity_instances {
+                    if let Some(entity_def) = project.defs.entities.iter().find(|d| d.uid == entity.def_uid) {
+                        add_missing_fields(&mut entity.field_instances, &entity_def.field_defs);
+                    }
+                }
+                layers.push(layer);
+            }
+            level.layer_instances = Some(layers);
+            add_missing_fields(&mut level.field_instances, &project.defs.level_fields);
+            project.levels.push(level);
+        }
+        project
+    }
+
+    fn import_external_tileset(&mut self, path: &Path) -> Result<GidTarget, ImportError> {
+        let key = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        if let Some(target) = self.tilesets.get(&key) {
+            return Ok(target.clone());
+        }
+
+        let text = std::fs::read_to_string(path).map_err(|e| ImportError::Io(path.into(), e))?;
+        let doc = roxmltree::Document::parse(&text).map_err(|e| ImportError::Xml(path.into(), e))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        self.import_tileset(doc.root_element(), dir, key, path)
+    }
+
+    fn import_tileset(&mut self, node: Node, dir: &Path, key: PathBuf, file: &Path) -> Result<GidTarget, ImportError> {
+        if let Some(target) = self.tilesets.get(&key) {
+            return Ok(target.clone());
+        }
+
+        let identifier = sanitize(node.attribute("name").unwrap_or("Tileset"));
+        let tile_count = int_attr(node, "tilecount", 0) as usize;
+        let mut values = HashMap::new();
+        let mut defs = Vec::new();
+        for tile in node.children().filter(|n| n.has_tag_name("tile")) {
+            let props = properties(tile);
+            let value = props.iter().find_map(|(name, _, value)| match (name.as_str(), value) {
+                ("value", Value::Number(n)) => n.as_i64(),
+                _ => None,
+            });
+            if let Some(value) = value {
+                values.insert(int_attr(tile, "id", 0) as u32, value);
+                defs.push(IntGridValueDefinition {
+                    value,
+                    identifier: props.iter().find_map(|(name, _, v)| match (name.as_str(), v) {
+                        ("identifier", Value::String(s)) => Some(s.clone()),
+                        _ => None,
+                    }),
+                    color: props
+                        .iter()
+                        .find_map(|(name, _, v)| match (name.as_str(), v) {
+                            ("color", Value::String(s)) => Color::from_hex(s).ok(),
+                            _ => None,
+                        })
+                        .unwrap_or_else(|| IntGridValueDefinition::default().color),
+                    ..Default::default()
+                });
+            }
+        }
+
+        let target = if tile_count > 0 && values.len() == tile_count {
+            defs.sort_by_key(|d| d.value);
+            GidTarget::IntGrid {
+                identifier,
+                values,
+                defs,
+            }
+        } else {
+            let image = match node.children().find(|n| n.has_tag_name("image")) {
+                Some(image) => image,
+                None => {
+                    return Err(ImportError::Unsupported(
+                        file.into(),
+                        "image collection tilesets".into(),
+                    ))
+                }
+            };
+            let image_path = dir.join(image.attribute("source").unwrap_or_default());
+            let (px_wid, px_hei) = match (image.attribute("width"), image.attribute("height")) {
+                (Some(_), Some(_)) => (int_attr(image, "width", 0), int_attr(image, "height", 0)),
+                _ => image::image_dimensions(&image_path)
+                    .map(|(w, h)| (w as i64, h as i64))
+                    .map_err(|e| ImportError::Io(image_path.clone(), io::Error::other(e)))?,
+            };
+
+            let invalid = |what: String| ImportError::Invalid(file.into(), what);
+            let grid = int_attr(node, "tilewidth", 16);
+            let spacing = int_attr(node, "spacing", 0);
+            let padding = int_attr(node, "margin", 0);
+            if grid <= 0 || spacing < 0 || padding < 0 {
+                return Err(invalid(format!(
+                    "tileset {} tile width {}, spacing {} or margin {}",
+                    identifier, grid, spacing, padding
+                )));
+            }
+            let c_wid = (px_wid - 2 * padding + spacing) / (grid + spacing);
+            let c_hei = (px_hei - 2 * padding + spacing) / (grid + spacing);
+            let columns = int_attr(node, "columns", c_wid);
+            if columns <= 0 {
+                return Err(invalid(format!("tileset {} with {} columns", identifier, columns)));
+            }
+
+            let uid = self.project.allocate_uid();
+            self.project.defs.tilesets.push(TilesetDefinition {
+                c_hei,
+                c_wid,
+                identifier: identifier.clone(),
+                padding,
+                px_hei,
+                px_wid,
+                rel_path: relative_path(&self.project_dir, &image_path)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+                spacing,
+                tile_grid_size: grid,
+                uid,
+                ..Default::default()
+            });
+            GidTarget::Tileset {
+                uid,
+                identifier,
+                columns: columns as u32,
+                c_wid,
+                grid,
+                spacing,
+                padding,
+            }
+        };
+
+        self.tilesets.insert(key, target.clone());
+        Ok(target)
+    }
+
+    fn import_layers(
+        &mut self,
+        parent: Node,
+        ctx: &LayerContext,
+        instances: &mut HashMap<i64, LayerInstance>,
+        file: &Path,
+    ) -> Result<(), ImportError> {
+        for node in parent.children().filter(|n| n.is_element()) {
+            let ctx = LayerContext {
+                offset: (
+                    ctx.offset.0 + float_attr(node, "offsetx", 0.) as i64,
+                    ctx.offset.1 + float_attr(node, "offsety", 0.) as i64,
+                ),
+                opacity: ctx.opacity * float_attr(node, "opacity", 1.),
+                visible: ctx.visible && node.attribute("visible") != Some("0"),
+                ..*ctx
+            };
+            let name = sanitize(node.attribute("name").unwrap_or("Layer"));
+
+            match node.tag_name().name() {
+                "group" => self.import_layers(node, &ctx, instances, file)?,
+                "layer" => {
+                    let data = match node.children().find(|n| n.has_tag_name("data")) {
+                        Some(data) => data,
+                        None => continue,
+                    };
+                    let gids = decode_data(data, file)?;
+                    self.import_tile_layer(&name, &gids, &ctx, instances);
+                }
+                "objectgroup" => self.import_object_group(node, &name, &ctx, instances),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn import_tile_layer(
+        &mut self,
+        name: &str,
+        gids: &[u32],
+        ctx: &LayerContext,
+        instances: &mut HashMap<i64, LayerInstance>,
+    ) {
+        let c_wid = ctx.level.px_wid / ctx.grid;
+
+        // One LDtk layer per tileset used by the Tiled layer
+        let mut layers: Vec<(usize, LayerInstance)> = Vec::new();
+        for (idx, raw) in gids.iter().enumerate() {
+            let gid = raw & !(FLIPPED_HORIZONTALLY | FLIPPED_VERTICALLY | FLIPPED_DIAGONALLY);
+            let target_idx = match ctx
+                .gids
+                .iter()
+                .rposition(|(first_gid, _)| gid != 0 && *first_gid <= gid)
+            {
+                Some(target_idx) => target_idx,
+                None => continue,
+            };
+            let (first_gid, target) = &ctx.gids[target_idx];
+            let local_id = gid - first_gid;
+
+            let layer = match layers.iter_mut().position(|(t, _)| *t == target_idx) {
+                Some(pos) => &mut layers[pos].1,
+                None => {
+                    let identifier = if layers.is_empty() {
+                        name.to_string()
+                    } else {
+                        format!("{}_{}", name, target.identifier())
+                    };
+                    let layer = match target {
+                        GidTarget::Tileset { uid, .. } => {
+                            self.layer_instance(&identifier, Type::Tiles, Some(*uid), &[], ctx, instances)
+                        }
+                        GidTarget::IntGrid { defs, .. } => {
+                            self.layer_instance(&identifier, Type::IntGrid, None, defs, ctx, instances)
+                        }
+                    };
+                    layers.push((target_idx, layer));
+                    &mut layers.last_mut().unwrap().1
+                }
+            };
+
+            let (cx, cy) = (idx as i64 % c_wid, idx as i64 / c_wid);
+            match target {
+                GidTarget::Tileset {
+                    columns,
+                    c_wid: tileset_c_wid,
+                    grid,
+                    spacing,
+                    padding,
+                    ..
+                } => {
+                    let (tx, ty) = ((local_id % columns) as i64, (local_id / columns) as i64);
+                    let mut f = 0;
+                    if raw & FLIPPED_HORIZONTALLY != 0 {
+                        f |= 1;
+                    }
+                    if raw & FLIPPED_VERTICALLY != 0 {
+                        f |= 2;
+                    }
+                    layer.grid_tiles.push(TileInstance {
+                        d: vec![cx + cy * layer.c_wid],
+                        f,
+                        px: vec![cx * ctx.grid, cy * ctx.grid],
+                        src: vec![padding + tx * (grid + spacing), padding + ty * (grid + spacing)],
+                        t: tx + ty * tileset_c_wid,
+                        ..Default::default()
+                    });
+                }
+                GidTarget::IntGrid { values, .. } => {
+                    if let (Some(value), Some(cell)) = (values.get(&local_id), layer.int_grid_csv.get_mut(idx)) {
+                        *cell = *value;
+                    }
+                }
+            }
+        }
+
+        for (_, layer) in layers {
+            instances.insert(layer.layer_def_uid, layer);
+        }
+    }
+
+    fn import_object_group(
+        &mut self,
+        node: Node,
+        name: &str,
+        ctx: &LayerContext,
+        instances: &mut HashMap<i64, LayerInstance>,
+    ) {
+        let mut layer = self.layer_instance(name, Type::Entities, None, &[], ctx, instances);
+
+        for object in node.children().filter(|n| n.has_tag_name("object")) {
+            let identifier = sanitize(
+                object
+                    .attribute("type")
+                    .or_else(|| object.attribute("class"))
+                    .or_else(|| object.attribute("name"))
+                    .unwrap_or("Object"),
+            );
+            let (mut width, mut height) = (
+                float_attr(object, "width", 0.).round() as i64,
+                float_attr(object, "height", 0.).round() as i64,
+            );
+            if width <= 0 || height <= 0 {
+                width = ctx.grid;
+                height = ctx.grid;
+            }
+            let x = float_attr(object, "x", 0.).round() as i64;
+            let mut y = float_attr(object, "y", 0.).round() as i64;
+            if object.attribute("gid").is_some() {
+                // Tile objects are anchored at their bottom-left corner
+                y -= height;
+            }
+
+            let def_uid = self.entity_def(&identifier, width, height);
+            let mut field_instances = Vec::new();
+            for (name, ty, value) in properties(object) {
+                let field = self.entity_field(def_uid, &name, ty);
+                field_instances.push(field_instance(field, value));
+            }
+
+            layer.entity_instances.push(EntityInstance {
+                grid: vec![x / ctx.grid, y / ctx.grid],
+                identifier,
+                def_uid,
+                field_instances,
+                height,
+                px: vec![x, y],
+                width,
+                ..Default::default()
+            });
+        }
+
+        instances.insert(layer.layer_def_uid, layer);
+    }
+
+    /// Creates an empty layer instance for the current level, along with its definition if it doesn't exist yet.
+    fn layer_instance(
+        &mut self,
+        name: &str,
+        ty: Type,
+        tileset_uid: Option<i64>,
+        int_grid_values: &[IntGridValueDefinition],
+        ctx: &LayerContext,
+        instances: &HashMap<i64, LayerInstance>,
+    ) -> LayerInstance {
+        // Layer identifiers are unique in LDtk, whatever their type
+        let mut identifier = name.to_string();
+        let mut suffix = 1;
+        let uid = loop {
+            match self.layer_defs.get(&identifier) {
+                Some(uid) => {
+                    let def = self.project.defs.layers.iter().find(|d| d.uid == *uid).unwrap();
+                    if !instances.contains_key(uid)
+                        && def.purple_type.name() == ty.name()
+                        && def.grid_size == ctx.grid
+                        && def.tileset_def_uid == tileset_uid
+                    {
+                        break *uid;
+                    }
+                }
+                None => {
+                    let uid = self.project.allocate_uid();
+                    self.project.defs.layers.push(LayerDefinition {
+                        layer_definition_type: ty.name().into(),
+                        display_opacity: ctx.opacity,
+                        grid_size: ctx.grid,
+                        identifier: identifier.clone(),
+                        int_grid_values: int_grid_values.to_vec(),
+                        tileset_def_uid: tileset_uid,
+                        purple_type: ty,
+                        uid,
+                        ..Default::default()
+                    });
+                    self.layer_defs.insert(identifier, uid);
+                    break uid;
+                }
+            }
+            suffix += 1;
+            identifier = format!("{}_{}", name, suffix);
+        };
+
+        let def = self.project.defs.layers.iter().find(|d| d.uid == uid).unwrap();
+        LayerInstance {
+            px_offset_x: ctx.offset.0,
+            px_offset_y: ctx.offset.1,
+            px_total_offset_x: def.px_offset_x + ctx.offset.0,
+            px_total_offset_y: def.px_offset_y + ctx.offset.1,
+            visible: ctx.visible,
+            ..LayerInstance::new(&self.project, def, ctx.level)
+        }
+    }
+
+    fn entity_def(&mut self, identifier: &str, width: i64, height: i64) -> i64 {
+        if let Some(uid) = self.entity_defs.get(identifier) {
+            let def = self.project.defs.entities.iter_mut().find(|d| d.uid == *uid).unwrap();
+            def.resizable_x |= def.width != width;
+            def.resizable_y |= def.height != height;
+            return *uid;
+        }
+
+        let uid = self.project.allocate_uid();
+        self.project.defs.entities.push(EntityDefinition {
+            identifier: identifier.into(),
+            width,
+            height,
+            uid,
+            ..Default::default()
+        });
+        self.entity_defs.insert(identifier.into(), uid);
+        uid
+    }
+
+    fn entity_field(&mut self, entity_uid: i64, name: &str, ty: FieldType) -> FieldRef {
+        let identifier = sanitize(name);
+        let idx = self
+            .project
+            .defs
+            .entities
+            .iter()
+            .position(|d| d.uid == entity_uid)
+            .unwrap();
+        if !self.project.defs.entities[idx]
+            .field_defs
+            .iter()
+            .any(|d| d.identifier == identifier)
+        {
+            let def = field_def(&identifier, ty, self.project.allocate_uid());
+            self.project.defs.entities[idx].field_defs.push(def);
+        }
+        FieldRef::find(&self.project.defs.entities[idx].field_defs, &identifier)
+    }
+
+    fn level_field(&mut self, name: &str, ty: FieldType) -> FieldRef {
+        let identifier = sanitize(name);
+        if !self
+            .project
+            .defs
+            .level_fields
+            .iter()
+            .any(|d| d.identifier == identifier)
+        {
+            let def = field_def(&identifier, ty, self.project.allocate_uid());
+            self.project.defs.level_fields.push(def);
+        }
+        FieldRef::find(&self.project.defs.level_fields, &identifier)
+    }
+}
+
+#[derive(Clone, Copy)]
+struct LayerContext<'a> {
+    level: &'a Level,
+    gids: &'a [(u32, GidTarget)],
+    grid: i64,
+    offset: (i64, i64),
+    opacity: f64,
+    visible: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum FieldType {
+    Int,
+    Float,
+    Bool,
+    String,
+    Color,
+    FilePath,
+}
+
+impl FieldType {
+    fn from_tiled(ty: &str) -> Self {
+        match ty {
+            "int" | "object" => FieldType::Int,
+            "float" => FieldType::Float,
+            "bool" => FieldType::Bool,
+            "color" => FieldType::Color,
+            "file" => FieldType::FilePath,
+            _ => FieldType::String,
+        }
+    }
+
+    fn names(self) -> (&'static str, &'static str) {
+        match self {
+            FieldType::Int => ("Int", "F_Int"),
+            FieldType::Float => ("Float", "F_Float"),
+            FieldType::Bool => ("Bool", "F_Bool"),
+            FieldType::String => ("String", "F_String"),
+            FieldType::Color => ("Color", "F_Color"),
+            FieldType::FilePath => ("FilePath", "F_Path"),
+        }
+    }
+}
+
+fn field_def(identifier: &str, ty: FieldType, uid: i64) -> FieldDefinition {
+    let (type_name, type_id) = ty.names();
+    FieldDefinition {
+        field_definition_type: type_name.into(),
+        can_be_null: true,
+        identifier: identifier.into(),
+        purple_type: Some(json!({"id": type_id, "params": []})),
+        uid,
+        ..Default::default()
+    }
+}
+
+/// The parts of a field definition needed to create instances of it.
+struct FieldRef {
+    identifier: String,
+    type_name: String,
+    uid: i64,
+}
+
+impl FieldRef {
+    fn find(defs: &[FieldDefinition], identifier: &str) -> Self {
+        let def = defs.iter().find(|d| d.identifier == identifier).unwrap();
+        Self {
+            identifier: def.identifier.clone(),
+            type_name: def.field_definition_type.clone(),
+            uid: def.uid,
+        }
+    }
+}
+
+fn field_instance(def: FieldRef, value: Value) -> FieldInstance {
+    let mut field = FieldInstance {
+        identifier: def.identifier,
+        field_instance_type: def.type_name,
+        def_uid: def.uid,
+        ..Default::default()
+    };
+    field.set_value(value);
+    field
+}
+
+/// Adds a null instance of every field from `defs` missing in `fields`.
+fn add_missing_fields(fields: &mut Vec<FieldInstance>, defs: &[FieldDefinition]) {
+    for def in defs {
+        if !fields.iter().any(|f| f.def_uid == def.uid) {
+            fields.push(FieldInstance {
+                identifier: def.identifier.clone(),
+                field_instance_type: def.field_definition_type.clone(),
+                value: Some(Value::Null),
+                def_uid: def.uid,
+                real_editor_values: vec![None],
+                ..Default::default()
+            });
+        }
+    }
+}
+
+/// Custom properties of a node, converted to field values.
+fn properties(node: Node) -> Vec<(String, FieldType, Value)> {
+    let props = match node.children().find(|n| n.has_tag_name("properties")) {
+        Some(props) => props,
+        None => return Vec::new(),
+    };
+
+    props
+        .children()
+        .filter(|n| n.has_tag_name("property"))
+        .map(|prop| {
+            let name = prop.attribute("name").unwrap_or_default().to_string();
+            let ty = FieldType::from_tiled(prop.attribute("type").unwrap_or("string"));
+            let raw = prop.attribute("value").or_else(|| prop.text()).unwrap_or_default();
+            let value = match ty {
+                FieldType::Int => raw.parse::<i64>().map(Value::from).unwrap_or(Value::Null),
+                FieldType::Float => raw.parse::<f64>().map(Value::from).unwrap_or(Value::Null),
+                FieldType::Bool => Value::Bool(raw == "true"),
+                FieldType::Color => rgb_color(raw)
+                    .map(|c| Value::String(c.to_string()))
+                    .unwrap_or(Value::Null),
+                FieldType::String | FieldType::FilePath => Value::String(raw.into()),
+            };
+            (name, ty, value)
+        })
+        .collect()
+}
+
+/// Decodes the global tile IDs of a layer `<data>` element.
+fn decode_data(data: Node, file: &Path) -> Result<Vec<u32>, ImportError> {
+    if let Some(compression) = data.attribute("compression") {
+        return Err(ImportError::Unsupported(
+            file.into(),
+            format!("{} compressed layers", compression),
+        ));
+    }
+    if data.children().any(|n| n.has_tag_name("chunk")) {
+        return Err(ImportError::Unsupported(file.into(), "infinite maps".into()));
+    }
+
+    let text = data.text().unwrap_or_default();
+    Ok(match data.attribute("encoding") {
+        Some("csv") => text.split(',').map(|gid| gid.trim().parse().unwrap_or(0)).collect(),
+        Some("base64") => decode_base64(text)
+            .chunks_exact(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect(),
+        Some(encoding) => {
+            return Err(ImportError::Unsupported(
+                file.into(),
+                format!("{} encoded layers", encoding),
+            ))
+        }
+        None => data
+            .children()
+            .filter(|n| n.has_tag_name("tile"))
+            .map(|tile| int_attr(tile, "gid", 0) as u32)
+            .collect(),
+    })
+}
+
+fn decode_base64(text: &str) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut buf = 0u32;
+    let mut bits = 0;
+    for c in text.bytes() {
+        let v = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => continue,
+        };
+        buf = (buf << 6) | v as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buf >> bits) as u8);
+        }
+    }
+    bytes
+}
+
+/// Converts a Tiled `#rrggbb` or `#aarrggbb` color, dropping alpha as LDtk colors are opaque.
+fn rgb_color(color: &str) -> Option<Color> {
+    let hex = color.trim_start_matches('#');
+    match hex.len() {
+        6 => Color::from_hex(hex).ok(),
+        8 => Color::from_hex(&hex[2..]).ok(),
+        _ => None,
+    }
+}
+
+/// Turns a Tiled name into a valid LDtk identifier.
+fn sanitize(name: &str) -> String {
+    let mut identifier: String = name
+        .trim()
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    if identifier.is_empty() || identifier.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+    identifier
+}
+
+fn int_attr(node: Node, name: &str, default: i64) -> i64 {
+    node.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+fn float_attr(node: Node, name: &str, default: f64) -> f64 {
+    node.attribute(name).and_then(|v| v.parse().ok()).unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A directory of test files, removed when dropped.
+    struct TestDir(PathBuf);
+
+    impl TestDir {
+        fn new(name: &str) -> Self {
+            let dir = std::env::temp_dir().join(format!("ldtk-import-{}-{}", name, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, path: &str, contents: &str) -> PathBuf {
+            let path = self.0.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(&path, contents).unwrap();
+            path
+        }
+    }
+
+    impl Drop for TestDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// A 3x2 map of 16 px tiles, with a tileset of 4x4 tiles and `layers` inside.
+    fn map(tile_size: i64, layers: &str) -> String {
+        format!(
+            r##"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.5" orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16" infinite="0">
+ <properties>
+  <property name="difficulty" type="int" value="3"/>
+ </properties>
+ <tileset firstgid="1" name="Tiles" tilewidth="{0}" tileheight="{0}" tilecount="16" columns="4">
+  <image source="tiles.png" width="{1}" height="{1}"/>
+ </tileset>
+ {2}
+</map>"##,
+            tile_size,
+            tile_size * 4,
+            layers
+        )
+    }
+
+    #[test]
+    fn import_map() {
+        let dir = TestDir::new("map");
+        let layers = r##"<layer id="1" name="Ground" width="3" height="2">
+  <data encoding="csv">1,0,6,0,2147483650,0</data>
+ </layer>
+ <objectgroup id="2" name="Objects">
+  <object id="1" type="Chest" x="32" y="16" width="16" height="16">
+   <properties>
+    <property name="gold" type="int" value="10"/>
+   </properties>
+  </object>
+ </objectgroup>"##;
+        let path = dir.write("castle.tmx", &map(16, layers));
+        let project = import_maps(&dir.0, &[path]).unwrap();
+
+        assert_eq!(project.defs.tilesets[0].rel_path, "tiles.png");
+        assert_eq!(
+            project
+                .defs
+                .layers
+                .iter()
+                .map(|d| d.identifier.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Objects", "Ground"]
+        );
+        let level = &project.levels[0];
+        assert_eq!(level.identifier, "castle");
+        assert_eq!((level.px_wid, level.px_hei), (48, 32));
+        assert_eq!(level.field_instances[0].identifier, "difficulty");
+        assert_eq!(level.field_instances[0].value, Some(json!(3)));
+
+        let layers = level.layer_instances.as_ref().unwrap();
+        let tiles = &layers[1].grid_tiles;
+        assert_eq!(
+            tiles.iter().map(|t| (t.px.clone(), t.t, t.f)).collect::<Vec<_>>(),
+            vec![(vec![0, 0], 0, 0), (vec![32, 0], 5, 0), (vec![16, 16], 1, 1)]
+        );
+        assert_eq!(tiles[1].src, vec![16, 16]);
+        assert_eq!(tiles[1].d, vec![2]);
+
+        let chest = &layers[0].entity_instances[0];
+        assert_eq!(chest.identifier, "Chest");
+        assert_eq!(chest.px, vec![32, 16]);
+        assert_eq!(chest.field_instances[0].value, Some(json!(10)));
+    }
+
+    #[test]
+    fn int_grid_tileset() {
+        let dir = TestDir::new("int-grid");
+        let tileset = r##"<?xml version="1.0" encoding="UTF-8"?>
+<tileset name="Walls" tilewidth="16" tileheight="16" tilecount="2" columns="2">
+ <tile id="0"><properties><property name="value" type="int" value="1"/></properties></tile>
+ <tile id="1"><properties><property name="value" type="int" value="2"/></properties></tile>
+</tileset>"##;
+        dir.write("walls.tsx", tileset);
+        let map = r##"<?xml version="1.0" encoding="UTF-8"?>
+<map orientation="orthogonal" width="3" height="2" tilewidth="16" tileheight="16">
+ <tileset firstgid="1" source="walls.tsx"/>
+ <layer name="Walls" width="3" height="2"><data encoding="csv">1,1,0,0,2,0</data></layer>
+</map>"##;
+        let path = dir.write("room.tmx", map);
+        let project = import_maps(&dir.0, &[path]).unwrap();
+
+        assert!(project.defs.tilesets.is_empty());
+        assert_eq!(project.defs.layers[0].purple_type, Type::IntGrid);
+        assert_eq!(project.defs.layers[0].int_grid_values.len(), 2);
+        let layer = &project.levels[0].layer_instances.as_ref().unwrap()[0];
+        assert_eq!(layer.int_grid_csv, vec![1, 1, 0, 0, 2, 0]);
+    }
+
+    #[test]
+    fn maps_with_the_same_name() {
+        let dir = TestDir::new("names");
+        let first = dir.write("forest/level.tmx", &map(16, ""));
+        let second = dir.write("cave/level.tmx", &map(16, ""));
+        let third = dir.write("level.tmx", &map(16, ""));
+        let project = import_maps(&dir.0, &[first, second, third]).unwrap();
+
+        assert_eq!(
+            project.levels.iter().map(|l| l.identifier.as_str()).collect::<Vec<_>>(),
+            vec!["level", "level_2", "level_3"]
+        );
+        assert_eq!(
+            project.levels.iter().map(|l| l.world_x).collect::<Vec<_>>(),
+            vec![0, 48, 96]
+        );
+    }
+
+    #[test]
+    fn unsupported_maps() {
+        let dir = TestDir::new("unsupported");
+        let path = dir.write("big_tiles.tmx", &map(32, ""));
+        match import_maps(&dir.0, [&path]) {
+            Err(ImportError::Unsupported(p, what)) => {
+                assert_eq!(p, path);
+                assert_eq!(what, "tilesets with 32 px tiles on a 16 px grid");
+            }
+            result => panic!("unexpected result {:?}", result.map(|p| p.levels.len())),
+        }
+
+        let path = dir.write(
+            "zero.tmx",
+            &map(16, "").replace(
+                r#"tilewidth="16" tileheight="16" infinite"#,
+                r#"tilewidth="0" tileheight="0" infinite"#,
+            ),
+        );
+        assert!(matches!(import_maps(&dir.0, &[path]), Err(ImportError::Invalid(..))));
+    }
+}
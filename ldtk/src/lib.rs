@@ -1,6 +1,37 @@
+pub mod auto_layer;
+mod background;
+mod builder;
+mod color;
+mod defaults;
+mod diff;
+pub mod edit;
+mod entity_tile;
+pub mod export;
+mod geom;
+pub mod import;
+mod merge;
+pub mod render;
 mod schema;
+mod shapes;
+mod spatial;
+mod unknown;
+mod validate;
+mod world;
 
+pub use background::LevelBackground;
+pub use builder::{LevelBuilder, ProjectBuilder};
+pub use color::{Color, ParseColorError};
+pub use diff::{diff, Change, DefinitionKind, ProjectDiff};
+pub use entity_tile::TileQuad;
+pub use geom::Rect;
+pub use merge::{merge, merge_level, Conflict, Merged};
 pub use schema::*;
+pub use shapes::FieldShape;
+pub use spatial::{EntityRef, Filter, SpatialIndex};
+pub use unknown::UnknownField;
+pub use validate::Issue;
+
+use serde_json::{json, Value};
 use std::{
     fs::File,
     path::{Path, PathBuf},
@@ -48,6 +79,36 @@ impl Project {
         }
     }
 
+    /// Reserves the next unique ID, the same way the editor does when creating definitions or levels.
+    pub fn allocate_uid(&mut self) -> i64 {
+        let uid = self.next_uid;
+        self.next_uid += 1;
+        uid
+    }
+
+    /// Uids of every level and definition, including auto-layer rules and field definitions, in no particular
+    /// order. Duplicates are kept.
+    pub(crate) fn defined_uids(&self) -> Vec<i64> {
+        let defs = &self.defs;
+        let mut uids = Vec::new();
+        uids.extend(self.levels.iter().map(|l| l.uid));
+        for layer in &defs.layers {
+            uids.push(layer.uid);
+            for group in &layer.auto_rule_groups {
+                uids.push(group.uid);
+                uids.extend(group.rules.iter().map(|r| r.uid));
+            }
+        }
+        for entity in &defs.entities {
+            uids.push(entity.uid);
+            uids.extend(entity.field_defs.iter().map(|f| f.uid));
+        }
+        uids.extend(defs.enums.iter().chain(&defs.external_enums).map(|e| e.uid));
+        uids.extend(defs.tilesets.iter().map(|t| t.uid));
+        uids.extend(defs.level_fields.iter().map(|f| f.uid));
+        uids
+    }
+
     pub fn get_level(&self, uid: i64) -> Option<&Level> {
         for level in &self.levels {
             if level.uid == uid {
@@ -58,6 +119,29 @@ impl Project {
     }
 }
 
+impl Type {
+    /// The layer type name, as found in `__type` fields.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Type::AutoLayer => "AutoLayer",
+            Type::Entities => "Entities",
+            Type::IntGrid => "IntGrid",
+            Type::Tiles => "Tiles",
+        }
+    }
+
+    /// The layer type named `name` in `__type` fields, if any.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "AutoLayer" => Some(Type::AutoLayer),
+            "Entities" => Some(Type::Entities),
+            "IntGrid" => Some(Type::IntGrid),
+            "Tiles" => Some(Type::Tiles),
+            _ => None,
+        }
+    }
+}
+
 impl Level {
     pub fn new<P: AsRef<Path>>(f: P) -> Self {
         let file = File::open(f).expect("level file not found");
@@ -66,51 +150,264 @@ impl Level {
     }
 }
 
-impl Clone for EntityInstanceTile {
-    fn clone(&self) -> Self {
+impl LayerInstance {
+    /// Creates an empty instance of the layer `def` in `level`, with all derived `__` fields filled in.
+    pub fn new(project: &Project, def: &LayerDefinition, level: &Level) -> Self {
+        let c_wid = (level.px_wid + def.grid_size - 1) / def.grid_size;
+        let c_hei = (level.px_hei + def.grid_size - 1) / def.grid_size;
+        let tileset_def_uid = def.tileset_def_uid.or(def.auto_tileset_def_uid);
         Self {
-            src_rect: self.src_rect.clone(),
-            tileset_uid: self.tileset_uid,
+            c_hei,
+            c_wid,
+            grid_size: def.grid_size,
+            identifier: def.identifier.clone(),
+            opacity: def.display_opacity,
+            px_total_offset_x: def.px_offset_x,
+            px_total_offset_y: def.px_offset_y,
+            tileset_def_uid,
+            tileset_rel_path: tileset_def_uid
+                .and_then(|uid| project.defs.tilesets.iter().find(|t| t.uid == uid))
+                .map(|t| t.rel_path.clone()),
+            layer_instance_type: def.purple_type.name().into(),
+            int_grid_csv: match def.purple_type {
+                Type::IntGrid => vec![0; (c_wid * c_hei) as usize],
+                _ => Vec::new(),
+            },
+            layer_def_uid: def.uid,
+            level_id: level.uid,
+            ..Default::default()
         }
     }
 }
 
-impl Clone for EntityInstance {
-    fn clone(&self) -> Self {
+impl EntityInstance {
+    /// Creates an instance of the entity `def` in `layer`, with its pivot at (`x`, `y`) pixels and every field set
+    /// to its default value.
+    pub fn new(project: &Project, def: &EntityDefinition, layer: &LayerInstance, x: i64, y: i64) -> Self {
+        let tile = match (def.tileset_id, def.tile_id) {
+            (Some(tileset_uid), Some(tile_id)) => {
+                project
+                    .defs
+                    .tilesets
+                    .iter()
+                    .find(|t| t.uid == tileset_uid)
+                    .map(|tileset| {
+                        let [x, y] = tileset.tile_src(tile_id);
+                        let grid = tileset.tile_grid_size;
+                        EntityInstanceTile {
+                            src_rect: vec![x, y, grid, grid],
+                            tileset_uid,
+                            unknown_fields: serde_json::Map::new(),
+                        }
+                    })
+            }
+            _ => None,
+        };
         Self {
-            grid: self.grid.clone(),
-            identifier: self.identifier.clone(),
-            pivot: self.pivot.clone(),
-            tile: self.tile.clone(),
-            def_uid: self.def_uid,
-            field_instances: self.field_instances.clone(),
-            height: self.height,
-            px: self.px.clone(),
-            width: self.width,
+            grid: vec![x / layer.grid_size, y / layer.grid_size],
+            identifier: def.identifier.clone(),
+            pivot: vec![def.pivot_x, def.pivot_y],
+            tile,
+            def_uid: def.uid,
+            field_instances: def.field_defs.iter().map(FieldInstance::new).collect(),
+            height: def.height,
+            px: vec![x, y],
+            width: def.width,
+            ..Default::default()
         }
     }
+
+    /// Pixel bounds of the entity in level space, computed from its pivot. Layer offsets are not included.
+    pub fn bounds(&self) -> Rect {
+        let (pivot_x, pivot_y) = match self.pivot[..] {
+            [x, y] => (x, y),
+            _ => (0., 0.),
+        };
+        Rect::new(
+            self.px[0] - (pivot_x * self.width as f64) as i64,
+            self.px[1] - (pivot_y * self.height as f64) as i64,
+            self.width,
+            self.height,
+        )
+    }
 }
 
-impl Clone for FieldInstance {
-    fn clone(&self) -> Self {
-        Self {
-            identifier: self.identifier.clone(),
-            field_instance_type: self.field_instance_type.clone(),
-            value: self.value.clone(),
-            def_uid: self.def_uid,
-            real_editor_values: self.real_editor_values.clone(),
+impl TilesetDefinition {
+    /// Number of tiles in the tileset.
+    pub fn tile_count(&self) -> i64 {
+        self.c_wid * self.c_hei
+    }
+
+    /// Pixel rectangle of tile `tile_id` in the tileset image, skipping the padding around the image and the
+    /// spacing between tiles.
+    pub fn tile_rect(&self, tile_id: i64) -> Rect {
+        let step = self.tile_grid_size + self.spacing;
+        let c_wid = self.c_wid.max(1);
+        Rect::new(
+            self.padding + (tile_id % c_wid) * step,
+            self.padding + (tile_id / c_wid) * step,
+            self.tile_grid_size,
+            self.tile_grid_size,
+        )
+    }
+
+    /// Id of the tile covering pixel `(x, y)` of the tileset image, or `None` if the pixel is in the padding, in
+    /// the spacing between tiles, or outside the tile grid.
+    pub fn tile_id_at(&self, x: i64, y: i64) -> Option<i64> {
+        let step = self.tile_grid_size + self.spacing;
+        if step <= 0 || x < self.padding || y < self.padding {
+            return None;
         }
+        let (cx, cy) = ((x - self.padding) / step, (y - self.padding) / step);
+        if cx >= self.c_wid || cy >= self.c_hei {
+            return None;
+        }
+        let tile_id = cx + cy * self.c_wid;
+        if self.tile_rect(tile_id).contains(x, y) {
+            Some(tile_id)
+        } else {
+            None
+        }
+    }
+
+    /// Texture coordinates of tile `tile_id`, as `[min_x, min_y, max_x, max_y]` normalized to the image size.
+    pub fn tile_uv(&self, tile_id: i64) -> [f32; 4] {
+        let rect = self.tile_rect(tile_id);
+        let (width, height) = (self.px_wid.max(1) as f32, self.px_hei.max(1) as f32);
+        [
+            rect.x as f32 / width,
+            rect.y as f32 / height,
+            rect.right() as f32 / width,
+            rect.bottom() as f32 / height,
+        ]
+    }
+
+    /// Top-left pixel of tile `tile_id` in the tileset image.
+    pub(crate) fn tile_src(&self, tile_id: i64) -> [i64; 2] {
+        let rect = self.tile_rect(tile_id);
+        [rect.x, rect.y]
     }
 }
 
-impl Clone for TileInstance {
-    fn clone(&self) -> Self {
-        Self {
-            d: self.d.clone(),
-            f: self.f.clone(),
-            px: self.px.clone(),
-            src: self.src.clone(),
-            t: self.t.clone(),
-        }
+impl FieldInstance {
+    /// Creates an instance of the field `def` holding its default value, as the editor does for new levels and
+    /// entities.
+    pub fn new(def: &FieldDefinition) -> Self {
+        let default = def
+            .default_override
+            .as_ref()
+            .and_then(|d| d.get("params"))
+            .and_then(|p| p.get(0))
+            .cloned();
+        let value = match (default, def.field_definition_type.as_str()) {
+            _ if def.is_array => Value::Array(Vec::new()),
+            // Colors are stored as integers in editor values.
+            (Some(Value::Number(n)), "Color") => format!("#{:06X}", n.as_i64().unwrap_or(0)).into(),
+            (Some(value), _) => value,
+            (None, _) if def.can_be_null => Value::Null,
+            (None, "Int") => 0.into(),
+            (None, "Float") => 0.0.into(),
+            (None, "Bool") => false.into(),
+            (None, "Color") => "#000000".into(),
+            (None, "String") | (None, "Multilines") => "".into(),
+            (None, _) => Value::Null,
+        };
+
+        let mut field = Self {
+            identifier: def.identifier.clone(),
+            field_instance_type: def.field_definition_type.clone(),
+            def_uid: def.uid,
+            ..Default::default()
+        };
+        field.set_value(value);
+        field
+    }
+
+    /// Sets the value, along with the editor's own representation of it in `realEditorValues`, which is what the
+    /// editor actually reads back.
+    pub fn set_value(&mut self, value: Value) {
+        let type_name = self.field_instance_type.as_str();
+        let item_type = type_name.strip_prefix("Array<").and_then(|t| t.strip_suffix('>'));
+        self.real_editor_values = match (item_type, &value) {
+            (Some(item_type), Value::Array(values)) => values.iter().map(|v| editor_value(item_type, v)).collect(),
+            _ => vec![editor_value(type_name, &value)],
+        };
+        self.value = Some(value);
+    }
+}
+
+fn editor_value(type_name: &str, value: &Value) -> Option<Value> {
+    let (id, param) = match (type_name, value) {
+        (_, Value::Null) => return None,
+        ("Int", v) => ("V_Int", v.clone()),
+        ("Float", v) => ("V_Float", v.clone()),
+        ("Bool", v) => ("V_Bool", v.clone()),
+        ("Color", Value::String(s)) => (
+            "V_Int",
+            i64::from_str_radix(s.trim_start_matches('#'), 16).unwrap_or(0).into(),
+        ),
+        (_, v) => ("V_String", v.clone()),
+    };
+    Some(json!({"id": id, "params": [param]}))
+}
+
+/// Path of `to` relative to the directory `from_dir`, falling back to the paths as given when they can't be
+/// canonicalized.
+pub(crate) fn relative_path(from_dir: &Path, to: &Path) -> PathBuf {
+    let from = from_dir.canonicalize().unwrap_or_else(|_| from_dir.to_path_buf());
+    let to = to.canonicalize().unwrap_or_else(|_| to.to_path_buf());
+
+    let common = from
+        .components()
+        .zip(to.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut rel = PathBuf::new();
+    for _ in from.components().skip(common) {
+        rel.push("..");
+    }
+    for component in to.components().skip(common) {
+        rel.push(component);
+    }
+    rel
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tile_geometry_with_padding_and_spacing() {
+        // 3x3 tiles of 16 px, 1 px around the image and 2 px between tiles.
+        let tileset = TilesetDefinition {
+            c_wid: 3,
+            c_hei: 3,
+            padding: 1,
+            spacing: 2,
+            px_wid: 54,
+            px_hei: 54,
+            tile_grid_size: 16,
+            ..Default::default()
+        };
+        assert_eq!(tileset.tile_count(), 9);
+        assert_eq!(tileset.tile_rect(0), Rect::new(1, 1, 16, 16));
+        assert_eq!(tileset.tile_rect(4), Rect::new(19, 19, 16, 16));
+        assert_eq!(tileset.tile_rect(8), Rect::new(37, 37, 16, 16));
+        assert_eq!(tileset.tile_src(5), [37, 19]);
+        assert_eq!(tileset.tile_uv(4), [19. / 54., 19. / 54., 35. / 54., 35. / 54.]);
+
+        // Inside tiles, including their edges.
+        assert_eq!(tileset.tile_id_at(1, 1), Some(0));
+        assert_eq!(tileset.tile_id_at(20, 25), Some(4));
+        assert_eq!(tileset.tile_id_at(52, 34), Some(5));
+        // In the spacing between tiles.
+        assert_eq!(tileset.tile_id_at(17, 5), None);
+        assert_eq!(tileset.tile_id_at(5, 36), None);
+        // In the padding around the image.
+        assert_eq!(tileset.tile_id_at(0, 5), None);
+        assert_eq!(tileset.tile_id_at(5, 53), None);
+        // Outside of the image.
+        assert_eq!(tileset.tile_id_at(-1, 5), None);
+        assert_eq!(tileset.tile_id_at(60, 5), None);
     }
 }
@@ -0,0 +1,769 @@
+//! Three-way merging of projects, so that designers editing different levels or different parts of a level don't
+//! end up with unresolvable conflicts in version control.
+
+use crate::{diff::match_entities, *};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+/// A part of the project that was changed differently on both sides. The merged result keeps our version of it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub level: Option<String>,
+    pub layer: Option<String>,
+    pub what: String,
+}
+
+impl Conflict {
+    fn new(level: Option<&str>, layer: Option<&str>, what: String) -> Self {
+        Self {
+            level: level.map(Into::into),
+            layer: layer.map(Into::into),
+            what,
+        }
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(level) = &self.level {
+            write!(f, "level `{}`, ", level)?;
+        }
+        if let Some(layer) = &self.layer {
+            write!(f, "layer `{}`, ", layer)?;
+        }
+        write!(f, "{} changed on both sides", self.what)
+    }
+}
+
+/// The outcome of a merge: the merged value, along with every conflict that had to be resolved in favour of ours.
+#[derive(Clone, Debug)]
+pub struct Merged<T> {
+    pub merged: T,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl<T> Merged<T> {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Merges the changes from `base` to `theirs` into `ours`.
+///
+/// Definitions and levels are matched by uid and merged as a whole, except levels changed on both sides, which are
+/// merged per field, per layer cell and per entity. Entities are paired up the same way [`diff`] does. Uids that
+/// both sides allocated independently are re-allocated in their version before merging.
+///
+/// Auto-layer tiles are never reported as conflicting: they are derived data the editor rebuilds on save.
+pub fn merge(base: &Project, ours: &Project, theirs: &Project) -> Merged<Project> {
+    let mut theirs = theirs.clone();
+    reallocate_uids(base, ours, &mut theirs);
+
+    let mut conflicts = Vec::new();
+    let mut merged = ours.clone();
+    merged.next_uid = ours.next_uid.max(theirs.next_uid);
+
+    let (b, o, t) = (settings(base), settings(ours), settings(&theirs));
+    if o == b && t != b {
+        merged = Project {
+            defs: merged.defs,
+            levels: merged.levels,
+            next_uid: merged.next_uid,
+            ..t
+        };
+    } else if o != b && t != b && o != t {
+        conflicts.push(Conflict::new(None, None, "project settings".into()));
+    }
+
+    let defs = &mut merged.defs;
+    let (b, o, t) = (&base.defs, &ours.defs, &theirs.defs);
+    defs.layers = merge_defs(
+        &b.layers,
+        &o.layers,
+        &t.layers,
+        |d| (d.uid, &d.identifier),
+        &mut conflicts,
+    );
+    defs.entities = merge_defs(
+        &b.entities,
+        &o.entities,
+        &t.entities,
+        |d| (d.uid, &d.identifier),
+        &mut conflicts,
+    );
+    defs.enums = merge_defs(&b.enums, &o.enums, &t.enums, |d| (d.uid, &d.identifier), &mut conflicts);
+    defs.external_enums = merge_defs(
+        &b.external_enums,
+        &o.external_enums,
+        &t.external_enums,
+        |d| (d.uid, &d.identifier),
+        &mut conflicts,
+    );
+    defs.tilesets = merge_defs(
+        &b.tilesets,
+        &o.tilesets,
+        &t.tilesets,
+        |d| (d.uid, &d.identifier),
+        &mut conflicts,
+    );
+    defs.level_fields = merge_defs(
+        &b.level_fields,
+        &o.level_fields,
+        &t.level_fields,
+        |d| (d.uid, &d.identifier),
+        &mut conflicts,
+    );
+
+    let by_uid = |l: &Level| l.uid;
+    let (b, o, t) = (&base.levels, &ours.levels, &theirs.levels);
+    merged.levels = merge_keyed(
+        &keyed(b, by_uid),
+        &keyed(o, by_uid),
+        &keyed(t, by_uid),
+        |b, o, t| match (b, o, t) {
+            (Some(b), Some(o), Some(t)) => {
+                let level = merge_level(b, o, t);
+                conflicts.extend(level.conflicts);
+                Some(level.merged)
+            }
+            _ => {
+                let level = o.or(t).map(|l| l.identifier.as_str());
+                conflicts.push(Conflict::new(level, None, "deleted level".into()));
+                o.cloned()
+            }
+        },
+    );
+
+    Merged { merged, conflicts }
+}
+
+/// A copy of the project settings, with definitions and levels left out without cloning them.
+fn settings(project: &Project) -> Project {
+    let Project {
+        backup_limit,
+        backup_on_save,
+        bg_color,
+        default_grid_size,
+        default_level_bg_color,
+        default_level_height,
+        default_level_width,
+        default_pivot_x,
+        default_pivot_y,
+        defs: _,
+        export_png,
+        export_tiled,
+        external_levels,
+        flags,
+        image_export_mode,
+        json_version,
+        level_name_pattern,
+        levels: _,
+        minify_json,
+        next_uid: _,
+        png_file_pattern,
+        world_grid_height,
+        world_grid_width,
+        world_layout,
+        unknown_fields,
+    } = project;
+    Project {
+        backup_limit: *backup_limit,
+        backup_on_save: *backup_on_save,
+        bg_color: *bg_color,
+        default_grid_size: *default_grid_size,
+        default_level_bg_color: *default_level_bg_color,
+        default_level_height: *default_level_height,
+        default_level_width: *default_level_width,
+        default_pivot_x: *default_pivot_x,
+        default_pivot_y: *default_pivot_y,
+        defs: Definitions::default(),
+        export_png: *export_png,
+        export_tiled: *export_tiled,
+        external_levels: *external_levels,
+        flags: flags.clone(),
+        image_export_mode: image_export_mode.clone(),
+        json_version: json_version.clone(),
+        level_name_pattern: level_name_pattern.clone(),
+        levels: Vec::new(),
+        minify_json: *minify_json,
+        next_uid: 0,
+        png_file_pattern: png_file_pattern.clone(),
+        world_grid_height: *world_grid_height,
+        world_grid_width: *world_grid_width,
+        world_layout: world_layout.clone(),
+        unknown_fields: unknown_fields.clone(),
+    }
+}
+
+/// Merges two versions of a single level, as found in external level files.
+pub fn merge_level(base: &Level, ours: &Level, theirs: &Level) -> Merged<Level> {
+    let mut conflicts = Vec::new();
+    let level = Some(ours.identifier.as_str());
+
+    let properties = |l: &Level| Level {
+        field_instances: Vec::new(),
+        layer_instances: None,
+        ..l.clone()
+    };
+    let (b, o, t) = (properties(base), properties(ours), properties(theirs));
+    let mut merged = if o == b {
+        t
+    } else {
+        if t != b && t != o {
+            conflicts.push(Conflict::new(level, None, "level properties".into()));
+        }
+        o
+    };
+
+    merged.field_instances = merge_fields(
+        &base.field_instances,
+        &ours.field_instances,
+        &theirs.field_instances,
+        |field| Conflict::new(level, None, format!("field `{}`", field)),
+        &mut conflicts,
+    );
+
+    // Levels saved in a separate file only have their layers there.
+    merged.layer_instances = match (&base.layer_instances, &ours.layer_instances, &theirs.layer_instances) {
+        (Some(b), Some(o), Some(t)) => Some(merge_keyed(
+            &keyed(b, |l| l.layer_def_uid),
+            &keyed(o, |l| l.layer_def_uid),
+            &keyed(t, |l| l.layer_def_uid),
+            |b, o, t| match (b, o, t) {
+                (Some(b), Some(o), Some(t)) => Some(merge_layer(level, b, o, t, &mut conflicts)),
+                _ => o.cloned(),
+            },
+        )),
+        _ => ours.layer_instances.clone(),
+    };
+
+    Merged { merged, conflicts }
+}
+
+fn merge_layer(
+    level: Option<&str>,
+    base: &LayerInstance,
+    ours: &LayerInstance,
+    theirs: &LayerInstance,
+    conflicts: &mut Vec<Conflict>,
+) -> LayerInstance {
+    let layer = Some(ours.identifier.as_str());
+
+    let properties = |l: &LayerInstance| LayerInstance {
+        auto_layer_tiles: Vec::new(),
+        entity_instances: Vec::new(),
+        grid_tiles: Vec::new(),
+        int_grid: None,
+        int_grid_csv: Vec::new(),
+        ..l.clone()
+    };
+    let (b, o, t) = (properties(base), properties(ours), properties(theirs));
+    if (o.c_wid, o.c_hei) != (b.c_wid, b.c_hei) || (t.c_wid, t.c_hei) != (b.c_wid, b.c_hei) {
+        // Cells can't be matched up across a resize, so the layer is taken as a whole.
+        if ours == base {
+            return theirs.clone();
+        }
+        if theirs != base && theirs != ours {
+            conflicts.push(Conflict::new(level, layer, "resized layer".into()));
+        }
+        return ours.clone();
+    }
+    let mut merged = if o == b {
+        t
+    } else {
+        if t != b && t != o {
+            conflicts.push(Conflict::new(level, layer, "layer properties".into()));
+        }
+        o
+    };
+
+    let mut cells = 0;
+    merged.int_grid_csv = ours.int_grid_csv.clone();
+    for (i, value) in merged.int_grid_csv.iter_mut().enumerate() {
+        let b = base.int_grid_csv.get(i);
+        let t = theirs.int_grid_csv.get(i);
+        if Some(&*value) == b {
+            if let Some(&t) = t {
+                *value = t;
+            }
+        } else if t != b && t != Some(&*value) {
+            cells += 1;
+        }
+    }
+
+    let by_cell = |l: &LayerInstance| {
+        let mut cells: BTreeMap<_, Vec<TileInstance>> = BTreeMap::new();
+        for tile in &l.grid_tiles {
+            cells.entry((tile.px[1], tile.px[0])).or_default().push(tile.clone());
+        }
+        cells.into_iter().collect::<Vec<_>>()
+    };
+    let (b, o, t) = (by_cell(base), by_cell(ours), by_cell(theirs));
+    fn as_keyed(cells: &[((i64, i64), Vec<TileInstance>)]) -> Vec<((i64, i64), &Vec<TileInstance>)> {
+        cells.iter().map(|(k, v)| (*k, v)).collect()
+    }
+    merged.grid_tiles = merge_keyed(&as_keyed(&b), &as_keyed(&o), &as_keyed(&t), |_, o, _| {
+        cells += 1;
+        o.cloned()
+    })
+    .into_iter()
+    .flatten()
+    .collect();
+    if cells > 0 {
+        let what = format!("{} cell{}", cells, if cells == 1 { "" } else { "s" });
+        conflicts.push(Conflict::new(level, layer, what));
+    }
+
+    merged.entity_instances = merge_entities(
+        &base.entity_instances,
+        &ours.entity_instances,
+        &theirs.entity_instances,
+        |e| {
+            Conflict::new(
+                level,
+                layer,
+                format!("entity `{}` at ({}, {})", e.identifier, e.px[0], e.px[1]),
+            )
+        },
+        conflicts,
+    );
+
+    merged.auto_layer_tiles = if ours.auto_layer_tiles == base.auto_layer_tiles {
+        theirs.auto_layer_tiles.clone()
+    } else {
+        ours.auto_layer_tiles.clone()
+    };
+    merged.int_grid = ours.int_grid.clone();
+
+    merged
+}
+
+/// Entities are keyed by the base entity they were matched with, or by their index on their own side if added.
+#[derive(Copy, Clone, PartialEq)]
+enum EntityKey {
+    Base(usize),
+    Ours(usize),
+    Theirs(usize),
+}
+
+fn merge_entities(
+    base: &[EntityInstance],
+    ours: &[EntityInstance],
+    theirs: &[EntityInstance],
+    conflict: impl Fn(&EntityInstance) -> Conflict,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<EntityInstance> {
+    fn side<'a>(
+        base: &[EntityInstance],
+        side: &'a [EntityInstance],
+        added: fn(usize) -> EntityKey,
+    ) -> Vec<(EntityKey, &'a EntityInstance)> {
+        let mut keyed = Vec::new();
+        for pair in match_entities(base, side) {
+            match pair {
+                (Some(b), Some(s)) => keyed.push((EntityKey::Base(b), &side[s])),
+                (None, Some(s)) => keyed.push((added(s), &side[s])),
+                _ => (),
+            }
+        }
+        keyed
+    }
+    let base_keyed = base
+        .iter()
+        .enumerate()
+        .map(|(i, e)| (EntityKey::Base(i), e))
+        .collect::<Vec<_>>();
+
+    merge_keyed(
+        &base_keyed,
+        &side(base, ours, EntityKey::Ours),
+        &side(base, theirs, EntityKey::Theirs),
+        |b, o, t| {
+            if let Some(e) = o.or(t).or(b) {
+                conflicts.push(conflict(e));
+            }
+            o.cloned()
+        },
+    )
+}
+
+fn merge_fields(
+    base: &[FieldInstance],
+    ours: &[FieldInstance],
+    theirs: &[FieldInstance],
+    conflict: impl Fn(&str) -> Conflict,
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<FieldInstance> {
+    merge_keyed(
+        &keyed(base, |f| f.def_uid),
+        &keyed(ours, |f| f.def_uid),
+        &keyed(theirs, |f| f.def_uid),
+        |b, o, t| {
+            if let Some(f) = o.or(t).or(b) {
+                conflicts.push(conflict(&f.identifier));
+            }
+            o.cloned()
+        },
+    )
+}
+
+fn merge_defs<T: Clone + PartialEq>(
+    base: &[T],
+    ours: &[T],
+    theirs: &[T],
+    key: impl Fn(&T) -> (i64, &String),
+    conflicts: &mut Vec<Conflict>,
+) -> Vec<T> {
+    let uid = |d: &T| key(d).0;
+    merge_keyed(&keyed(base, uid), &keyed(ours, uid), &keyed(theirs, uid), |b, o, t| {
+        if let Some(d) = o.or(t).or(b) {
+            conflicts.push(Conflict::new(None, None, format!("definition `{}`", key(d).1)));
+        }
+        o.cloned()
+    })
+}
+
+fn keyed<T, K>(items: &[T], key: impl Fn(&T) -> K) -> Vec<(K, &T)> {
+    items.iter().map(|i| (key(i), i)).collect()
+}
+
+/// Three-way merge of keyed items. Items only changed on one side take that change; items changed on both sides
+/// are handed to `resolve`. Our order is kept, with items only found in theirs appended.
+fn merge_keyed<K: Copy + PartialEq, T: Clone + PartialEq>(
+    base: &[(K, &T)],
+    ours: &[(K, &T)],
+    theirs: &[(K, &T)],
+    mut resolve: impl FnMut(Option<&T>, Option<&T>, Option<&T>) -> Option<T>,
+) -> Vec<T> {
+    fn find<'a, K: PartialEq, T>(items: &[(K, &'a T)], key: K) -> Option<&'a T> {
+        items.iter().find(|(k, _)| *k == key).map(|&(_, item)| item)
+    }
+
+    let mut keys: Vec<K> = Vec::new();
+    for &(key, _) in ours.iter().chain(theirs).chain(base) {
+        if !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+
+    let mut merged = Vec::new();
+    for key in keys {
+        let (b, o, t) = (find(base, key), find(ours, key), find(theirs, key));
+        let item = if o == t || t == b {
+            o.cloned()
+        } else if o == b {
+            t.cloned()
+        } else {
+            resolve(b, o, t)
+        };
+        merged.extend(item);
+    }
+    merged
+}
+
+/// Gives new uids to everything `theirs` created with a uid that `ours` also used for something new, and updates
+/// every reference to them.
+fn reallocate_uids(base: &Project, ours: &Project, theirs: &mut Project) {
+    let base_uids = base.defined_uids().into_iter().collect::<HashSet<_>>();
+    let ours_uids = ours.defined_uids().into_iter().collect::<HashSet<_>>();
+
+    let mut next_uid = ours.next_uid.max(theirs.next_uid);
+    let mut remap = HashMap::new();
+    let mut theirs_uids = theirs.defined_uids();
+    theirs_uids.sort_unstable();
+    theirs_uids.dedup();
+    for uid in theirs_uids {
+        if !base_uids.contains(&uid) && ours_uids.contains(&uid) {
+            remap.insert(uid, next_uid);
+            next_uid += 1;
+        }
+    }
+    if remap.is_empty() {
+        return;
+    }
+
+    for_each_uid(theirs, |uid| {
+        if let Some(&new) = remap.get(uid) {
+            *uid = new;
+        }
+    });
+    theirs.next_uid = next_uid;
+}
+
+/// Calls `f` on every uid in the project, both definitions and references to them.
+fn for_each_uid(project: &mut Project, mut f: impl FnMut(&mut i64)) {
+    let defs = &mut project.defs;
+    for layer in &mut defs.layers {
+        f(&mut layer.uid);
+        layer.auto_source_layer_def_uid.iter_mut().for_each(&mut f);
+        layer.auto_tileset_def_uid.iter_mut().for_each(&mut f);
+        layer.tileset_def_uid.iter_mut().for_each(&mut f);
+        for group in &mut layer.auto_rule_groups {
+            f(&mut group.uid);
+            for rule in &mut group.rules {
+                f(&mut rule.uid);
+            }
+        }
+    }
+    for entity in &mut defs.entities {
+        f(&mut entity.uid);
+        entity.tileset_id.iter_mut().for_each(&mut f);
+        for field in &mut entity.field_defs {
+            f(&mut field.uid);
+            for_enum_uid(&mut field.purple_type, &mut f);
+        }
+    }
+    for e in defs.enums.iter_mut().chain(&mut defs.external_enums) {
+        f(&mut e.uid);
+        e.icon_tileset_uid.iter_mut().for_each(&mut f);
+    }
+    for tileset in &mut defs.tilesets {
+        f(&mut tileset.uid);
+        tileset.tags_source_enum_uid.iter_mut().for_each(&mut f);
+    }
+    for field in &mut defs.level_fields {
+        f(&mut field.uid);
+        for_enum_uid(&mut field.purple_type, &mut f);
+    }
+
+    for level in &mut project.levels {
+        f(&mut level.uid);
+        for neighbour in &mut level.neighbours {
+            f(&mut neighbour.level_uid);
+        }
+        for field in &mut level.field_instances {
+            f(&mut field.def_uid);
+        }
+        for layer in level.layer_instances.iter_mut().flatten() {
+            f(&mut layer.layer_def_uid);
+            f(&mut layer.level_id);
+            layer.tileset_def_uid.iter_mut().for_each(&mut f);
+            layer.override_tileset_uid.iter_mut().for_each(&mut f);
+            layer.optional_rules.iter_mut().for_each(&mut f);
+            // The first element of auto-layer tile data is the rule that produced it.
+            for tile in &mut layer.auto_layer_tiles {
+                tile.d.first_mut().into_iter().for_each(&mut f);
+            }
+            for entity in &mut layer.entity_instances {
+                f(&mut entity.def_uid);
+                if let Some(tile) = &mut entity.tile {
+                    f(&mut tile.tileset_uid);
+                }
+                for field in &mut entity.field_instances {
+                    f(&mut field.def_uid);
+                }
+            }
+        }
+    }
+}
+
+/// Calls `f` on the enum uid of an enum field type, written as `F_Enum(<uid>)`, or as
+/// `{"id": "F_Enum", "params": [<uid>]}` by older editor versions.
+fn for_enum_uid(ty: &mut Option<serde_json::Value>, f: &mut impl FnMut(&mut i64)) {
+    use serde_json::Value;
+
+    match ty {
+        Some(Value::String(ty)) => {
+            let uid = ty.strip_prefix("F_Enum(").and_then(|uid| uid.strip_suffix(')'));
+            if let Some(mut uid) = uid.and_then(|uid| uid.parse().ok()) {
+                f(&mut uid);
+                *ty = format!("F_Enum({})", uid);
+            }
+        }
+        Some(Value::Object(ty)) if ty.get("id").and_then(Value::as_str) == Some("F_Enum") => {
+            let param = ty.get_mut("params").and_then(|params| params.get_mut(0));
+            if let Some(param) = param {
+                if let Some(mut uid) = param.as_i64() {
+                    f(&mut uid);
+                    *param = uid.into();
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> Project {
+        let mut builder = ProjectBuilder::new();
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Walls".into(),
+            purple_type: Type::IntGrid,
+            grid_size: 16,
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Player".into(),
+            width: 16,
+            height: 16,
+            ..Default::default()
+        });
+        let mut level = builder.level("Start", 64, 64);
+        level.entity("Entities", "Player", 16, 16);
+        level.finish();
+        builder.build()
+    }
+
+    fn layer_mut<'a>(project: &'a mut Project, identifier: &str) -> &'a mut LayerInstance {
+        project.levels[0]
+            .layer_instances
+            .iter_mut()
+            .flatten()
+            .find(|l| l.identifier == identifier)
+            .unwrap()
+    }
+
+    #[test]
+    fn disjoint_edits() {
+        let base = project();
+        let mut ours = base.clone();
+        layer_mut(&mut ours, "Walls").int_grid_csv[0] = 1;
+        let mut theirs = base.clone();
+        layer_mut(&mut theirs, "Walls").int_grid_csv[5] = 1;
+        layer_mut(&mut theirs, "Entities").entity_instances[0].px = vec![32, 16];
+        theirs.bg_color = Color::rgb(0x10, 0x20, 0x30);
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.is_clean(), "{:?}", result.conflicts);
+        let mut merged = result.merged;
+        assert_eq!(merged.bg_color, theirs.bg_color);
+        assert_eq!(&layer_mut(&mut merged, "Walls").int_grid_csv[..6], &[1, 0, 0, 0, 0, 1]);
+        assert_eq!(layer_mut(&mut merged, "Entities").entity_instances[0].px, vec![32, 16]);
+    }
+
+    #[test]
+    fn conflicting_edits() {
+        let base = project();
+        let mut ours = base.clone();
+        layer_mut(&mut ours, "Walls").int_grid_csv[0] = 1;
+        ours.levels[0].world_x = 64;
+        ours.default_grid_size = 8;
+        let mut theirs = base.clone();
+        layer_mut(&mut theirs, "Walls").int_grid_csv[0] = 2;
+        theirs.levels[0].world_x = -64;
+        theirs.default_grid_size = 32;
+
+        let result = merge(&base, &ours, &theirs);
+        assert_eq!(
+            result.conflicts,
+            vec![
+                Conflict::new(None, None, "project settings".into()),
+                Conflict::new(Some("Start"), None, "level properties".into()),
+                Conflict::new(Some("Start"), Some("Walls"), "1 cell".into()),
+            ]
+        );
+        let mut merged = result.merged;
+        assert_eq!(merged.default_grid_size, 8);
+        assert_eq!(merged.levels[0].world_x, 64);
+        assert_eq!(layer_mut(&mut merged, "Walls").int_grid_csv[0], 1);
+    }
+
+    #[test]
+    fn uid_collisions() {
+        let base = project();
+        let mut ours = ProjectBuilder::from_project(base.clone());
+        let ours_level = ours.level("Ours", 64, 64).finish();
+        let ours = ours.build();
+
+        let mut theirs = ProjectBuilder::from_project(base.clone());
+        let chest = theirs.add_entity(EntityDefinition {
+            identifier: "Chest".into(),
+            width: 16,
+            height: 16,
+            ..Default::default()
+        });
+        let mut level = theirs.level("Theirs", 64, 64);
+        level.entity("Entities", "Chest", 32, 32);
+        level.finish();
+        let theirs = theirs.build();
+        assert_eq!(chest, ours_level);
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.is_clean(), "{:?}", result.conflicts);
+        let merged = result.merged;
+
+        let mut uids = merged.defined_uids();
+        let count = uids.len();
+        uids.sort_unstable();
+        uids.dedup();
+        assert_eq!(uids.len(), count, "duplicate uids in {:?}", uids);
+        assert!(uids.iter().all(|&uid| uid < merged.next_uid));
+
+        let chest = merged.defs.entities.iter().find(|d| d.identifier == "Chest").unwrap();
+        assert_ne!(chest.uid, ours_level);
+        let level = merged.levels.iter().find(|l| l.identifier == "Theirs").unwrap();
+        let entity = &level.layer_instances.as_ref().unwrap()[0].entity_instances[0];
+        assert_eq!(entity.def_uid, chest.uid);
+        assert_eq!(
+            merged.levels.iter().find(|l| l.identifier == "Ours").unwrap().uid,
+            ours_level
+        );
+    }
+
+    #[test]
+    fn enum_uid_collisions() {
+        let base = project();
+        let mut ours = ProjectBuilder::from_project(base.clone());
+        let ours_level = ours.level("Ours", 64, 64).finish();
+        let ours = ours.build();
+
+        let mut theirs = base.clone();
+        let enum_uid = theirs.allocate_uid();
+        assert_eq!(enum_uid, ours_level);
+        theirs.defs.enums.push(EnumDefinition {
+            external_file_checksum: None,
+            external_rel_path: None,
+            icon_tileset_uid: None,
+            identifier: "Item".into(),
+            uid: enum_uid,
+            values: Vec::new(),
+            unknown_fields: Default::default(),
+        });
+        let mut theirs = ProjectBuilder::from_project(theirs);
+        theirs.add_entity(EntityDefinition {
+            identifier: "Chest".into(),
+            width: 16,
+            height: 16,
+            field_defs: vec![FieldDefinition {
+                identifier: "item".into(),
+                field_definition_type: "LocalEnum.Item".into(),
+                purple_type: Some(format!("F_Enum({})", enum_uid).into()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        theirs.add_level_field(FieldDefinition {
+            identifier: "reward".into(),
+            field_definition_type: "LocalEnum.Item".into(),
+            purple_type: Some(serde_json::json!({"id": "F_Enum", "params": [enum_uid]})),
+            ..Default::default()
+        });
+        let theirs = theirs.build();
+
+        let result = merge(&base, &ours, &theirs);
+        assert!(result.is_clean(), "{:?}", result.conflicts);
+        let merged = result.merged;
+        let enum_uid = merged.defs.enums[0].uid;
+        assert_ne!(enum_uid, ours_level);
+        let chest = merged.defs.entities.iter().find(|d| d.identifier == "Chest").unwrap();
+        assert_eq!(
+            chest.field_defs[0].purple_type,
+            Some(format!("F_Enum({})", enum_uid).into())
+        );
+        assert_eq!(
+            merged.defs.level_fields[0].purple_type,
+            Some(serde_json::json!({"id": "F_Enum", "params": [enum_uid]}))
+        );
+    }
+}
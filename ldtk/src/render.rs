@@ -0,0 +1,453 @@
+use crate::{geom::Rect, ImageExportMode, LayerInstance, Level, Project, RenderMode};
+use image::{imageops, ImageResult, Rgba, RgbaImage};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+/// File pattern used when `pngFilePattern` is not set and the project exports one image per level.
+pub static DEFAULT_LEVEL_PNG_PATTERN: &str = "%level_name";
+/// File pattern used when `pngFilePattern` is not set and the project exports one image per layer.
+pub static DEFAULT_LAYER_PNG_PATTERN: &str = "%level_name--%layer_name";
+
+/// Something left out of a rendered level, which doesn't prevent rendering the rest.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Warning {
+    pub level: String,
+    pub message: String,
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "level `{}`: {}", self.level, self.message)
+    }
+}
+
+/// CPU renderer compositing levels into RGBA images, similar to the editor's image export.
+///
+/// Tileset images are loaded lazily, relative to `base_dir` (the directory holding the project file).
+pub struct Renderer<'a> {
+    project: &'a Project,
+    base_dir: PathBuf,
+    tilesets: HashMap<i64, RgbaImage>,
+    warnings: Vec<Warning>,
+}
+
+impl<'a> Renderer<'a> {
+    pub fn new<P: AsRef<Path>>(project: &'a Project, base_dir: P) -> Self {
+        Self {
+            project,
+            base_dir: base_dir.as_ref().to_path_buf(),
+            tilesets: HashMap::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// Renders every visible layer of `level`, bottom-most first, on top of the level background color and image.
+    ///
+    /// A background image that can't be loaded is left out instead of failing the render, and reported by
+    /// [`Renderer::take_warnings`].
+    pub fn render_level(&mut self, level: &Level) -> ImageResult<RgbaImage> {
+        let mut canvas =
+            RgbaImage::from_pixel(level.px_wid as u32, level.px_hei as u32, Rgba(level.bg_color.to_rgba()));
+        self.draw_background(&mut canvas, level);
+        if let Some(layers) = &level.layer_instances {
+            for layer in layers.iter().rev().filter(|l| l.visible) {
+                self.draw_layer(&mut canvas, layer)?;
+            }
+        }
+        Ok(canvas)
+    }
+
+    /// Returns the warnings of the renders since the last call.
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// Renders a single layer of `level` on a transparent canvas.
+    pub fn render_layer(&mut self, level: &Level, layer: &LayerInstance) -> ImageResult<RgbaImage> {
+        let mut canvas = RgbaImage::new(level.px_wid as u32, level.px_hei as u32);
+        self.draw_layer(&mut canvas, layer)?;
+        Ok(canvas)
+    }
+
+    fn draw_background(&mut self, canvas: &mut RgbaImage, level: &Level) {
+        let rel_path = match &level.bg_rel_path {
+            Some(rel_path) => rel_path,
+            None => return,
+        };
+        let image = match image::open(self.base_dir.join(rel_path)) {
+            Ok(image) => image.into_rgba8(),
+            Err(err) => {
+                self.warnings.push(Warning {
+                    level: level.identifier.clone(),
+                    message: format!("skipped background image `{}`: {}", rel_path, err),
+                });
+                return;
+            }
+        };
+        let background = match level.background(&self.base_dir, image.width() as f64, image.height() as f64) {
+            Some(background) => background,
+            None => return,
+        };
+
+        let [x, y, width, height] = background.crop;
+        let cropped = imageops::crop_imm(&image, x as u32, y as u32, width as u32, height as u32).to_image();
+        let [width, height] = background.size();
+        if width < 1. || height < 1. {
+            return;
+        }
+        let scaled = imageops::resize(
+            &cropped,
+            width.round() as u32,
+            height.round() as u32,
+            imageops::FilterType::Nearest,
+        );
+        let [x, y] = background.top_left;
+        imageops::overlay(canvas, &scaled, x as u32, y as u32);
+    }
+
+    fn draw_layer(&mut self, canvas: &mut RgbaImage, layer: &LayerInstance) -> ImageResult<()> {
+        match &layer.layer_instance_type[..] {
+            "Tiles" => self.draw_tiles(canvas, layer, false),
+            "AutoLayer" => self.draw_tiles(canvas, layer, true),
+            "IntGrid" if !layer.auto_layer_tiles.is_empty() => self.draw_tiles(canvas, layer, true),
+            "IntGrid" => {
+                self.draw_int_grid(canvas, layer);
+                Ok(())
+            }
+            "Entities" => self.draw_entities(canvas, layer),
+            _ => Ok(()),
+        }
+    }
+
+    fn draw_tiles(&mut self, canvas: &mut RgbaImage, layer: &LayerInstance, auto: bool) -> ImageResult<()> {
+        let uid = match layer.tileset_def_uid {
+            Some(uid) => uid,
+            None => return Ok(()),
+        };
+        let grid = self
+            .project
+            .defs
+            .tilesets
+            .iter()
+            .find(|t| t.uid == uid)
+            .map_or(layer.grid_size, |t| t.tile_grid_size);
+        let tileset = self.tileset(uid)?;
+
+        let tiles = if auto {
+            &layer.auto_layer_tiles
+        } else {
+            &layer.grid_tiles
+        };
+        for tile in tiles {
+            let src = Rect::new(tile.src[0], tile.src[1], grid, grid);
+            let dst = Rect::new(
+                tile.px[0] + layer.px_total_offset_x,
+                tile.px[1] + layer.px_total_offset_y,
+                grid,
+                grid,
+            );
+            blit(
+                canvas,
+                tileset,
+                src,
+                dst,
+                tile.f & 1 != 0,
+                tile.f & 2 != 0,
+                layer.opacity as f32,
+            );
+        }
+        Ok(())
+    }
+
+    fn draw_int_grid(&self, canvas: &mut RgbaImage, layer: &LayerInstance) {
+        let colors: HashMap<i64, Rgba<u8>> =
+            match self.project.defs.layers.iter().find(|l| l.uid == layer.layer_def_uid) {
+                Some(def) => def
+                    .int_grid_values
+                    .iter()
+                    .map(|v| (v.value, Rgba(v.color.to_rgba())))
+                    .collect(),
+                None => return,
+            };
+
+        for (idx, value) in layer.int_grid_csv.iter().enumerate() {
+            let color = match colors.get(value) {
+                Some(color) => *color,
+                None => continue,
+            };
+            let idx = idx as i64;
+            let cell = Rect::new(
+                idx % layer.c_wid * layer.grid_size + layer.px_total_offset_x,
+                idx / layer.c_wid * layer.grid_size + layer.px_total_offset_y,
+                layer.grid_size,
+                layer.grid_size,
+            );
+            fill_rect(canvas, cell, color, layer.opacity as f32);
+        }
+    }
+
+    fn draw_entities(&mut self, canvas: &mut RgbaImage, layer: &LayerInstance) -> ImageResult<()> {
+        let project = self.project;
+        for entity in &layer.entity_instances {
+            if let Some(tile) = &entity.tile {
+                self.tileset(tile.tileset_uid)?;
+            }
+        }
+
+        for entity in &layer.entity_instances {
+            let def = match project.defs.entities.iter().find(|e| e.uid == entity.def_uid) {
+                Some(def) => def,
+                None => continue,
+            };
+            let mut bounds = entity.bounds();
+            bounds.x += layer.px_total_offset_x;
+            bounds.y += layer.px_total_offset_y;
+            let color = Rgba(def.color.to_rgba());
+            let opacity = layer.opacity as f32;
+
+            match def.render_mode {
+                RenderMode::Rectangle => {
+                    if !def.hollow {
+                        fill_rect(canvas, bounds, color, opacity * def.fill_opacity as f32);
+                    }
+                    stroke_rect(canvas, bounds, color, opacity * def.line_opacity as f32);
+                }
+                RenderMode::Ellipse => draw_ellipse(
+                    canvas,
+                    bounds,
+                    color,
+                    if def.hollow {
+                        0.
+                    } else {
+                        opacity * def.fill_opacity as f32
+                    },
+                    opacity * def.line_opacity as f32,
+                ),
+                RenderMode::Cross => draw_cross(canvas, bounds, color, opacity * def.line_opacity as f32),
+                RenderMode::Tile => {
+                    for quad in entity.tile_quads(def) {
+                        let mut dst = quad.dst;
+                        dst.x += layer.px_total_offset_x;
+                        dst.y += layer.px_total_offset_y;
+                        blit(
+                            canvas,
+                            &self.tilesets[&quad.tileset_uid],
+                            quad.src,
+                            dst,
+                            false,
+                            false,
+                            opacity,
+                        );
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tileset(&mut self, uid: i64) -> ImageResult<&RgbaImage> {
+        if !self.tilesets.contains_key(&uid) {
+            let rel_path = match self.project.defs.tilesets.iter().find(|t| t.uid == uid) {
+                Some(tileset) => &tileset.rel_path,
+                None => {
+                    return Err(image::ImageError::IoError(std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("unknown tileset uid {}", uid),
+                    )))
+                }
+            };
+            let img = image::open(self.base_dir.join(rel_path))?.into_rgba8();
+            self.tilesets.insert(uid, img);
+        }
+        Ok(&self.tilesets[&uid])
+    }
+}
+
+/// Writes level images the way the editor does on save, honoring `imageExportMode` (or the deprecated
+/// `exportPng`, which meant one image per layer) and `pngFilePattern`.
+///
+/// Supported pattern variables are `%level_name`, `%level_idx`, `%layer_name` and `%layer_idx`.
+/// Returns the paths of all written files, and the warnings of [`Renderer::render_level`].
+pub fn export_pngs<P: AsRef<Path>, O: AsRef<Path>>(
+    project: &Project,
+    project_dir: P,
+    out_dir: O,
+) -> ImageResult<(Vec<PathBuf>, Vec<Warning>)> {
+    let per_layer = match project.image_export_mode {
+        ImageExportMode::OneImagePerLayer => true,
+        ImageExportMode::OneImagePerLevel => false,
+        ImageExportMode::None if project.export_png == Some(true) => true,
+        ImageExportMode::None => return Ok((Vec::new(), Vec::new())),
+    };
+    let pattern = project.png_file_pattern.as_deref().unwrap_or(if per_layer {
+        DEFAULT_LAYER_PNG_PATTERN
+    } else {
+        DEFAULT_LEVEL_PNG_PATTERN
+    });
+
+    std::fs::create_dir_all(&out_dir)?;
+    let mut renderer = Renderer::new(project, project_dir);
+    let mut written = Vec::new();
+    for (level_idx, level) in project.levels.iter().enumerate() {
+        if per_layer {
+            let layers = match &level.layer_instances {
+                Some(layers) => layers,
+                None => continue,
+            };
+            for (layer_idx, layer) in layers.iter().enumerate().filter(|(_, l)| l.visible) {
+                let path = out_dir
+                    .as_ref()
+                    .join(png_file_name(pattern, level, level_idx, Some((layer, layer_idx))));
+                renderer.render_layer(level, layer)?.save(&path)?;
+                written.push(path);
+            }
+        } else {
+            let path = out_dir.as_ref().join(png_file_name(pattern, level, level_idx, None));
+            renderer.render_level(level)?.save(&path)?;
+            written.push(path);
+        }
+    }
+    Ok((written, renderer.take_warnings()))
+}
+
+fn png_file_name(pattern: &str, level: &Level, level_idx: usize, layer: Option<(&LayerInstance, usize)>) -> String {
+    let mut name = pattern
+        .replace("%level_name", &level.identifier)
+        .replace("%level_idx", &format!("{:04}", level_idx));
+    if let Some((layer, layer_idx)) = layer {
+        name = name
+            .replace("%layer_name", &layer.identifier)
+            .replace("%layer_idx", &format!("{:02}", layer_idx));
+    }
+    name + ".png"
+}
+
+/// Source-over blending of `color` onto the canvas, scaled by `opacity`. Out of bounds pixels are ignored.
+fn blend(canvas: &mut RgbaImage, x: i64, y: i64, color: Rgba<u8>, opacity: f32) {
+    if x < 0 || y < 0 || x >= canvas.width() as i64 || y >= canvas.height() as i64 {
+        return;
+    }
+    let src_a = color[3] as f32 / 255. * opacity.clamp(0., 1.);
+    if src_a <= 0. {
+        return;
+    }
+    let dst = canvas.get_pixel_mut(x as u32, y as u32);
+    let dst_a = dst[3] as f32 / 255.;
+    let out_a = src_a + dst_a * (1. - src_a);
+    for c in 0..3 {
+        let v = (color[c] as f32 * src_a + dst[c] as f32 * dst_a * (1. - src_a)) / out_a;
+        dst[c] = v.round() as u8;
+    }
+    dst[3] = (out_a * 255.).round() as u8;
+}
+
+/// Copies `src` from `image` into `dst` on the canvas, scaling with nearest neighbour sampling if sizes differ.
+fn blit(canvas: &mut RgbaImage, image: &RgbaImage, src: Rect, dst: Rect, flip_x: bool, flip_y: bool, opacity: f32) {
+    if src.is_empty() || dst.is_empty() {
+        return;
+    }
+    for dy in 0..dst.height {
+        let mut v = dy * src.height / dst.height;
+        if flip_y {
+            v = src.height - 1 - v;
+        }
+        for dx in 0..dst.width {
+            let mut u = dx * src.width / dst.width;
+            if flip_x {
+                u = src.width - 1 - u;
+            }
+            let (sx, sy) = (src.x + u, src.y + v);
+            if sx < 0 || sy < 0 || sx >= image.width() as i64 || sy >= image.height() as i64 {
+                continue;
+            }
+            blend(
+                canvas,
+                dst.x + dx,
+                dst.y + dy,
+                *image.get_pixel(sx as u32, sy as u32),
+                opacity,
+            );
+        }
+    }
+}
+
+fn fill_rect(canvas: &mut RgbaImage, rect: Rect, color: Rgba<u8>, opacity: f32) {
+    for y in rect.y..rect.bottom() {
+        for x in rect.x..rect.right() {
+            blend(canvas, x, y, color, opacity);
+        }
+    }
+}
+
+fn stroke_rect(canvas: &mut RgbaImage, rect: Rect, color: Rgba<u8>, opacity: f32) {
+    for y in rect.y..rect.bottom() {
+        for x in rect.x..rect.right() {
+            if x == rect.x || y == rect.y || x == rect.right() - 1 || y == rect.bottom() - 1 {
+                blend(canvas, x, y, color, opacity);
+            }
+        }
+    }
+}
+
+fn draw_ellipse(canvas: &mut RgbaImage, rect: Rect, color: Rgba<u8>, fill_opacity: f32, line_opacity: f32) {
+    let (rx, ry) = (rect.width as f32 / 2., rect.height as f32 / 2.);
+    let inside =
+        |x: f32, y: f32, rx: f32, ry: f32| rx > 0. && ry > 0. && (x * x) / (rx * rx) + (y * y) / (ry * ry) <= 1.;
+    for y in rect.y..rect.bottom() {
+        for x in rect.x..rect.right() {
+            let px = (x - rect.x) as f32 + 0.5 - rx;
+            let py = (y - rect.y) as f32 + 0.5 - ry;
+            if !inside(px, py, rx, ry) {
+                continue;
+            }
+            if inside(px, py, rx - 1., ry - 1.) {
+                blend(canvas, x, y, color, fill_opacity);
+            } else {
+                blend(canvas, x, y, color, line_opacity);
+            }
+        }
+    }
+}
+
+fn draw_cross(canvas: &mut RgbaImage, rect: Rect, color: Rgba<u8>, opacity: f32) {
+    let steps = rect.width.max(rect.height);
+    for i in 0..steps {
+        let x = rect.x + i * rect.width / steps;
+        let y = i * rect.height / steps;
+        blend(canvas, x, rect.y + y, color, opacity);
+        blend(canvas, x, rect.bottom() - 1 - y, color, opacity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ProjectBuilder;
+
+    #[test]
+    fn missing_background() {
+        let mut builder = ProjectBuilder::new();
+        builder.level("Level_0", 16, 8).finish();
+        let mut project = builder.build();
+        let level = &mut project.levels[0];
+        level.bg_color = "#FF0000".parse().unwrap();
+        level.bg_rel_path = Some("missing.png".into());
+
+        let mut renderer = Renderer::new(&project, "does/not/exist");
+        let image = renderer.render_level(&project.levels[0]).unwrap();
+        assert_eq!((image.width(), image.height()), (16, 8));
+        assert_eq!(image.get_pixel(15, 7), &Rgba([255, 0, 0, 255]));
+
+        let warnings = renderer.take_warnings();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].level, "Level_0");
+        assert!(warnings[0]
+            .message
+            .starts_with("skipped background image `missing.png`: "));
+        assert!(renderer.take_warnings().is_empty());
+    }
+}
@@ -11,6 +11,7 @@
 //     let model: [object Object] = serde_json::from_str(&json).unwrap();
 // }
 
+use crate::Color;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -19,7 +20,7 @@ use std::collections::HashMap;
 /// This is the root of any Project JSON file. It contains:  - the project settings, - an
 /// array of levels, - a group of definitions (that can probably be safely ignored for most
 /// users).
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Project {
     /// Number of backup files to keep, if the `backupOnSave` is TRUE
     #[serde(rename = "backupLimit")]
@@ -31,7 +32,7 @@ pub struct Project {
 
     /// Project background color
     #[serde(rename = "bgColor")]
-    pub bg_color: String,
+    pub bg_color: Color,
 
     /// Default grid size for new layers
     #[serde(rename = "defaultGridSize")]
@@ -39,7 +40,7 @@ pub struct Project {
 
     /// Default background color of levels
     #[serde(rename = "defaultLevelBgColor")]
-    pub default_level_bg_color: String,
+    pub default_level_bg_color: Color,
 
     /// Default new level height
     #[serde(rename = "defaultLevelHeight")]
@@ -125,6 +126,10 @@ pub struct Project {
     /// space). Possible values: `Free`, `GridVania`, `LinearHorizontal`, `LinearVertical`
     #[serde(rename = "worldLayout")]
     pub world_layout: WorldLayout,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// A structure containing all the definitions of this project
@@ -135,7 +140,7 @@ pub struct Project {
 /// from definitions is often duplicated in fields prefixed with a double underscore (eg.
 /// `__identifier` or `__type`).  The 2 only definition types you might need here are
 /// **Tilesets** and **Enums**.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Definitions {
     /// All entities definitions, including their custom fields
     #[serde(rename = "entities")]
@@ -161,13 +166,17 @@ pub struct Definitions {
     /// All tilesets
     #[serde(rename = "tilesets")]
     pub tilesets: Vec<TilesetDefinition>,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntityDefinition {
     /// Base entity color
     #[serde(rename = "color")]
-    pub color: String,
+    pub color: Color,
 
     /// Array of field definitions
     #[serde(rename = "fieldDefs")]
@@ -255,11 +264,15 @@ pub struct EntityDefinition {
     /// Pixel width
     #[serde(rename = "width")]
     pub width: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// This section is mostly only intended for the LDtk editor app itself. You can safely
 /// ignore it.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldDefinition {
     /// Human readable value type (eg. `Int`, `Float`, `Point`, etc.). If the field is an array,
     /// this field will look like `Array<...>` (eg. `Array<Int>`, `Array<Point>` etc.)
@@ -336,9 +349,13 @@ pub struct FieldDefinition {
     /// Unique Int identifier
     #[serde(rename = "uid")]
     pub uid: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnumDefinition {
     #[serde(rename = "externalFileChecksum")]
     pub external_file_checksum: Option<String>,
@@ -362,9 +379,13 @@ pub struct EnumDefinition {
     /// All possible enum values, with their optional Tile infos.
     #[serde(rename = "values")]
     pub values: Vec<EnumValueDefinition>,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EnumValueDefinition {
     /// An array of 4 Int values that refers to the tile in the tileset image: `[ x, y, width,
     /// height ]`
@@ -373,7 +394,8 @@ pub struct EnumValueDefinition {
 
     /// Optional color
     #[serde(rename = "color")]
-    pub color: i64,
+    #[serde(with = "crate::color::int")]
+    pub color: Color,
 
     /// Enum value
     #[serde(rename = "id")]
@@ -382,9 +404,13 @@ pub struct EnumValueDefinition {
     /// The optional ID of the tile
     #[serde(rename = "tileId")]
     pub tile_id: Option<i64>,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LayerDefinition {
     /// Type of the layer (*IntGrid, Entities, Tiles or AutoLayer*)
     #[serde(rename = "__type")]
@@ -462,9 +488,13 @@ pub struct LayerDefinition {
     /// Unique Int identifier
     #[serde(rename = "uid")]
     pub uid: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AutoLayerRuleGroup {
     #[serde(rename = "active")]
     pub active: bool,
@@ -483,12 +513,16 @@ pub struct AutoLayerRuleGroup {
 
     #[serde(rename = "uid")]
     pub uid: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// This complex section isn't meant to be used by game devs at all, as these rules are
 /// completely resolved internally by the editor before any saving. You should just ignore
 /// this part.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AutoLayerRuleDefinition {
     /// If FALSE, the rule effect isn't applied, and no tiles are generated.
     #[serde(rename = "active")]
@@ -567,13 +601,17 @@ pub struct AutoLayerRuleDefinition {
     /// Y cell coord modulo
     #[serde(rename = "yModulo")]
     pub y_modulo: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// IntGrid value definition
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IntGridValueDefinition {
     #[serde(rename = "color")]
-    pub color: String,
+    pub color: Color,
 
     /// Unique String identifier
     #[serde(rename = "identifier")]
@@ -582,12 +620,16 @@ pub struct IntGridValueDefinition {
     /// The IntGrid value itself
     #[serde(rename = "value")]
     pub value: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// The `Tileset` definition is the most important part among project definitions. It
 /// contains some extra informations about each integrated tileset. If you only had to parse
 /// one definition section, that would be the one.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TilesetDefinition {
     /// Grid-based height
     #[serde(rename = "__cHei")]
@@ -649,6 +691,10 @@ pub struct TilesetDefinition {
     /// Unique Intidentifier
     #[serde(rename = "uid")]
     pub uid: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// This section contains all the level data. It can be found in 2 distinct forms, depending
@@ -659,12 +705,12 @@ pub struct TilesetDefinition {
 /// except heavy sections, like the `layerInstances` array (which will be null). The
 /// `externalRelPath` string points to the `ldtkl` file.  A `ldtkl` file is just a JSON file
 /// containing exactly what is described below.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Level {
     /// Background color of the level (same as `bgColor`, except the default value is
     /// automatically used here if its value is `null`)
     #[serde(rename = "__bgColor")]
-    pub bg_color: String,
+    pub bg_color: Color,
 
     /// Position informations of the background image, if there is one.
     #[serde(rename = "__bgPos")]
@@ -679,7 +725,7 @@ pub struct Level {
     /// Background color of the level. If `null`, the project `defaultLevelBgColor` should be
     /// used.
     #[serde(rename = "bgColor")]
-    pub level_bg_color: Option<String>,
+    pub level_bg_color: Option<Color>,
 
     /// Background image X pivot (0-1)
     #[serde(rename = "bgPivotX")]
@@ -743,10 +789,14 @@ pub struct Level {
     /// World Y coordinate in pixels
     #[serde(rename = "worldY")]
     pub world_y: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Level background image position info
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LevelBackgroundPosition {
     /// An array of 4 float values describing the cropped sub-rectangle of the displayed
     /// background image. This cropping happens when original is larger than the level bounds.
@@ -763,9 +813,13 @@ pub struct LevelBackgroundPosition {
     /// **cropped** background image, depending on `bgPos` option.
     #[serde(rename = "topLeftPx")]
     pub top_left_px: Vec<i64>,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct FieldInstance {
     /// Field definition identifier
     #[serde(rename = "__identifier")]
@@ -787,9 +841,13 @@ pub struct FieldInstance {
     /// Editor internal raw values
     #[serde(rename = "realEditorValues")]
     pub real_editor_values: Vec<Option<serde_json::Value>>,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LayerInstance {
     /// Grid-based height
     #[serde(rename = "__cHei")]
@@ -889,10 +947,14 @@ pub struct LayerInstance {
     /// Layer instance visibility
     #[serde(rename = "visible")]
     pub visible: bool,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// This structure represents a single tile from a given Tileset.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TileInstance {
     /// Internal data used by the editor.<br/>  For auto-layer tiles: `[ruleId, coordId]`.<br/>
     /// For tile-layer tiles: `[coordId]`.
@@ -917,9 +979,13 @@ pub struct TileInstance {
     /// The *Tile ID* in the corresponding tileset.
     #[serde(rename = "t")]
     pub t: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntityInstance {
     /// Grid-based coordinates (`[x,y]` format)
     #[serde(rename = "__grid")]
@@ -960,10 +1026,14 @@ pub struct EntityInstance {
     /// definition.
     #[serde(rename = "width")]
     pub width: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Tile data in an Entity instance
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct EntityInstanceTile {
     /// An array of 4 Int values that refers to the tile in the tileset image: `[ x, y, width,
     /// height ]`
@@ -973,10 +1043,14 @@ pub struct EntityInstanceTile {
     /// Tileset ID
     #[serde(rename = "tilesetUid")]
     pub tileset_uid: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// IntGrid value instance
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct IntGridValueInstance {
     /// Coordinate ID in the layer grid
     #[serde(rename = "coordId")]
@@ -985,10 +1059,14 @@ pub struct IntGridValueInstance {
     /// IntGrid value
     #[serde(rename = "v")]
     pub v: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Nearby level info
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct NeighbourLevel {
     /// A single lowercase character tipping on the level location (`n`orth, `s`outh, `w`est,
     /// `e`ast).
@@ -997,11 +1075,15 @@ pub struct NeighbourLevel {
 
     #[serde(rename = "levelUid")]
     pub level_uid: i64,
+
+    /// Fields unknown to this version of the schema, written back as they were read.
+    #[serde(flatten)]
+    pub unknown_fields: serde_json::Map<String, serde_json::Value>,
 }
 
 /// Possible values: `Hidden`, `ValueOnly`, `NameAndValue`, `EntityTile`, `Points`,
 /// `PointStar`, `PointPath`, `PointPathLoop`, `RadiusPx`, `RadiusGrid`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EditorDisplayMode {
     #[serde(rename = "EntityTile")]
     EntityTile,
@@ -1035,7 +1117,7 @@ pub enum EditorDisplayMode {
 }
 
 /// Possible values: `Above`, `Center`, `Beneath`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum EditorDisplayPos {
     #[serde(rename = "Above")]
     Above,
@@ -1047,7 +1129,7 @@ pub enum EditorDisplayPos {
     Center,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TextLanguageMode {
     #[serde(rename = "LangC")]
     LangC,
@@ -1078,7 +1160,7 @@ pub enum TextLanguageMode {
 }
 
 /// Possible values: `DiscardOldOnes`, `PreventAdding`, `MoveLastOne`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LimitBehavior {
     #[serde(rename = "DiscardOldOnes")]
     DiscardOldOnes,
@@ -1092,7 +1174,7 @@ pub enum LimitBehavior {
 
 /// If TRUE, the maxCount is a "per world" limit, if FALSE, it's a "per level". Possible
 /// values: `PerLayer`, `PerLevel`, `PerWorld`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum LimitScope {
     #[serde(rename = "PerLayer")]
     PerLayer,
@@ -1105,7 +1187,7 @@ pub enum LimitScope {
 }
 
 /// Possible values: `Rectangle`, `Ellipse`, `Tile`, `Cross`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum RenderMode {
     #[serde(rename = "Cross")]
     Cross,
@@ -1121,7 +1203,7 @@ pub enum RenderMode {
 }
 
 /// Possible values: `Cover`, `FitInside`, `Repeat`, `Stretch`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TileRenderMode {
     #[serde(rename = "Cover")]
     Cover,
@@ -1137,7 +1219,7 @@ pub enum TileRenderMode {
 }
 
 /// Checker mode Possible values: `None`, `Horizontal`, `Vertical`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Checker {
     #[serde(rename = "Horizontal")]
     Horizontal,
@@ -1150,7 +1232,7 @@ pub enum Checker {
 }
 
 /// Defines how tileIds array is used Possible values: `Single`, `Stamp`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum TileMode {
     #[serde(rename = "Single")]
     Single,
@@ -1161,7 +1243,7 @@ pub enum TileMode {
 
 /// Type of the layer as Haxe Enum Possible values: `IntGrid`, `Entities`, `Tiles`,
 /// `AutoLayer`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Type {
     #[serde(rename = "AutoLayer")]
     AutoLayer,
@@ -1176,7 +1258,7 @@ pub enum Type {
     Tiles,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Flag {
     #[serde(rename = "DiscardPreCsvIntGrid")]
     DiscardPreCsvIntGrid,
@@ -1187,7 +1269,7 @@ pub enum Flag {
 
 /// "Image export" option when saving project. Possible values: `None`, `OneImagePerLayer`,
 /// `OneImagePerLevel`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ImageExportMode {
     #[serde(rename = "None")]
     None,
@@ -1199,7 +1281,7 @@ pub enum ImageExportMode {
     OneImagePerLevel,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum BgPos {
     #[serde(rename = "Contain")]
     Contain,
@@ -1216,7 +1298,7 @@ pub enum BgPos {
 
 /// An enum that describes how levels are organized in this project (ie. linearly or in a 2D
 /// space). Possible values: `Free`, `GridVania`, `LinearHorizontal`, `LinearVertical`
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum WorldLayout {
     #[serde(rename = "Free")]
     Free,
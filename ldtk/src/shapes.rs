@@ -0,0 +1,193 @@
+//! Entity fields the editor displays as shapes (points, paths and radii), resolved in world pixels.
+
+use crate::*;
+use serde_json::Value;
+
+/// An entity field resolved according to its editor display mode. Coordinates are world pixels, with cells
+/// resolved to their center.
+#[derive(Clone, Debug, PartialEq)]
+pub enum FieldShape {
+    /// Standalone points, from `Points` fields.
+    Points(Vec<[f64; 2]>),
+    /// Points each linked to the entity, from `PointStar` fields. The entity position comes first.
+    Star([f64; 2], Vec<[f64; 2]>),
+    /// A path starting at the entity, from `PointPath` fields.
+    Path(Vec<[f64; 2]>),
+    /// A closed path starting at the entity, from `PointPathLoop` fields. The last point links back to the first.
+    Loop(Vec<[f64; 2]>),
+    /// A radius in pixels, from `RadiusPx` and `RadiusGrid` fields.
+    Radius(f64),
+}
+
+impl FieldInstance {
+    /// Cells held by a `Point` or `Array<Point>` field, skipping null values.
+    pub fn points(&self) -> Vec<(i64, i64)> {
+        let point = |v: &Value| Some((v.get("cx")?.as_i64()?, v.get("cy")?.as_i64()?));
+        match &self.value {
+            Some(Value::Array(values)) => values.iter().filter_map(point).collect(),
+            Some(value) => point(value).into_iter().collect(),
+            None => Vec::new(),
+        }
+    }
+}
+
+impl EntityInstance {
+    /// Position of the entity pivot in world pixels, `layer` being the layer of `level` holding it. Returns `None`
+    /// when `px` isn't a pair of coordinates.
+    pub fn world_position(&self, level: &Level, layer: &LayerInstance) -> Option<[f64; 2]> {
+        match self.px[..] {
+            [x, y] => Some([
+                (level.world_x + layer.px_total_offset_x + x) as f64,
+                (level.world_y + layer.px_total_offset_y + y) as f64,
+            ]),
+            _ => None,
+        }
+    }
+
+    /// Resolves the field `identifier` according to the display mode of its definition. Returns `None` for
+    /// unknown fields, fields displayed as values, null radii, and shapes starting at an entity with a malformed
+    /// `px`.
+    pub fn field_shape(
+        &self,
+        project: &Project,
+        level: &Level,
+        layer: &LayerInstance,
+        identifier: &str,
+    ) -> Option<FieldShape> {
+        let field = self.field_instances.iter().find(|f| f.identifier == identifier)?;
+        let def = project
+            .defs
+            .entities
+            .iter()
+            .find(|d| d.uid == self.def_uid)?
+            .field_defs
+            .iter()
+            .find(|d| d.uid == field.def_uid)?;
+
+        let grid = layer.grid_size as f64;
+        let origin_x = (level.world_x + layer.px_total_offset_x) as f64;
+        let origin_y = (level.world_y + layer.px_total_offset_y) as f64;
+        let points = || -> Vec<[f64; 2]> {
+            field
+                .points()
+                .into_iter()
+                .map(|(cx, cy)| [origin_x + (cx as f64 + 0.5) * grid, origin_y + (cy as f64 + 0.5) * grid])
+                .collect()
+        };
+        let path = || {
+            let mut path = vec![self.world_position(level, layer)?];
+            path.extend(points());
+            Some(path)
+        };
+
+        match def.editor_display_mode {
+            EditorDisplayMode::Points => Some(FieldShape::Points(points())),
+            EditorDisplayMode::PointStar => Some(FieldShape::Star(self.world_position(level, layer)?, points())),
+            EditorDisplayMode::PointPath => path().map(FieldShape::Path),
+            EditorDisplayMode::PointPathLoop => path().map(FieldShape::Loop),
+            EditorDisplayMode::RadiusPx => field.value.as_ref()?.as_f64().map(FieldShape::Radius),
+            EditorDisplayMode::RadiusGrid => field.value.as_ref()?.as_f64().map(|r| FieldShape::Radius(r * grid)),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A level at (100, 200) holding an entity at (8, 24) pixels, on a layer offset by (4, -4).
+    fn project() -> Project {
+        let mut builder = ProjectBuilder::new();
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            ..Default::default()
+        });
+        let fields = [
+            ("points", "Array<Point>", EditorDisplayMode::Points),
+            ("star", "Array<Point>", EditorDisplayMode::PointStar),
+            ("path", "Array<Point>", EditorDisplayMode::PointPath),
+            ("loop", "Array<Point>", EditorDisplayMode::PointPathLoop),
+            ("range", "Float", EditorDisplayMode::RadiusGrid),
+            ("reach", "Int", EditorDisplayMode::RadiusPx),
+            ("name", "String", EditorDisplayMode::NameAndValue),
+        ];
+        builder.add_entity(EntityDefinition {
+            identifier: "Patrol".into(),
+            field_defs: fields
+                .iter()
+                .map(|(identifier, ty, mode)| FieldDefinition {
+                    identifier: identifier.to_string(),
+                    field_definition_type: ty.to_string(),
+                    editor_display_mode: mode.clone(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        });
+
+        let mut level = builder.level("Level_0", 64, 64);
+        level.world_position(100, 200);
+        let entity = level.entity("Entities", "Patrol", 8, 24);
+        for field in &mut entity.field_instances {
+            match &field.identifier[..] {
+                "points" => field.set_value(json!([{ "cx": 1, "cy": 2 }, null, { "cx": 0, "cy": 0 }])),
+                "star" | "path" | "loop" => field.set_value(json!([{ "cx": 1, "cy": 2 }])),
+                "range" => field.set_value(json!(1.5)),
+                "reach" => field.set_value(json!(40)),
+                _ => {}
+            }
+        }
+        level.finish();
+
+        let mut project = builder.build();
+        let layer = &mut project.levels[0].layer_instances.as_mut().unwrap()[0];
+        layer.px_total_offset_x = 4;
+        layer.px_total_offset_y = -4;
+        project
+    }
+
+    fn shape(project: &Project, identifier: &str) -> Option<FieldShape> {
+        let level = &project.levels[0];
+        let layer = &level.layer_instances.as_ref().unwrap()[0];
+        layer.entity_instances[0].field_shape(project, level, layer, identifier)
+    }
+
+    #[test]
+    fn shapes() {
+        let mut project = project();
+        let entity = [112., 220.];
+        let cell = [128., 236.];
+
+        assert_eq!(
+            shape(&project, "points"),
+            Some(FieldShape::Points(vec![cell, [112., 204.]]))
+        );
+        assert_eq!(shape(&project, "star"), Some(FieldShape::Star(entity, vec![cell])));
+        assert_eq!(shape(&project, "path"), Some(FieldShape::Path(vec![entity, cell])));
+        assert_eq!(shape(&project, "loop"), Some(FieldShape::Loop(vec![entity, cell])));
+        assert_eq!(shape(&project, "range"), Some(FieldShape::Radius(24.)));
+        assert_eq!(shape(&project, "reach"), Some(FieldShape::Radius(40.)));
+        assert_eq!(shape(&project, "name"), None);
+        assert_eq!(shape(&project, "missing"), None);
+
+        let layer = &mut project.levels[0].layer_instances.as_mut().unwrap()[0];
+        layer.entity_instances[0].field_instances[4].value = None;
+        assert_eq!(shape(&project, "range"), None);
+    }
+
+    #[test]
+    fn malformed_position() {
+        let mut project = project();
+        let layer = &mut project.levels[0].layer_instances.as_mut().unwrap()[0];
+        layer.entity_instances[0].px = vec![8];
+
+        assert_eq!(shape(&project, "star"), None);
+        assert_eq!(shape(&project, "path"), None);
+        assert_eq!(shape(&project, "loop"), None);
+        assert!(matches!(shape(&project, "points"), Some(FieldShape::Points(_))));
+    }
+}
@@ -0,0 +1,452 @@
+//! Spatial lookups of levels and entities in world space, for gameplay queries like "enemies around the player".
+
+use crate::*;
+use std::collections::{BTreeSet, HashMap};
+
+/// Designates an entity by its level, its layer definition and its index in the layer.
+///
+/// Entities have no uid in this format, so removing an entity shifts the index of the ones after it in its layer,
+/// and refs to them then designate other entities. This includes removals done by
+/// [`Editor::add_entity`](crate::edit::Editor::add_entity) to enforce `maxCount`: call
+/// [`SpatialIndex::update_level`] after removals instead of keeping refs around.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityRef {
+    pub level_uid: i64,
+    pub layer_def_uid: i64,
+    pub index: usize,
+}
+
+impl EntityRef {
+    /// The entity this designates in `project`, if it still exists.
+    pub fn get<'a>(&self, project: &'a Project) -> Option<&'a EntityInstance> {
+        project
+            .get_level(self.level_uid)?
+            .layer_instances
+            .iter()
+            .flatten()
+            .find(|l| l.layer_def_uid == self.layer_def_uid)?
+            .entity_instances
+            .get(self.index)
+    }
+}
+
+/// Restricts queries to some entities. The default filter matches every entity.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Filter {
+    identifier: Option<String>,
+    tags: Vec<String>,
+}
+
+impl Filter {
+    /// Only matches entities of this definition.
+    pub fn identifier(mut self, identifier: &str) -> Self {
+        self.identifier = Some(identifier.into());
+        self
+    }
+
+    /// Only matches entities whose definition has this tag. Tags add up: every one of them is required.
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.into());
+        self
+    }
+
+    fn matches(&self, entity: &Indexed) -> bool {
+        self.identifier.iter().all(|i| *i == entity.identifier) && self.tags.iter().all(|t| entity.tags.contains(t))
+    }
+}
+
+struct Indexed {
+    bounds: Rect,
+    identifier: String,
+    tags: Vec<String>,
+}
+
+/// Buckets levels and entities by the world grid cells their bounds cover.
+///
+/// The index is a snapshot: after moving entities in the project, call [`SpatialIndex::update_entity`], and after
+/// adding or removing entities, which shifts the [`EntityRef`] indices, call [`SpatialIndex::update_level`].
+pub struct SpatialIndex {
+    cell_size: i64,
+    levels: Vec<(i64, Rect)>,
+    level_cells: HashMap<(i64, i64), Vec<usize>>,
+    entities: HashMap<EntityRef, Indexed>,
+    entity_cells: HashMap<(i64, i64), BTreeSet<EntityRef>>,
+}
+
+impl SpatialIndex {
+    /// Indexes every level and entity of `project`, with buckets of `cell_size` pixels. Buckets about the size of
+    /// the usual queries work best.
+    pub fn new(project: &Project, cell_size: i64) -> Self {
+        let mut index = Self {
+            cell_size: cell_size.max(1),
+            levels: project.levels.iter().map(|l| (l.uid, l.world_bounds())).collect(),
+            level_cells: HashMap::new(),
+            entities: HashMap::new(),
+            entity_cells: HashMap::new(),
+        };
+        index.rebuild_level_cells();
+        for level in &project.levels {
+            index.index_entities(project, level);
+        }
+        index
+    }
+
+    /// Re-indexes a level and all of its entities, e.g. after it moved or entities were added or removed.
+    pub fn update_level(&mut self, project: &Project, level_uid: i64) {
+        let stale: Vec<EntityRef> = self
+            .entities
+            .keys()
+            .filter(|e| e.level_uid == level_uid)
+            .copied()
+            .collect();
+        for entity in stale {
+            self.remove_entity(entity);
+        }
+        let level = match project.get_level(level_uid) {
+            Some(level) => level,
+            None => {
+                self.levels.retain(|(uid, _)| *uid != level_uid);
+                self.rebuild_level_cells();
+                return;
+            }
+        };
+
+        let bounds = level.world_bounds();
+        match self.levels.iter_mut().find(|(uid, _)| *uid == level_uid) {
+            Some(entry) => entry.1 = bounds,
+            None => self.levels.push((level_uid, bounds)),
+        }
+        self.rebuild_level_cells();
+        self.index_entities(project, level);
+    }
+
+    /// Re-indexes one entity, e.g. after it moved. Entities that no longer exist are dropped from the index.
+    pub fn update_entity(&mut self, project: &Project, entity: EntityRef) {
+        self.remove_entity(entity);
+        let level = match project.get_level(entity.level_uid) {
+            Some(level) => level,
+            None => return,
+        };
+        let layer = level
+            .layer_instances
+            .iter()
+            .flatten()
+            .find(|l| l.layer_def_uid == entity.layer_def_uid);
+        let layer = match layer {
+            Some(layer) => layer,
+            None => return,
+        };
+        if let Some(instance) = layer.entity_instances.get(entity.index) {
+            self.insert_entity(project, level, layer, entity, instance);
+        }
+    }
+
+    /// Indexes every entity of `level`, which must not be indexed yet.
+    fn index_entities(&mut self, project: &Project, level: &Level) {
+        for layer in level.layer_instances.iter().flatten() {
+            for (index, instance) in layer.entity_instances.iter().enumerate() {
+                let entity = EntityRef {
+                    level_uid: level.uid,
+                    layer_def_uid: layer.layer_def_uid,
+                    index,
+                };
+                self.insert_entity(project, level, layer, entity, instance);
+            }
+        }
+    }
+
+    fn insert_entity(
+        &mut self,
+        project: &Project,
+        level: &Level,
+        layer: &LayerInstance,
+        entity: EntityRef,
+        instance: &EntityInstance,
+    ) {
+        let (offset_x, offset_y) = (layer.px_total_offset_x, layer.px_total_offset_y);
+
+        let mut bounds = instance.bounds();
+        bounds.x += level.world_x + offset_x;
+        bounds.y += level.world_y + offset_y;
+        let tags = project
+            .defs
+            .entities
+            .iter()
+            .find(|d| d.uid == instance.def_uid)
+            .map(|d| d.tags.clone())
+            .unwrap_or_default();
+
+        for cell in self.cells(bounds) {
+            self.entity_cells.entry(cell).or_default().insert(entity);
+        }
+        self.entities.insert(
+            entity,
+            Indexed {
+                bounds,
+                identifier: instance.identifier.clone(),
+                tags,
+            },
+        );
+    }
+
+    /// World bounds of an indexed entity.
+    pub fn entity_bounds(&self, entity: EntityRef) -> Option<Rect> {
+        self.entities.get(&entity).map(|e| e.bounds)
+    }
+
+    /// Uids of the levels containing the world pixel (`x`, `y`), in project order.
+    pub fn levels_at(&self, x: i64, y: i64) -> Vec<i64> {
+        let mut found: Vec<usize> = self
+            .level_cells
+            .get(&self.cell_of(x, y))
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&i| self.levels[i].1.contains(x, y))
+            .collect();
+        found.sort_unstable();
+        found.into_iter().map(|i| self.levels[i].0).collect()
+    }
+
+    /// Entities whose bounds contain the world pixel (`x`, `y`).
+    pub fn entities_at(&self, x: i64, y: i64, filter: &Filter) -> Vec<EntityRef> {
+        self.query(Rect::new(x, y, 1, 1), filter, |bounds| bounds.contains(x, y))
+    }
+
+    /// Entities whose bounds overlap `rect`.
+    pub fn entities_in_rect(&self, rect: Rect, filter: &Filter) -> Vec<EntityRef> {
+        self.query(rect, filter, |bounds| bounds.intersection(&rect).is_some())
+    }
+
+    /// Entities whose bounds come within `radius` pixels of the world pixel (`x`, `y`).
+    pub fn entities_in_radius(&self, x: i64, y: i64, radius: i64, filter: &Filter) -> Vec<EntityRef> {
+        let area = Rect::new(x - radius, y - radius, 2 * radius + 1, 2 * radius + 1);
+        self.query(area, filter, |bounds| {
+            let dx = (bounds.x - x).max(x - (bounds.right() - 1)).max(0);
+            let dy = (bounds.y - y).max(y - (bounds.bottom() - 1)).max(0);
+            dx * dx + dy * dy <= radius * radius
+        })
+    }
+
+    fn query(&self, area: Rect, filter: &Filter, keep: impl Fn(&Rect) -> bool) -> Vec<EntityRef> {
+        let mut found = BTreeSet::new();
+        for cell in self.cells(area) {
+            for entity in self.entity_cells.get(&cell).into_iter().flatten() {
+                let indexed = &self.entities[entity];
+                if filter.matches(indexed) && keep(&indexed.bounds) {
+                    found.insert(*entity);
+                }
+            }
+        }
+        found.into_iter().collect()
+    }
+
+    fn remove_entity(&mut self, entity: EntityRef) {
+        if let Some(indexed) = self.entities.remove(&entity) {
+            for cell in self.cells(indexed.bounds) {
+                if let Some(bucket) = self.entity_cells.get_mut(&cell) {
+                    bucket.remove(&entity);
+                    if bucket.is_empty() {
+                        self.entity_cells.remove(&cell);
+                    }
+                }
+            }
+        }
+    }
+
+    fn rebuild_level_cells(&mut self) {
+        let mut cells: HashMap<(i64, i64), Vec<usize>> = HashMap::new();
+        for (i, (_, bounds)) in self.levels.iter().enumerate() {
+            for cell in self.cells(*bounds) {
+                cells.entry(cell).or_default().push(i);
+            }
+        }
+        self.level_cells = cells;
+    }
+
+    fn cell_of(&self, x: i64, y: i64) -> (i64, i64) {
+        (x.div_euclid(self.cell_size), y.div_euclid(self.cell_size))
+    }
+
+    /// Buckets covered by `rect`, at least one even for empty rectangles.
+    fn cells(&self, rect: Rect) -> Vec<(i64, i64)> {
+        let (left, top) = self.cell_of(rect.x, rect.y);
+        let (right, bottom) = self.cell_of(rect.x + (rect.width - 1).max(0), rect.y + (rect.height - 1).max(0));
+        (top..=bottom)
+            .flat_map(|y| (left..=right).map(move |x| (x, y)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two levels side by side, with entities of 16x16 pixels, and a layer offset on the second level.
+    fn project() -> (Project, i64, i64) {
+        let mut builder = ProjectBuilder::new();
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            ..Default::default()
+        });
+        for (identifier, tags) in &[
+            ("Enemy", vec!["hostile"]),
+            ("Boss", vec!["hostile", "big"]),
+            ("Coin", vec![]),
+        ] {
+            builder.add_entity(EntityDefinition {
+                identifier: identifier.to_string(),
+                width: 16,
+                height: 16,
+                tags: tags.iter().map(|t| t.to_string()).collect(),
+                ..Default::default()
+            });
+        }
+
+        let mut level = builder.level("West", 128, 128);
+        level.entity("Entities", "Enemy", 0, 0);
+        level.entity("Entities", "Coin", 56, 56);
+        level.entity("Entities", "Boss", 100, 20);
+        let west = level.finish();
+        let mut level = builder.level("East", 128, 128);
+        level.world_position(128, 0);
+        level.entity("Entities", "Enemy", 8, 8);
+        let east = level.finish();
+
+        let mut project = builder.build();
+        let layer = &mut project.levels[1].layer_instances.as_mut().unwrap()[0];
+        layer.px_total_offset_x = 4;
+        layer.px_total_offset_y = -4;
+        (project, west, east)
+    }
+
+    fn entity(project: &Project, level_uid: i64, index: usize) -> EntityRef {
+        EntityRef {
+            level_uid,
+            layer_def_uid: project.defs.layers[0].uid,
+            index,
+        }
+    }
+
+    #[test]
+    fn queries() {
+        let (project, west, east) = project();
+        // Buckets smaller than entities, so that entities and queries span several of them
+        let index = SpatialIndex::new(&project, 10);
+        let all = Filter::default();
+
+        assert_eq!(index.levels_at(127, 0), vec![west]);
+        assert_eq!(index.levels_at(128, 127), vec![east]);
+        assert!(index.levels_at(256, 0).is_empty());
+        assert_eq!(
+            index.entity_bounds(entity(&project, east, 0)),
+            Some(Rect::new(140, 4, 16, 16))
+        );
+
+        assert_eq!(index.entities_at(71, 71, &all), vec![entity(&project, west, 1)]);
+        assert!(index.entities_at(72, 71, &all).is_empty());
+        assert_eq!(index.entities_at(140, 19, &all), vec![entity(&project, east, 0)]);
+        assert!(index.entities_at(136, 8, &all).is_empty());
+
+        assert_eq!(
+            index.entities_in_rect(Rect::new(10, 10, 100, 20), &all),
+            vec![entity(&project, west, 0), entity(&project, west, 2)]
+        );
+        assert_eq!(
+            index.entities_in_rect(Rect::new(110, 0, 40, 24), &all),
+            vec![entity(&project, west, 2), entity(&project, east, 0)]
+        );
+
+        // The nearest pixels of the coin and the enemy are 22.6 and 35.4 pixels away
+        assert_eq!(
+            index.entities_in_radius(40, 40, 36, &all),
+            vec![entity(&project, west, 0), entity(&project, west, 1)]
+        );
+        assert_eq!(
+            index.entities_in_radius(40, 40, 35, &all),
+            vec![entity(&project, west, 1)]
+        );
+        assert_eq!(
+            index.entities_in_radius(40, 40, 23, &all),
+            vec![entity(&project, west, 1)]
+        );
+        assert!(index.entities_in_radius(40, 40, 22, &all).is_empty());
+    }
+
+    #[test]
+    fn filters() {
+        let (project, west, east) = project();
+        let index = SpatialIndex::new(&project, 64);
+        let everywhere = Rect::new(0, 0, 256, 128);
+
+        let hostile = Filter::default().tag("hostile");
+        assert_eq!(
+            index.entities_in_rect(everywhere, &hostile),
+            vec![
+                entity(&project, west, 0),
+                entity(&project, west, 2),
+                entity(&project, east, 0)
+            ]
+        );
+        let big = Filter::default().tag("hostile").tag("big");
+        assert_eq!(
+            index.entities_in_rect(everywhere, &big),
+            vec![entity(&project, west, 2)]
+        );
+        let enemies = Filter::default().identifier("Enemy");
+        assert_eq!(
+            index.entities_in_rect(everywhere, &enemies),
+            vec![entity(&project, west, 0), entity(&project, east, 0)]
+        );
+        let none = Filter::default().identifier("Coin").tag("hostile");
+        assert!(index.entities_in_rect(everywhere, &none).is_empty());
+    }
+
+    #[test]
+    fn updates() {
+        let (mut project, west, east) = project();
+        let mut index = SpatialIndex::new(&project, 32);
+        let all = Filter::default();
+        let coin = entity(&project, west, 1);
+
+        project.levels[0].layer_instances.as_mut().unwrap()[0].entity_instances[1].px = vec![200, 200];
+        index.update_entity(&project, coin);
+        assert!(index.entities_at(60, 60, &all).is_empty());
+        assert_eq!(index.entities_at(210, 210, &all), vec![coin]);
+
+        // Moving a level moves its entities, and removing one shifts the refs after it
+        project.levels[1].world_y = 128;
+        project.levels[1].layer_instances.as_mut().unwrap()[0]
+            .entity_instances
+            .clear();
+        project.levels[0].layer_instances.as_mut().unwrap()[0]
+            .entity_instances
+            .remove(0);
+        index.update_level(&project, east);
+        index.update_level(&project, west);
+        assert!(index.levels_at(130, 10).is_empty());
+        assert_eq!(index.levels_at(130, 130), vec![east]);
+        assert_eq!(
+            index.entities_in_rect(Rect::new(0, 0, 256, 256), &all),
+            vec![entity(&project, west, 0), entity(&project, west, 1)]
+        );
+        assert_eq!(
+            index.entity_bounds(entity(&project, west, 0)),
+            Some(Rect::new(200, 200, 16, 16))
+        );
+        assert_eq!(index.entity_bounds(entity(&project, west, 2)), None);
+
+        project.levels.remove(1);
+        index.update_level(&project, east);
+        assert!(index.levels_at(130, 130).is_empty());
+
+        // A fresh index agrees with the updated one
+        let fresh = SpatialIndex::new(&project, 32);
+        for (x, y) in &[(0, 0), (100, 20), (210, 210), (130, 130)] {
+            assert_eq!(fresh.entities_at(*x, *y, &all), index.entities_at(*x, *y, &all));
+            assert_eq!(fresh.levels_at(*x, *y), index.levels_at(*x, *y));
+        }
+    }
+}
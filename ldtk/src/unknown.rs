@@ -0,0 +1,172 @@
+//! Reporting of the fields kept in `unknown_fields`, which come from editor versions newer than this schema.
+
+use crate::*;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::{collections::BTreeMap, fmt};
+
+/// A field this schema doesn't know about, and how many objects of type `owner` have it.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct UnknownField {
+    pub owner: &'static str,
+    pub field: String,
+    pub count: usize,
+}
+
+impl fmt::Display for UnknownField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{} ({}x)", self.owner, self.field, self.count)
+    }
+}
+
+#[derive(Default)]
+struct Report(BTreeMap<(&'static str, String), usize>);
+
+impl Report {
+    fn add(&mut self, owner: &'static str, fields: &Map<String, Value>) {
+        for field in fields.keys() {
+            *self.0.entry((owner, field.clone())).or_default() += 1;
+        }
+    }
+}
+
+impl Project {
+    /// Lists the fields found in the file that this schema doesn't know about, sorted by owner type and field
+    /// name. They are written back on save, but their meaning is lost on this crate: a non-empty report means the
+    /// file uses features of a newer editor.
+    pub fn unknown_fields_report(&self) -> Vec<UnknownField> {
+        let mut report = Report::default();
+        report.add("Project", &self.unknown_fields);
+
+        let defs = &self.defs;
+        report.add("Definitions", &defs.unknown_fields);
+        for entity in &defs.entities {
+            report.add("EntityDefinition", &entity.unknown_fields);
+            for field in &entity.field_defs {
+                report.add("FieldDefinition", &field.unknown_fields);
+            }
+        }
+        for def in defs.enums.iter().chain(&defs.external_enums) {
+            report.add("EnumDefinition", &def.unknown_fields);
+            for value in &def.values {
+                report.add("EnumValueDefinition", &value.unknown_fields);
+            }
+        }
+        for layer in &defs.layers {
+            report.add("LayerDefinition", &layer.unknown_fields);
+            for value in &layer.int_grid_values {
+                report.add("IntGridValueDefinition", &value.unknown_fields);
+            }
+            for group in &layer.auto_rule_groups {
+                report.add("AutoLayerRuleGroup", &group.unknown_fields);
+                for rule in &group.rules {
+                    report.add("AutoLayerRuleDefinition", &rule.unknown_fields);
+                }
+            }
+        }
+        for field in &defs.level_fields {
+            report.add("FieldDefinition", &field.unknown_fields);
+        }
+        for tileset in &defs.tilesets {
+            report.add("TilesetDefinition", &tileset.unknown_fields);
+        }
+
+        for level in &self.levels {
+            report.add("Level", &level.unknown_fields);
+            if let Some(bg_pos) = &level.bg_pos {
+                report.add("LevelBackgroundPosition", &bg_pos.unknown_fields);
+            }
+            for neighbour in &level.neighbours {
+                report.add("NeighbourLevel", &neighbour.unknown_fields);
+            }
+            for field in &level.field_instances {
+                report.add("FieldInstance", &field.unknown_fields);
+            }
+            for layer in level.layer_instances.iter().flatten() {
+                report.add("LayerInstance", &layer.unknown_fields);
+                for tile in layer.grid_tiles.iter().chain(&layer.auto_layer_tiles) {
+                    report.add("TileInstance", &tile.unknown_fields);
+                }
+                for value in layer.int_grid.iter().flatten() {
+                    report.add("IntGridValueInstance", &value.unknown_fields);
+                }
+                for entity in &layer.entity_instances {
+                    report.add("EntityInstance", &entity.unknown_fields);
+                    if let Some(tile) = &entity.tile {
+                        report.add("EntityInstanceTile", &tile.unknown_fields);
+                    }
+                    for field in &entity.field_instances {
+                        report.add("FieldInstance", &field.unknown_fields);
+                    }
+                }
+            }
+        }
+
+        report
+            .0
+            .into_iter()
+            .map(|((owner, field), count)| UnknownField { owner, field, count })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn round_trip() {
+        let mut builder = ProjectBuilder::new();
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Door".into(),
+            width: 16,
+            height: 16,
+            ..Default::default()
+        });
+        for identifier in &["Level_0", "Level_1"] {
+            let mut level = builder.level(identifier, 64, 64);
+            level.entity("Entities", "Door", 0, 0);
+            level.finish();
+        }
+        let mut value = serde_json::to_value(builder.build()).unwrap();
+
+        value["iid"] = json!("a2c6f1e0-66b0-11ec-9cd6-c7d0bd8b5f8b");
+        value["toc"] = json!([]);
+        for level in value["levels"].as_array_mut().unwrap() {
+            level["iid"] = json!("a2c6f1e1-66b0-11ec-9cd6-c7d0bd8b5f8b");
+            let layer = &mut level["layerInstances"][0];
+            layer["iid"] = json!("a2c6f1e2-66b0-11ec-9cd6-c7d0bd8b5f8b");
+            layer["entityInstances"][0]["__worldX"] = json!(0);
+            layer["entityInstances"][0]["iid"] = json!("a2c6f1e3-66b0-11ec-9cd6-c7d0bd8b5f8b");
+        }
+        value["levels"][1]["__smartColor"] = json!("#ADADB5");
+
+        let project: Project = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(serde_json::to_value(&project).unwrap(), value);
+
+        let report: Vec<String> = project
+            .unknown_fields_report()
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+        assert_eq!(
+            report,
+            [
+                "EntityInstance.__worldX (2x)",
+                "EntityInstance.iid (2x)",
+                "LayerInstance.iid (2x)",
+                "Level.__smartColor (1x)",
+                "Level.iid (2x)",
+                "Project.iid (1x)",
+                "Project.toc (1x)",
+            ]
+        );
+    }
+}
@@ -0,0 +1,395 @@
+//! Consistency checks for projects edited outside of the editor, by hand or by tools.
+
+use crate::*;
+use serde::Serialize;
+use std::{collections::HashSet, fmt};
+
+/// Something wrong with a project, optionally located in a level.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Issue {
+    pub level: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.level {
+            Some(level) => write!(f, "level `{}`: {}", level, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+struct Issues(Vec<Issue>);
+
+impl Issues {
+    fn project(&mut self, message: String) {
+        self.0.push(Issue { level: None, message });
+    }
+
+    fn level(&mut self, level: &Level, message: String) {
+        self.0.push(Issue {
+            level: Some(level.identifier.clone()),
+            message,
+        });
+    }
+}
+
+impl Project {
+    /// Checks that uids are unique and below `next_uid`, that every uid reference points to an existing definition,
+    /// and that layer contents fit their level and definition. An empty list means the project is consistent.
+    pub fn validate(&self) -> Vec<Issue> {
+        let mut issues = Issues(Vec::new());
+        let defs = &self.defs;
+
+        let mut seen = HashSet::new();
+        for uid in self.defined_uids() {
+            if !seen.insert(uid) {
+                issues.project(format!("uid {} is used more than once", uid));
+            }
+            if uid >= self.next_uid {
+                issues.project(format!("uid {} is not below nextUid ({})", uid, self.next_uid));
+            }
+        }
+
+        let mut identifiers = HashSet::new();
+        for level in &self.levels {
+            if !identifiers.insert(&level.identifier) {
+                issues.level(level, "identifier is used by another level".into());
+            }
+        }
+
+        let tileset_exists = |uid: i64| defs.tilesets.iter().any(|t| t.uid == uid);
+        for layer in &defs.layers {
+            for uid in layer.tileset_def_uid.iter().chain(&layer.auto_tileset_def_uid) {
+                if !tileset_exists(*uid) {
+                    issues.project(format!("layer `{}` uses missing tileset {}", layer.identifier, uid));
+                }
+            }
+            if let Some(uid) = layer.auto_source_layer_def_uid {
+                if !defs.layers.iter().any(|l| l.uid == uid) {
+                    issues.project(format!(
+                        "layer `{}` uses missing source layer {}",
+                        layer.identifier, uid
+                    ));
+                }
+            }
+        }
+        for entity in &defs.entities {
+            if let Some(uid) = entity.tileset_id {
+                if !tileset_exists(uid) {
+                    issues.project(format!("entity `{}` uses missing tileset {}", entity.identifier, uid));
+                }
+            }
+        }
+
+        for level in &self.levels {
+            check_fields(&mut issues, level, "level", &level.field_instances, &defs.level_fields);
+
+            for layer in level.layer_instances.iter().flatten() {
+                let def = match defs.layers.iter().find(|d| d.uid == layer.layer_def_uid) {
+                    Some(def) => def,
+                    None => {
+                        let message = format!(
+                            "layer `{}` has missing definition {}",
+                            layer.identifier, layer.layer_def_uid
+                        );
+                        issues.level(level, message);
+                        continue;
+                    }
+                };
+                check_layer(&mut issues, self, level, def, layer);
+            }
+        }
+
+        issues.0
+    }
+}
+
+fn check_layer(issues: &mut Issues, project: &Project, level: &Level, def: &LayerDefinition, layer: &LayerInstance) {
+    let name = &layer.identifier;
+
+    if layer.level_id != level.uid {
+        issues.level(level, format!("layer `{}` belongs to level {}", name, layer.level_id));
+    }
+    let c_wid = (level.px_wid + layer.grid_size - 1) / layer.grid_size.max(1);
+    let c_hei = (level.px_hei + layer.grid_size - 1) / layer.grid_size.max(1);
+    if (layer.c_wid, layer.c_hei) != (c_wid, c_hei) {
+        let message = format!(
+            "layer `{}` is {}x{} cells, the level needs {}x{}",
+            name, layer.c_wid, layer.c_hei, c_wid, c_hei
+        );
+        issues.level(level, message);
+    }
+
+    if def.purple_type.name() == "IntGrid" {
+        let expected = (layer.c_wid * layer.c_hei) as usize;
+        if layer.int_grid_csv.len() != expected {
+            let message = format!(
+                "layer `{}` has {} IntGrid values instead of {}",
+                name,
+                layer.int_grid_csv.len(),
+                expected
+            );
+            issues.level(level, message);
+        }
+        let mut unknown: Vec<i64> = layer
+            .int_grid_csv
+            .iter()
+            .copied()
+            .filter(|&v| v != 0 && !def.int_grid_values.iter().any(|d| d.value == v))
+            .collect();
+        unknown.sort_unstable();
+        unknown.dedup();
+        for value in unknown {
+            issues.level(
+                level,
+                format!("layer `{}` uses undefined IntGrid value {}", name, value),
+            );
+        }
+    }
+
+    let override_uid = layer
+        .override_tileset_uid
+        .filter(|&uid| Some(uid) != layer.tileset_def_uid);
+    for uid in layer.tileset_def_uid.into_iter().chain(override_uid) {
+        if !project.defs.tilesets.iter().any(|t| t.uid == uid) {
+            issues.level(level, format!("layer `{}` uses missing tileset {}", name, uid));
+        }
+    }
+    let bounds = Rect::new(0, 0, layer.c_wid * layer.grid_size, layer.c_hei * layer.grid_size);
+    let (mut outside, mut malformed) = (0, 0);
+    for tile in layer.grid_tiles.iter().chain(&layer.auto_layer_tiles) {
+        match tile.px[..] {
+            [x, y] if !bounds.contains(x, y) => outside += 1,
+            [_, _] => (),
+            _ => malformed += 1,
+        }
+    }
+    if outside > 0 {
+        issues.level(
+            level,
+            format!("layer `{}` has {} tiles outside of the level", name, outside),
+        );
+    }
+    if malformed > 0 {
+        issues.level(
+            level,
+            format!("layer `{}` has {} tiles with an invalid position", name, malformed),
+        );
+    }
+
+    for entity in &layer.entity_instances {
+        let def = match project.defs.entities.iter().find(|d| d.uid == entity.def_uid) {
+            Some(def) => def,
+            None => {
+                let message = format!(
+                    "entity `{}` has missing definition {}",
+                    entity.identifier, entity.def_uid
+                );
+                issues.level(level, message);
+                continue;
+            }
+        };
+        match entity.px[..] {
+            [x, y] if !bounds.contains(x, y) => {
+                let message = format!(
+                    "entity `{}` at ({}, {}) is outside of the level",
+                    entity.identifier, x, y
+                );
+                issues.level(level, message);
+            }
+            [_, _] => (),
+            _ => {
+                let message = format!("entity `{}` has an invalid position {:?}", entity.identifier, entity.px);
+                issues.level(level, message);
+            }
+        }
+        let what = format!("entity `{}`", entity.identifier);
+        check_fields(issues, level, &what, &entity.field_instances, &def.field_defs);
+    }
+}
+
+fn check_fields(issues: &mut Issues, level: &Level, owner: &str, fields: &[FieldInstance], defs: &[FieldDefinition]) {
+    for field in fields {
+        if !defs.iter().any(|d| d.uid == field.def_uid) {
+            let message = format!(
+                "{} field `{}` has missing definition {}",
+                owner, field.identifier, field.def_uid
+            );
+            issues.level(level, message);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project() -> Project {
+        let mut builder = ProjectBuilder::new();
+        let tileset = builder.add_tileset(TilesetDefinition {
+            identifier: "Tiles".into(),
+            tile_grid_size: 16,
+            px_wid: 64,
+            px_hei: 64,
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Entities".into(),
+            purple_type: Type::Entities,
+            grid_size: 16,
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Ground".into(),
+            purple_type: Type::Tiles,
+            grid_size: 16,
+            tileset_def_uid: Some(tileset),
+            ..Default::default()
+        });
+        builder.add_layer(LayerDefinition {
+            identifier: "Walls".into(),
+            purple_type: Type::IntGrid,
+            grid_size: 16,
+            int_grid_values: vec![IntGridValueDefinition {
+                value: 1,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        builder.add_entity(EntityDefinition {
+            identifier: "Player".into(),
+            width: 16,
+            height: 16,
+            ..Default::default()
+        });
+
+        let mut level = builder.level("Start", 64, 64);
+        level.int_grid("Walls", 0, 0, 1);
+        level.tile("Ground", 1, 1, 5);
+        level.entity("Entities", "Player", 16, 16);
+        level.finish();
+        builder.build()
+    }
+
+    fn layer_mut<'a>(project: &'a mut Project, identifier: &str) -> &'a mut LayerInstance {
+        project.levels[0]
+            .layer_instances
+            .iter_mut()
+            .flatten()
+            .find(|l| l.identifier == identifier)
+            .unwrap()
+    }
+
+    fn messages(project: &Project) -> Vec<String> {
+        project.validate().iter().map(|issue| issue.to_string()).collect()
+    }
+
+    #[test]
+    fn consistent_project() {
+        assert_eq!(messages(&project()), Vec::<String>::new());
+    }
+
+    #[test]
+    fn uids_and_identifiers() {
+        let mut project = project();
+        project.defs.entities[0].uid = project.defs.layers[0].uid;
+        layer_mut(&mut project, "Entities").entity_instances.clear();
+        project.next_uid = project.levels[0].uid;
+        let mut level = project.levels[0].clone();
+        level.uid = project.next_uid + 1;
+        for layer in level.layer_instances.iter_mut().flatten() {
+            layer.level_id = level.uid;
+        }
+        project.levels.push(level);
+
+        assert_eq!(
+            messages(&project),
+            vec![
+                format!("uid {0} is not below nextUid ({0})", project.next_uid),
+                format!(
+                    "uid {} is not below nextUid ({})",
+                    project.next_uid + 1,
+                    project.next_uid
+                ),
+                format!("uid {} is used more than once", project.defs.layers[0].uid),
+                "level `Start`: identifier is used by another level".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn missing_references() {
+        let mut project = project();
+        project.defs.layers[1].tileset_def_uid = Some(100);
+        project.defs.entities[0].tileset_id = Some(101);
+        let ground = layer_mut(&mut project, "Ground");
+        ground.override_tileset_uid = Some(102);
+        let entities = layer_mut(&mut project, "Entities");
+        entities.entity_instances[0].def_uid = 103;
+        project.levels[0].field_instances.push(FieldInstance {
+            identifier: "lost".into(),
+            def_uid: 104,
+            ..Default::default()
+        });
+
+        assert_eq!(
+            messages(&project),
+            vec![
+                "layer `Ground` uses missing tileset 100",
+                "entity `Player` uses missing tileset 101",
+                "level `Start`: level field `lost` has missing definition 104",
+                "level `Start`: entity `Player` has missing definition 103",
+                "level `Start`: layer `Ground` uses missing tileset 102",
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_contents() {
+        let mut project = project();
+        let walls = layer_mut(&mut project, "Walls");
+        walls.int_grid_csv[1] = 7;
+        walls.int_grid_csv.pop();
+        let ground = layer_mut(&mut project, "Ground");
+        let mut tile = ground.grid_tiles[0].clone();
+        tile.px = vec![64, 0];
+        ground.grid_tiles.push(tile.clone());
+        tile.px = vec![16];
+        ground.grid_tiles.push(tile);
+        let entities = layer_mut(&mut project, "Entities");
+        let mut entity = entities.entity_instances[0].clone();
+        entity.px = vec![-1, 0];
+        entities.entity_instances.push(entity.clone());
+        entity.px = Vec::new();
+        entities.entity_instances.push(entity);
+
+        assert_eq!(
+            messages(&project),
+            vec![
+                "level `Start`: entity `Player` at (-1, 0) is outside of the level",
+                "level `Start`: entity `Player` has an invalid position []",
+                "level `Start`: layer `Ground` has 1 tiles outside of the level",
+                "level `Start`: layer `Ground` has 1 tiles with an invalid position",
+                "level `Start`: layer `Walls` has 15 IntGrid values instead of 16",
+                "level `Start`: layer `Walls` uses undefined IntGrid value 7",
+            ]
+        );
+    }
+
+    #[test]
+    fn layer_size() {
+        let mut project = project();
+        project.levels[0].px_wid = 80;
+
+        assert_eq!(
+            messages(&project),
+            vec![
+                "level `Start`: layer `Entities` is 4x4 cells, the level needs 5x4",
+                "level `Start`: layer `Ground` is 4x4 cells, the level needs 5x4",
+                "level `Start`: layer `Walls` is 4x4 cells, the level needs 5x4",
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Level placement in the world, following the project's world layout.
+
+use crate::*;
+
+impl Project {
+    /// Places levels according to `world_layout`: linear layouts put levels side by side in project order,
+    /// GridVania snaps levels to the world grid, and free layouts keep levels where they are. Neighbours are then
+    /// derived from the new positions.
+    pub fn arrange_levels(&mut self) {
+        match self.world_layout {
+            WorldLayout::LinearHorizontal => {
+                let mut x = 0;
+                for level in &mut self.levels {
+                    level.world_x = x;
+                    level.world_y = 0;
+                    x += level.px_wid;
+                }
+            }
+            WorldLayout::LinearVertical => {
+                let mut y = 0;
+                for level in &mut self.levels {
+                    level.world_x = 0;
+                    level.world_y = y;
+                    y += level.px_hei;
+                }
+            }
+            WorldLayout::GridVania => {
+                let (grid_w, grid_h) = (self.world_grid_width.max(1), self.world_grid_height.max(1));
+                for level in &mut self.levels {
+                    level.world_x = level.world_x.div_euclid(grid_w) * grid_w;
+                    level.world_y = level.world_y.div_euclid(grid_h) * grid_h;
+                }
+            }
+            WorldLayout::Free => {}
+        }
+        self.update_neighbours();
+    }
+
+    /// Recomputes `__neighbours` of every level: levels touching its north, south, west or east edge.
+    pub fn update_neighbours(&mut self) {
+        let bounds: Vec<(i64, Rect)> = self.levels.iter().map(|l| (l.uid, l.world_bounds())).collect();
+        for level in &mut self.levels {
+            let a = level.world_bounds();
+            level.neighbours = bounds
+                .iter()
+                .filter(|(uid, _)| *uid != level.uid)
+                .filter_map(|(uid, b)| {
+                    let overlap_x = a.x < b.right() && b.x < a.right();
+                    let overlap_y = a.y < b.bottom() && b.y < a.bottom();
+                    let dir = if overlap_x && b.bottom() == a.y {
+                        "n"
+                    } else if overlap_x && b.y == a.bottom() {
+                        "s"
+                    } else if overlap_y && b.right() == a.x {
+                        "w"
+                    } else if overlap_y && b.x == a.right() {
+                        "e"
+                    } else {
+                        return None;
+                    };
+                    Some(NeighbourLevel {
+                        dir: dir.into(),
+                        level_uid: *uid,
+                        unknown_fields: serde_json::Map::new(),
+                    })
+                })
+                .collect();
+        }
+    }
+
+    /// The level containing the world pixel (`x`, `y`). Levels listed first win where levels overlap.
+    pub fn level_at(&self, x: i64, y: i64) -> Option<&Level> {
+        self.levels.iter().find(|l| l.world_bounds().contains(x, y))
+    }
+}
+
+impl Level {
+    /// Pixel bounds of the level in world space.
+    pub fn world_bounds(&self) -> Rect {
+        Rect::new(self.world_x, self.world_y, self.px_wid, self.px_hei)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(layout: WorldLayout, levels: &[(i64, i64, i64, i64)]) -> Project {
+        let mut builder = ProjectBuilder::new();
+        for (i, &(x, y, px_wid, px_hei)) in levels.iter().enumerate() {
+            let mut level = builder.level(&format!("Level_{}", i), px_wid, px_hei);
+            level.world_position(x, y);
+            level.finish();
+        }
+        let mut project = builder.build();
+        project.world_layout = layout;
+        project.world_grid_width = 256;
+        project.world_grid_height = 128;
+        project
+    }
+
+    fn positions(project: &Project) -> Vec<(i64, i64)> {
+        project.levels.iter().map(|l| (l.world_x, l.world_y)).collect()
+    }
+
+    fn neighbours(project: &Project, level: usize) -> Vec<(String, i64)> {
+        let uids: Vec<i64> = project.levels.iter().map(|l| l.uid).collect();
+        project.levels[level]
+            .neighbours
+            .iter()
+            .map(|n| {
+                (
+                    n.dir.clone(),
+                    uids.iter().position(|&uid| uid == n.level_uid).unwrap() as i64,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn linear_horizontal() {
+        let mut project = project(
+            WorldLayout::LinearHorizontal,
+            &[(500, 500, 64, 32), (0, 0, 32, 48), (-50, 10, 16, 16)],
+        );
+        project.arrange_levels();
+        assert_eq!(positions(&project), vec![(0, 0), (64, 0), (96, 0)]);
+        assert_eq!(neighbours(&project, 0), vec![("e".to_string(), 1)]);
+        assert_eq!(
+            neighbours(&project, 1),
+            vec![("w".to_string(), 0), ("e".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn linear_vertical() {
+        let mut project = project(
+            WorldLayout::LinearVertical,
+            &[(500, 500, 64, 32), (0, 0, 32, 48), (-50, 10, 16, 16)],
+        );
+        project.arrange_levels();
+        assert_eq!(positions(&project), vec![(0, 0), (0, 32), (0, 80)]);
+        assert_eq!(
+            neighbours(&project, 1),
+            vec![("n".to_string(), 0), ("s".to_string(), 2)]
+        );
+    }
+
+    #[test]
+    fn grid_vania() {
+        let mut project = project(
+            WorldLayout::GridVania,
+            &[(-10, -300, 256, 128), (10, 10, 256, 128), (-256, -200, 256, 256)],
+        );
+        project.arrange_levels();
+        assert_eq!(positions(&project), vec![(-256, -384), (0, 0), (-256, -256)]);
+        assert_eq!(neighbours(&project, 0), vec![("s".to_string(), 2)]);
+        assert_eq!(neighbours(&project, 2), vec![("n".to_string(), 0)]);
+        // Levels 1 and 2 only share a corner
+        assert_eq!(neighbours(&project, 1), Vec::new());
+    }
+
+    #[test]
+    fn free_layout_neighbours() {
+        // A central level with partially overlapping levels on each side, and one touching only its corner
+        let mut project = project(
+            WorldLayout::Free,
+            &[
+                (0, 0, 64, 64),
+                (32, -32, 64, 32),
+                (-16, 64, 32, 16),
+                (-32, 48, 32, 64),
+                (64, 0, 16, 16),
+                (64, 64, 16, 16),
+            ],
+        );
+        project.arrange_levels();
+        assert_eq!(positions(&project)[5], (64, 64));
+        assert_eq!(
+            neighbours(&project, 0),
+            vec![
+                ("n".to_string(), 1),
+                ("s".to_string(), 2),
+                ("w".to_string(), 3),
+                ("e".to_string(), 4)
+            ]
+        );
+        assert_eq!(neighbours(&project, 5), Vec::new());
+    }
+
+    #[test]
+    fn level_at() {
+        let project = project(WorldLayout::Free, &[(-64, -64, 64, 64), (-32, -32, 64, 64)]);
+        let identifier = |x, y| project.level_at(x, y).map(|l| l.identifier.as_str());
+        assert_eq!(identifier(-64, -64), Some("Level_0"));
+        assert_eq!(identifier(-16, -16), Some("Level_0"));
+        assert_eq!(identifier(0, 0), Some("Level_1"));
+        assert_eq!(identifier(31, 31), Some("Level_1"));
+        assert_eq!(identifier(32, 0), None);
+        assert_eq!(identifier(-65, -64), None);
+    }
+}
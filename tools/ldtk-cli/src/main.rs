@@ -0,0 +1,272 @@
+//! Command-line access to the `ldtk` crate, for scripting asset pipelines. Every subcommand accepts `--json` to
+//! print machine-readable output instead of text.
+
+use ldtk::{Level, Project};
+use serde_json::{json, Value};
+use std::{
+    error::Error,
+    path::{Path, PathBuf},
+};
+
+static USAGE: &str = "\
+usage: ldtk <command> [--json] <args>...
+
+commands:
+  info <project>                                 list levels, layers and entity counts
+  validate <project>                             check uids, references and layer contents
+  export <project> <out-dir> [tiled|csv|json]    export levels to another format (default: tiled)
+  render <project> <out-dir>                     render levels to PNG
+  diff <old-project> <new-project>               list changes between two projects
+  convert <out-project> <input>...               import Tiled maps, or embed external levels of a project";
+
+enum Command {
+    Info(PathBuf),
+    Validate(PathBuf),
+    Export(PathBuf, PathBuf, Format),
+    Render(PathBuf, PathBuf),
+    Diff(PathBuf, PathBuf),
+    Convert(PathBuf, Vec<PathBuf>),
+}
+
+enum Format {
+    Tiled,
+    Csv,
+    Json,
+}
+
+struct App {
+    command: Command,
+    json: bool,
+}
+
+impl App {
+    fn from_args() -> Result<Self, Box<dyn Error>> {
+        let mut args = std::env::args().skip(1).collect::<Vec<_>>();
+        let json = args.iter().any(|a| a == "--json");
+        args.retain(|a| a != "--json");
+
+        let command = match args.iter().map(|s| s.as_str()).collect::<Vec<_>>().as_slice() {
+            ["info", project] => Command::Info(project.into()),
+            ["validate", project] => Command::Validate(project.into()),
+            ["export", project, out_dir] => Command::Export(project.into(), out_dir.into(), Format::Tiled),
+            ["export", project, out_dir, format] => {
+                let format = match *format {
+                    "tiled" => Format::Tiled,
+                    "csv" => Format::Csv,
+                    "json" => Format::Json,
+                    _ => return Err(format!("unknown export format `{}`", format).into()),
+                };
+                Command::Export(project.into(), out_dir.into(), format)
+            }
+            ["render", project, out_dir] => Command::Render(project.into(), out_dir.into()),
+            ["diff", old, new] => Command::Diff(old.into(), new.into()),
+            ["convert", output, inputs @ ..] if !inputs.is_empty() => {
+                Command::Convert(output.into(), inputs.iter().map(PathBuf::from).collect())
+            }
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        };
+
+        Ok(App { command, json })
+    }
+
+    /// Runs the command, returning whether it succeeded. Failed validations aren't errors, but still fail.
+    fn run(&self) -> Result<bool, Box<dyn Error>> {
+        match &self.command {
+            Command::Info(path) => {
+                let project = load(path)?;
+                self.print_info(&project);
+                Ok(true)
+            }
+            Command::Validate(path) => {
+                let issues = load(path)?.validate();
+                if self.json {
+                    println!("{}", json!({ "valid": issues.is_empty(), "issues": issues }));
+                } else if issues.is_empty() {
+                    println!("{} is valid", path.display());
+                } else {
+                    for issue in &issues {
+                        println!("{}", issue);
+                    }
+                }
+                Ok(issues.is_empty())
+            }
+            Command::Export(path, out_dir, format) => {
+                let project = load(path)?;
+                let written = match format {
+                    Format::Tiled => ldtk::export::tiled::export_project(&project, project_dir(path), out_dir)?,
+                    Format::Csv => ldtk::export::csv::export_project(&project, out_dir)?,
+                    Format::Json => ldtk::export::json::export_levels(&project, out_dir)?,
+                };
+                self.print_written(&written);
+                Ok(true)
+            }
+            Command::Render(path, out_dir) => {
+                let project = load(path)?;
+                let (written, warnings) = ldtk::render::export_pngs(&project, project_dir(path), out_dir)?;
+                if self.json {
+                    let written = written.iter().map(|p| p.to_string_lossy()).collect::<Vec<_>>();
+                    println!("{}", json!({ "written": written, "warnings": warnings }));
+                } else {
+                    self.print_written(&written);
+                    for warning in &warnings {
+                        eprintln!("warning: {}", warning);
+                    }
+                }
+                Ok(true)
+            }
+            Command::Diff(old, new) => {
+                let diff = ldtk::diff(&load(old)?, &load(new)?);
+                if self.json {
+                    println!("{}", serde_json::to_string(&diff)?);
+                } else {
+                    print!("{}", diff);
+                }
+                Ok(true)
+            }
+            Command::Convert(output, inputs) => {
+                let out_dir = project_dir(output);
+                let project = match inputs.as_slice() {
+                    [input] if input.extension() == Some("ldtk".as_ref()) => {
+                        let mut project = load(input)?;
+                        project.external_levels = false;
+                        for level in &mut project.levels {
+                            level.external_rel_path = None;
+                        }
+                        project
+                    }
+                    _ => ldtk::import::tiled::import_maps(out_dir, inputs)?,
+                };
+                std::fs::create_dir_all(out_dir)?;
+                let content = if project.minify_json {
+                    serde_json::to_string(&project)?
+                } else {
+                    serde_json::to_string_pretty(&project)?
+                };
+                std::fs::write(output, content).map_err(|e| format!("failed to write {}: {}", output.display(), e))?;
+                self.print_written(std::slice::from_ref(output));
+                Ok(true)
+            }
+        }
+    }
+
+    fn print_info(&self, project: &Project) {
+        let layers = |level: &Level| {
+            let mut layers = Vec::new();
+            for layer in level.layer_instances.iter().flatten() {
+                layers.push(json!({
+                    "identifier": layer.identifier,
+                    "type": layer.layer_instance_type,
+                    "entities": layer.entity_instances.len(),
+                    "tiles": layer.grid_tiles.len() + layer.auto_layer_tiles.len(),
+                }));
+            }
+            layers
+        };
+
+        if self.json {
+            let levels = project
+                .levels
+                .iter()
+                .map(|level| {
+                    json!({
+                        "identifier": level.identifier,
+                        "uid": level.uid,
+                        "worldX": level.world_x,
+                        "worldY": level.world_y,
+                        "pxWid": level.px_wid,
+                        "pxHei": level.px_hei,
+                        "layers": layers(level),
+                    })
+                })
+                .collect::<Vec<_>>();
+            let unknown = project.unknown_fields_report();
+            println!(
+                "{}",
+                json!({ "jsonVersion": project.json_version, "levels": levels, "unknownFields": unknown })
+            );
+            return;
+        }
+
+        println!("LDtk {} project, {} levels", project.json_version, project.levels.len());
+        for level in &project.levels {
+            println!(
+                "{} (uid {}) at ({}, {}), {}x{} px",
+                level.identifier, level.uid, level.world_x, level.world_y, level.px_wid, level.px_hei
+            );
+            for layer in layers(level) {
+                let count = |key: &str| layer[key].as_u64().unwrap_or(0);
+                print!(
+                    "  {} ({})",
+                    layer["identifier"].as_str().unwrap_or(""),
+                    layer["type"].as_str().unwrap_or("")
+                );
+                match (count("entities"), count("tiles")) {
+                    (0, 0) => println!(),
+                    (0, tiles) => println!(": {} tiles", tiles),
+                    (1, _) => println!(": 1 entity"),
+                    (entities, _) => println!(": {} entities", entities),
+                }
+            }
+        }
+
+        let unknown = project.unknown_fields_report();
+        if !unknown.is_empty() {
+            println!("Fields unknown to this version:");
+            for field in unknown {
+                println!("  {}", field);
+            }
+        }
+    }
+
+    fn print_written(&self, written: &[PathBuf]) {
+        if self.json {
+            let written = written
+                .iter()
+                .map(|p| Value::from(p.to_string_lossy()))
+                .collect::<Vec<_>>();
+            println!("{}", json!({ "written": written }));
+        } else {
+            for path in written {
+                println!("wrote {}", path.display());
+            }
+        }
+    }
+}
+
+fn project_dir(path: &Path) -> &Path {
+    path.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."))
+}
+
+/// Loads a project along with its external levels, reporting errors instead of panicking like `Project::new`.
+fn load(path: &Path) -> Result<Project, Box<dyn Error>> {
+    let read =
+        |path: &Path| std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e));
+    let parse_error = |path: &Path, e: serde_json::Error| format!("failed to parse {}: {}", path.display(), e);
+
+    let mut project: Project = serde_json::from_str(&read(path)?).map_err(|e| parse_error(path, e))?;
+    if project.external_levels {
+        for level in &mut project.levels {
+            if let Some(rel_path) = &level.external_rel_path {
+                let level_path = project_dir(path).join(rel_path);
+                *level = serde_json::from_str(&read(&level_path)?).map_err(|e| parse_error(&level_path, e))?;
+            }
+        }
+    }
+    Ok(project)
+}
+
+fn main() {
+    match App::from_args().and_then(|app| app.run()) {
+        Ok(true) => (),
+        Ok(false) => std::process::exit(1),
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
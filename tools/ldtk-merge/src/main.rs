@@ -0,0 +1,86 @@
+//! Git merge driver for `.ldtk` projects and `.ldtkl` external level files. Register it with:
+//!
+//! ```text
+//! git config merge.ldtk.driver "ldtk-merge %O %A %B"
+//! echo "*.ldtk merge=ldtk" >> .gitattributes
+//! echo "*.ldtkl merge=ldtk" >> .gitattributes
+//! ```
+//!
+//! The merged file replaces ours. Conflicts are listed on stderr and make the driver exit with a non-zero status, in
+//! which case git leaves the file marked as conflicted, holding our side of every conflicting change.
+
+use ldtk::{Conflict, Level, Project};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{error::Error, path::PathBuf};
+
+struct App {
+    base: PathBuf,
+    ours: PathBuf,
+    theirs: PathBuf,
+}
+
+impl App {
+    fn from_args() -> Result<Self, Box<dyn Error>> {
+        let args = std::env::args().skip(1).collect::<Vec<_>>();
+        match args.as_slice() {
+            [base, ours, theirs] => Ok(App {
+                base: base.into(),
+                ours: ours.into(),
+                theirs: theirs.into(),
+            }),
+            _ => {
+                eprintln!("usage: ldtk-merge <base> <ours> <theirs>");
+                std::process::exit(2);
+            }
+        }
+    }
+
+    fn run(&self) -> Result<Vec<Conflict>, Box<dyn Error>> {
+        let ours: serde_json::Value = self.read(&self.ours)?;
+        // Projects always have levels, even when they are stored in separate files.
+        if ours.get("levels").is_some() {
+            let (base, ours, theirs): (Project, Project, Project) =
+                (self.read(&self.base)?, serde_json::from_value(ours)?, self.read(&self.theirs)?);
+            let merged = ldtk::merge(&base, &ours, &theirs);
+            self.write(&merged.merged, merged.merged.minify_json)?;
+            Ok(merged.conflicts)
+        } else {
+            let (base, ours, theirs): (Level, Level, Level) =
+                (self.read(&self.base)?, serde_json::from_value(ours)?, self.read(&self.theirs)?);
+            let merged = ldtk::merge_level(&base, &ours, &theirs);
+            self.write(&merged.merged, false)?;
+            Ok(merged.conflicts)
+        }
+    }
+
+    fn read<T: DeserializeOwned>(&self, path: &PathBuf) -> Result<T, Box<dyn Error>> {
+        let content = std::fs::read_to_string(path).map_err(|e| format!("failed to read {}: {}", path.display(), e))?;
+        Ok(serde_json::from_str(&content).map_err(|e| format!("failed to parse {}: {}", path.display(), e))?)
+    }
+
+    fn write<T: Serialize>(&self, value: &T, minify: bool) -> Result<(), Box<dyn Error>> {
+        let content = if minify {
+            serde_json::to_string(value)?
+        } else {
+            serde_json::to_string_pretty(value)?
+        };
+        std::fs::write(&self.ours, content).map_err(|e| format!("failed to write {}: {}", self.ours.display(), e))?;
+        Ok(())
+    }
+}
+
+fn main() {
+    match App::from_args().and_then(|app| app.run()) {
+        Ok(conflicts) if conflicts.is_empty() => (),
+        Ok(conflicts) => {
+            for conflict in conflicts {
+                eprintln!("conflict: {}", conflict);
+            }
+            std::process::exit(1);
+        }
+        Err(err) => {
+            eprintln!("error: {}", err);
+            std::process::exit(2);
+        }
+    }
+}
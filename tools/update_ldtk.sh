@@ -1,5 +1,23 @@
 #!/bin/bash
 
-quicktype https://ldtk.io/files/JSON_SCHEMA.json --src-lang schema -o ldtk/src/schema.rs -t Project --visibility public --derive-debug --density normal
-sed -i -e 's/extern crate serde_derive;/use serde::{Deserialize, Serialize};/g' ldtk/src/schema.rs
+quicktype https://ldtk.io/files/JSON_SCHEMA.json --src-lang schema -o ldtk/src/schema.rs -t Project --visibility public --derive-debug --derive-clone --derive-partial-eq --density normal
+sed -i -e 's/extern crate serde_derive;/use crate::Color;\nuse serde::{Deserialize, Serialize};/g' ldtk/src/schema.rs
+# Colors are typed, enum value colors being stored as integers.
+sed -i \
+    -e 's/^    pub \(bg_color\|default_level_bg_color\|color\): String,$/    pub \1: Color,/' \
+    -e 's/^    pub level_bg_color: Option<String>,$/    pub level_bg_color: Option<Color>,/' \
+    -e 's/^    pub color: i64,$/    #[serde(with = "crate::color::int")]\n    pub color: Color,/' \
+    ldtk/src/schema.rs
+# Keep the fields of newer editor versions, so that they survive a load and save.
+awk '
+/^pub struct / { in_struct = 1 }
+/^}/ && in_struct {
+    print ""
+    print "    /// Fields unknown to this version of the schema, written back as they were read."
+    print "    #[serde(flatten)]"
+    print "    pub unknown_fields: serde_json::Map<String, serde_json::Value>,"
+    in_struct = 0
+}
+{ print }
+' ldtk/src/schema.rs > ldtk/src/schema.rs.tmp && mv ldtk/src/schema.rs.tmp ldtk/src/schema.rs
 rustfmt ldtk/src/schema.rs